@@ -1,3 +1,701 @@
+use clap::{Parser, Subcommand, ValueEnum};
+use ennio_lib::{
+    action::{
+        cache::{Cache, CACHE_DIR},
+        Status,
+    },
+    config::Config,
+    event::{JsonLinesObserver, Observers},
+    logs::{LogDir, LogObserver, LOG_DIR},
+    progress::{self, ProgressObserver},
+    report::{html, junit, prometheus},
+    run::{self, ActionRun, Run, RunStore, HISTORY_FILE},
+    secret,
+    trace::{FileExporter, OtlpExporter, TraceObserver},
+    vault,
+    workflow::Selection,
+};
+use std::{
+    cell::RefCell,
+    env,
+    fs::{self, OpenOptions, Permissions},
+    io::{self, IsTerminal, Write},
+    os::unix::fs::{OpenOptionsExt, PermissionsExt},
+    path::{Path, PathBuf},
+    process::{self, Command},
+    rc::Rc,
+};
+
+const SLOWEST_ACTIONS: usize = 5;
+
+#[derive(Parser)]
+#[command(version, about = "Tasks orchestrator")]
+struct Args {
+    #[command(subcommand)]
+    cmd: Cmd,
+}
+
+#[derive(Subcommand)]
+enum Cmd {
+    #[command(about = "Run a workflow")]
+    Run(Box<RunArgs>),
+    #[command(subcommand, about = "Manage the actions cache")]
+    Cache(CacheCmd),
+    #[command(subcommand, about = "Browse the runs history")]
+    History(HistoryCmd),
+    #[command(subcommand, about = "Manage encrypted secret files")]
+    Secret(SecretCmd),
+    #[command(subcommand, about = "Manage vault files")]
+    Vault(VaultCmd),
+    #[command(about = "Check a workflow file without running it")]
+    Validate { file: PathBuf },
+}
+
+#[derive(clap::Args)]
+struct RunArgs {
+    file: PathBuf,
+    #[arg(help = "Name of the workflow (default: default workflow of the file)")]
+    workflow: Option<String>,
+    #[arg(
+        long,
+        value_delimiter = ',',
+        help = "Run only actions with one of these tags"
+    )]
+    tags: Vec<String>,
+    #[arg(
+        long,
+        value_delimiter = ',',
+        help = "Skip actions with one of these tags"
+    )]
+    skip_tags: Vec<String>,
+    #[arg(
+        long,
+        value_name = "ACTION",
+        help = "Start at this action, reusing the outputs of the last run for previous actions"
+    )]
+    start_at: Option<String>,
+    #[arg(
+        long,
+        value_name = "RUN_ID",
+        conflicts_with = "start_at",
+        help = "Resume a run from its first failed action"
+    )]
+    resume: Option<String>,
+    #[arg(long, value_name = "FORMAT", help = "Stream the execution events")]
+    events: Option<EventsFormat>,
+    #[arg(
+        long,
+        value_name = "PATH",
+        requires = "events",
+        help = "Write the events to this file (default: stdout)"
+    )]
+    events_file: Option<PathBuf>,
+    #[arg(
+        long,
+        conflicts_with_all = ["log_dir", "log_max_age"],
+        help = "Do not write the actions output to log files"
+    )]
+    no_logs: bool,
+    #[arg(
+        long,
+        value_name = "DIR",
+        help = "Write the actions output to DIR/<run-id>/<action>.log (default: .ennio/logs next to the workflow file)"
+    )]
+    log_dir: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "RUNS",
+        default_value_t = 20,
+        help = "Number of runs to keep in the log directory"
+    )]
+    log_keep: usize,
+    #[arg(
+        long,
+        value_name = "DAYS",
+        help = "Remove the logs of runs older than DAYS days"
+    )]
+    log_max_age: Option<u64>,
+    #[arg(
+        long,
+        help = "Display the progress of the actions (plain lines if stdout is not a terminal)"
+    )]
+    progress: bool,
+    #[arg(long, value_name = "PATH", help = "Write a JUnit XML report")]
+    junit: Option<PathBuf>,
+    #[arg(long, value_name = "PATH", help = "Write an HTML report")]
+    html: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Write the run metrics in the Prometheus textfile format"
+    )]
+    metrics: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "URL",
+        help = "Export the run trace to this OTLP/HTTP endpoint, /v1/traces is appended if it has no path (e.g. http://localhost:4318)"
+    )]
+    otlp_endpoint: Option<String>,
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Append the run trace to this file as OTLP JSON"
+    )]
+    trace_file: Option<PathBuf>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum EventsFormat {
+    Jsonl,
+}
+
+#[derive(Subcommand)]
+enum CacheCmd {
+    #[command(about = "List the cache entries of a workflow file")]
+    List { file: PathBuf },
+    #[command(about = "Remove the cache entries of a workflow file")]
+    Prune {
+        file: PathBuf,
+        #[arg(
+            long,
+            value_name = "DAYS",
+            help = "Remove only the entries older than this number of days"
+        )]
+        max_age: Option<u64>,
+    },
+}
+
+#[derive(Subcommand)]
+enum HistoryCmd {
+    #[command(about = "List the runs of a workflow file")]
+    List {
+        file: PathBuf,
+        #[arg(short, long, help = "Show only the runs of this workflow")]
+        workflow: Option<String>,
+        #[arg(short = 'n', long, help = "Show only the last runs")]
+        limit: Option<usize>,
+    },
+    #[command(about = "Show the actions of a run")]
+    Show { file: PathBuf, id: String },
+}
+
+#[derive(Subcommand)]
+enum SecretCmd {
+    #[command(about = "Generate a key for encrypted secret files")]
+    Keygen {
+        #[arg(long, help = "Key file (default: ~/.ennio/secret.key)")]
+        key_file: Option<PathBuf>,
+        #[arg(long, help = "Overwrite the key file if it exists")]
+        force: bool,
+    },
+    #[command(about = "Encrypt a plaintext file into an encrypted secret file")]
+    Encrypt {
+        file: PathBuf,
+        #[arg(long, help = "Key file (default: ~/.ennio/secret.key)")]
+        key_file: Option<PathBuf>,
+        #[arg(
+            short,
+            long,
+            help = "Output file (default: input file with .enc extension)"
+        )]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum VaultCmd {
+    #[command(about = "Encrypt a plaintext file into a vault file")]
+    Encrypt {
+        file: PathBuf,
+        #[arg(short, long, help = "Output file (default: overwrite input file)")]
+        output: Option<PathBuf>,
+    },
+    #[command(about = "Decrypt a vault file")]
+    Decrypt {
+        file: PathBuf,
+        #[arg(short, long, help = "Output file (default: stdout)")]
+        output: Option<PathBuf>,
+    },
+    #[command(about = "Edit a vault file with $EDITOR")]
+    Edit { file: PathBuf },
+}
+
 fn main() {
-    println!("Hello, world!");
+    let args = Args::parse();
+    let res = match args.cmd {
+        Cmd::Run(args) => run(*args),
+        Cmd::Cache(cmd) => cache(cmd),
+        Cmd::History(cmd) => history(cmd),
+        Cmd::Secret(cmd) => secret(cmd),
+        Cmd::Vault(cmd) => vault(cmd),
+        Cmd::Validate { file } => validate(&file),
+    };
+    if let Err(err) = res {
+        eprintln!("{}", err);
+        process::exit(1);
+    }
+}
+
+fn run(args: RunArgs) -> Result<(), String> {
+    let file = &args.file;
+    let cfg = load_config(file)?;
+    let workflow_name = match args.workflow {
+        Some(workflow_name) => workflow_name,
+        None => cfg.default_workflow().map(String::from).ok_or_else(|| {
+            format!(
+                "No default workflow in {}, choose one of: {}",
+                file.display(),
+                cfg.workflow_names().join(", ")
+            )
+        })?,
+    };
+    let mut workflow = cfg
+        .workflow(&workflow_name)
+        .map_err(|err| err.to_string())?
+        .with_selection(Selection::new(args.tags, args.skip_tags));
+    let store = run_store(file);
+    if let Some(run_id) = args.resume {
+        let run = store.load(&run_id).map_err(|err| err.to_string())?;
+        if run.workflow() != workflow_name {
+            return Err(format!(
+                "Run '{}' is a run of workflow '{}'",
+                run_id,
+                run.workflow()
+            ));
+        }
+        let action_name = workflow
+            .resume_point(&run.outputs())
+            .map(String::from)
+            .ok_or_else(|| {
+                format!(
+                    "Run '{}' has no failed or skipped action to resume from",
+                    run_id
+                )
+            })?;
+        workflow = workflow
+            .with_start_at(action_name, run.outputs())
+            .map_err(|err| err.to_string())?;
+    } else if let Some(action_name) = args.start_at {
+        let previous_outputs = store
+            .last(&workflow_name)
+            .map_err(|err| err.to_string())?
+            .map(|run| run.outputs())
+            .unwrap_or_default();
+        workflow = workflow
+            .with_start_at(action_name, previous_outputs)
+            .map_err(|err| err.to_string())?;
+    }
+    let mut observers = Observers::new();
+    let events_to_stdout = args.events.is_some() && args.events_file.is_none();
+    if args.progress && events_to_stdout {
+        return Err(String::from(
+            "--progress cannot be used when the events are streamed on stdout",
+        ));
+    }
+    if args.progress {
+        let action_names = workflow
+            .steps()
+            .iter()
+            .map(|step| step.action().name().to_string())
+            .collect();
+        let width = env::var("COLUMNS")
+            .ok()
+            .and_then(|columns| columns.parse().ok())
+            .unwrap_or(progress::DEFAULT_WIDTH);
+        let observer =
+            ProgressObserver::new(io::stdout(), io::stdout().is_terminal(), action_names)
+                .with_width(width);
+        observers = observers.with(Rc::new(observer));
+    }
+    if let Some(EventsFormat::Jsonl) = args.events {
+        observers = match &args.events_file {
+            Some(path) => {
+                let file = fs::File::create(path)
+                    .map_err(|err| format!("Unable to create {}: {}", path.display(), err))?;
+                observers.with(Rc::new(JsonLinesObserver::new(file)))
+            }
+            None => observers.with(Rc::new(JsonLinesObserver::new(io::stdout()))),
+        };
+    }
+    let otlp_observer = args
+        .otlp_endpoint
+        .clone()
+        .map(|endpoint| Rc::new(TraceObserver::new(OtlpExporter::new(endpoint))));
+    if let Some(observer) = &otlp_observer {
+        observers = observers.with(observer.clone());
+    }
+    let file_observer = args
+        .trace_file
+        .clone()
+        .map(|path| Rc::new(TraceObserver::new(FileExporter::new(path))));
+    if let Some(observer) = &file_observer {
+        observers = observers.with(observer.clone());
+    }
+    let run = Run::start(workflow_name.clone()).with_vars(workflow.vars().clone());
+    let log_dir = LogDir::new(
+        args.log_dir
+            .clone()
+            .unwrap_or_else(|| file_dir(&args.file).join(LOG_DIR)),
+    );
+    let log_observer =
+        (!args.no_logs).then(|| Rc::new(LogObserver::new(log_dir.run_dir(run.id()))));
+    if let Some(observer) = &log_observer {
+        observers = observers.with(observer.clone());
+    }
+    let workflow = workflow.with_observers(observers);
+    let mut outputs = workflow.run();
+    let actions: Vec<ActionRun> = workflow
+        .steps()
+        .iter()
+        .chain(workflow.handlers())
+        .chain(workflow.finally())
+        .filter_map(|step| {
+            let action_name = step.action().name();
+            outputs
+                .remove(action_name)
+                .map(|output| ActionRun::new(action_name.into(), output))
+        })
+        .collect();
+    let trace_errors = otlp_observer
+        .iter()
+        .flat_map(|observer| observer.take_errors())
+        .chain(
+            file_observer
+                .iter()
+                .flat_map(|observer| observer.take_errors()),
+        );
+    for err in trace_errors {
+        eprintln!("{}", err);
+    }
+    if let Some(observer) = &log_observer {
+        for err in observer.take_errors() {
+            eprintln!("{}", err);
+        }
+        let max_age = args.log_max_age.map(run::days);
+        if let Err(err) = log_dir.prune(Some(args.log_keep), max_age) {
+            eprintln!("{}", err);
+        }
+    }
+    let run = run.finish(actions);
+    store.save(&run).map_err(|err| err.to_string())?;
+    if let Some(path) = &args.junit {
+        write(path, &junit::junit(&run))?;
+    }
+    if let Some(path) = &args.html {
+        write(path, &html::html(&run))?;
+    }
+    if let Some(path) = &args.metrics {
+        let history = store.list().map_err(|err| err.to_string())?;
+        let tmp_path = path.with_extension("prom.tmp");
+        write(&tmp_path, &prometheus::metrics(&run, &history))?;
+        fs::rename(&tmp_path, path)
+            .map_err(|err| format!("Unable to write {}: {}", path.display(), err))?;
+    }
+    let mut summary: Vec<String> = if args.progress {
+        vec![]
+    } else {
+        run.actions()
+            .iter()
+            .map(|action| format!("{}: {}", action.name(), action.output().status()))
+            .collect()
+    };
+    let slowest = run.slowest(SLOWEST_ACTIONS);
+    if !slowest.is_empty() {
+        let width = slowest
+            .iter()
+            .map(|action| action.name().len())
+            .max()
+            .unwrap_or_default();
+        summary.push(String::new());
+        summary.push(String::from("slowest actions:"));
+        summary.extend(slowest.into_iter().map(|action| {
+            format!(
+                "  {:<width$}  {:>9}",
+                action.name(),
+                run::format_duration(action.output().duration().unwrap_or_default()),
+                width = width
+            )
+        }));
+        summary.push(String::new());
+    }
+    summary.push(format!("run: {}", run.id()));
+    for line in summary {
+        if events_to_stdout {
+            eprintln!("{}", line);
+        } else {
+            println!("{}", line);
+        }
+    }
+    match run.status() {
+        Status::Failed => Err(format!("Workflow '{}' failed", workflow_name)),
+        _ => Ok(()),
+    }
+}
+
+fn cache(cmd: CacheCmd) -> Result<(), String> {
+    match cmd {
+        CacheCmd::List { file } => {
+            let cache = Cache::new(file_dir(&file).join(CACHE_DIR));
+            let entries = cache.entries().map_err(|err| err.to_string())?;
+            println!("{:<64} {:<21} {:<10} FILES", "KEY", "CREATED AT", "STATUS");
+            for entry in entries {
+                println!(
+                    "{:<64} {:<21} {:<10} {}",
+                    entry.key(),
+                    run::format_timestamp(entry.created_at()),
+                    entry.output().status().to_string(),
+                    entry.files().len()
+                );
+            }
+            Ok(())
+        }
+        CacheCmd::Prune { file, max_age } => {
+            let cache = Cache::new(file_dir(&file).join(CACHE_DIR));
+            let max_age = max_age.map(run::days);
+            let pruned = cache.prune(max_age).map_err(|err| err.to_string())?;
+            println!("{} cache entries removed", pruned.len());
+            Ok(())
+        }
+    }
+}
+
+fn history(cmd: HistoryCmd) -> Result<(), String> {
+    match cmd {
+        HistoryCmd::List {
+            file,
+            workflow,
+            limit,
+        } => {
+            let runs: Vec<Run> = run_store(&file)
+                .list()
+                .map_err(|err| err.to_string())?
+                .into_iter()
+                .filter(|run| workflow.as_ref().is_none_or(|name| run.workflow() == name))
+                .collect();
+            let skipped = limit.map_or(0, |limit| runs.len().saturating_sub(limit));
+            println!(
+                "{:<15} {:<20} {:<10} {:<21} DURATION",
+                "ID", "WORKFLOW", "STATUS", "STARTED AT"
+            );
+            for run in runs.iter().skip(skipped) {
+                println!(
+                    "{:<15} {:<20} {:<10} {:<21} {}",
+                    run.id(),
+                    run.workflow(),
+                    run.status().to_string(),
+                    run::format_timestamp(run.started_at()),
+                    run::format_duration(run.duration())
+                );
+            }
+            Ok(())
+        }
+        HistoryCmd::Show { file, id } => {
+            let run = run_store(&file).load(&id).map_err(|err| err.to_string())?;
+            println!("id: {}", run.id());
+            println!("workflow: {}", run.workflow());
+            println!("status: {}", run.status());
+            println!("started at: {}", run::format_timestamp(run.started_at()));
+            println!("finished at: {}", run::format_timestamp(run.finished_at()));
+            println!("duration: {}", run::format_duration(run.duration()));
+            let mut var_names: Vec<&String> = run.vars().keys().collect();
+            var_names.sort();
+            for var_name in var_names {
+                println!("vars.{}: {}", var_name, run.vars()[var_name]);
+            }
+            for action in run.actions() {
+                let output = action.output();
+                let duration = output
+                    .duration()
+                    .map(run::format_duration)
+                    .unwrap_or_default();
+                println!();
+                println!("{}: {} {}", action.name(), output.status(), duration);
+                let mut var_names: Vec<&String> = output.vars().keys().collect();
+                var_names.sort();
+                for var_name in var_names {
+                    let val = output.vars()[var_name].to_string();
+                    println!("  {}: {}", var_name, val.trim_end().replace('\n', "\n    "));
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+fn secret(cmd: SecretCmd) -> Result<(), String> {
+    match cmd {
+        SecretCmd::Keygen { key_file, force } => {
+            let key_file = key_file_or_default(key_file)?;
+            if key_file.exists() && !force {
+                return Err(format!(
+                    "{} already exists, use --force to overwrite it",
+                    key_file.display()
+                ));
+            }
+            if let Some(dir) = key_file.parent() {
+                fs::create_dir_all(dir)
+                    .map_err(|err| format!("Unable to create {}: {}", dir.display(), err))?;
+            }
+            OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .mode(0o600)
+                .open(&key_file)
+                .and_then(|mut file| {
+                    file.set_permissions(Permissions::from_mode(0o600))?;
+                    file.write_all(secret::generate_key().as_bytes())
+                })
+                .map_err(|err| format!("Unable to write {}: {}", key_file.display(), err))?;
+            println!("Key written to {}", key_file.display());
+            Ok(())
+        }
+        SecretCmd::Encrypt {
+            file,
+            key_file,
+            output,
+        } => {
+            let key = read(&key_file_or_default(key_file)?)?;
+            let plaintext = read(&file)?;
+            let content = secret::encrypt(&key, plaintext.trim_end_matches('\n'))
+                .map_err(|err| err.to_string())?;
+            let output = output.unwrap_or_else(|| {
+                let mut output = file.into_os_string();
+                output.push(".enc");
+                PathBuf::from(output)
+            });
+            write(&output, &content)
+        }
+    }
+}
+
+fn key_file_or_default(key_file: Option<PathBuf>) -> Result<PathBuf, String> {
+    match key_file {
+        Some(key_file) => Ok(key_file),
+        None => secret::default_key_file().map_err(|err| err.to_string()),
+    }
+}
+
+fn validate(file: &Path) -> Result<(), String> {
+    let cfg = load_config(file)?;
+    let errs: Vec<String> = cfg
+        .workflow_names()
+        .into_iter()
+        .filter_map(|workflow_name| cfg.workflow(workflow_name).err())
+        .map(|err| err.to_string())
+        .collect();
+    if !errs.is_empty() {
+        return Err(errs.join("\n"));
+    }
+    println!("{} is valid", file.display());
+    Ok(())
+}
+
+fn vault(cmd: VaultCmd) -> Result<(), String> {
+    match cmd {
+        VaultCmd::Encrypt { file, output } => {
+            let plaintext = read(&file)?;
+            vault::parse(&plaintext)
+                .map_err(|err| format!("{} is not a valid vault: {}", file.display(), err))?;
+            let passphrase = passphrase(true)?;
+            let content = vault::encrypt(&passphrase, &plaintext).map_err(|err| err.to_string())?;
+            write(output.as_ref().unwrap_or(&file), &content)
+        }
+        VaultCmd::Decrypt { file, output } => {
+            let content = read(&file)?;
+            let passphrase = passphrase(false)?;
+            let plaintext = vault::decrypt(&passphrase, &content).map_err(|err| err.to_string())?;
+            match output {
+                Some(output) => write(&output, &plaintext),
+                None => io::stdout()
+                    .write_all(plaintext.as_bytes())
+                    .map_err(|err| err.to_string()),
+            }
+        }
+        VaultCmd::Edit { file } => {
+            let exists = file.exists();
+            let passphrase = passphrase(!exists)?;
+            let plaintext = if exists {
+                let content = read(&file)?;
+                vault::decrypt(&passphrase, &content).map_err(|err| err.to_string())?
+            } else {
+                String::new()
+            };
+            let tmp_file = tempfile::Builder::new()
+                .suffix(".yml")
+                .tempfile()
+                .map_err(|err| err.to_string())?;
+            write(tmp_file.path(), &plaintext)?;
+            let editor = env::var("EDITOR").unwrap_or_else(|_| String::from("vi"));
+            let mut editor_args = editor.split_whitespace();
+            let program = editor_args.next().unwrap_or("vi");
+            let status = Command::new(program)
+                .args(editor_args)
+                .arg(tmp_file.path())
+                .status()
+                .map_err(|err| format!("Unable to run {}: {}", editor, err))?;
+            if !status.success() {
+                return Err(format!("{} terminated with {}", editor, status));
+            }
+            let plaintext = read(tmp_file.path())?;
+            if let Err(err) = vault::parse(&plaintext) {
+                let (_, path) = tmp_file.keep().map_err(|err| err.to_string())?;
+                return Err(format!(
+                    "Edited vault is not valid, {} is unchanged and the edited content is kept in {}: {}",
+                    file.display(),
+                    path.display(),
+                    err
+                ));
+            }
+            let content = vault::encrypt(&passphrase, &plaintext).map_err(|err| err.to_string())?;
+            write(&file, &content)
+        }
+    }
+}
+
+fn load_config(file: &Path) -> Result<Config, String> {
+    let cached = RefCell::new(None);
+    let passphrase_fn = || {
+        if let Some(passphrase) = cached.borrow().clone() {
+            return Ok(passphrase);
+        }
+        let passphrase = vault::passphrase()
+            .or_else(|err| rpassword::prompt_password("Vault password: ").map_err(|_| err))?;
+        cached.replace(Some(passphrase.clone()));
+        Ok(passphrase)
+    };
+    Config::load_with_passphrase(&file.to_string_lossy(), &passphrase_fn)
+        .map_err(|err| err.to_string())
+}
+
+fn passphrase(confirm: bool) -> Result<String, String> {
+    if let Ok(passphrase) = vault::passphrase() {
+        return Ok(passphrase);
+    }
+    let passphrase =
+        rpassword::prompt_password("Vault password: ").map_err(|err| err.to_string())?;
+    if confirm {
+        let confirmation = rpassword::prompt_password("Confirm vault password: ")
+            .map_err(|err| err.to_string())?;
+        if passphrase != confirmation {
+            return Err(String::from("Passwords do not match"));
+        }
+    }
+    Ok(passphrase)
+}
+
+fn file_dir(file: &Path) -> &Path {
+    file.parent().unwrap_or_else(|| Path::new("."))
+}
+
+fn run_store(file: &Path) -> RunStore {
+    RunStore::new(file_dir(file).join(HISTORY_FILE))
+}
+
+fn read(path: &Path) -> Result<String, String> {
+    fs::read_to_string(path).map_err(|err| format!("Unable to read {}: {}", path.display(), err))
+}
+
+fn write(path: &Path, content: &str) -> Result<(), String> {
+    fs::write(path, content).map_err(|err| format!("Unable to write {}: {}", path.display(), err))
 }
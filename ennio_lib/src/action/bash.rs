@@ -1,13 +1,21 @@
 use crate::{
     action::*,
-    command::{Command, Output as CmdOutput},
+    command::{Command, Output as CmdOutput, Stream},
+    event::EventKind,
+    template,
 };
 use log::{debug, error};
-use std::io;
+use std::{
+    io::{self, Write},
+    path::Path,
+};
+use tempfile::NamedTempFile;
 
 pub struct BashAction {
     name: String,
     script: String,
+    creates: Option<String>,
+    removes: Option<String>,
     execute_fn: ExecuteFn,
 }
 
@@ -16,9 +24,37 @@ impl BashAction {
         Self {
             name,
             script,
+            creates: None,
+            removes: None,
             execute_fn: Box::new(|cmd| cmd.execute()),
         }
     }
+
+    pub fn with_creates(mut self, creates: Option<String>) -> Self {
+        self.creates = creates;
+        self
+    }
+
+    pub fn with_removes(mut self, removes: Option<String>) -> Self {
+        self.removes = removes;
+        self
+    }
+
+    fn skip_reason(&self, ctx: &Context) -> Result<Option<String>, VarError> {
+        if let Some(path) = &self.creates {
+            let path = template::render(path, ctx)?;
+            if Path::new(&path).exists() {
+                return Ok(Some(format!("{} exists", path)));
+            }
+        }
+        if let Some(path) = &self.removes {
+            let path = template::render(path, ctx)?;
+            if !Path::new(&path).exists() {
+                return Ok(Some(format!("{} does not exist", path)));
+            }
+        }
+        Ok(None)
+    }
 }
 
 impl Action for BashAction {
@@ -26,11 +62,47 @@ impl Action for BashAction {
         &self.name
     }
 
-    fn run(&self, _ctx: &Context) -> Output {
-        let cmd = Command::new("bash").with_args(vec!["-ec", &self.script]);
+    fn run(&self, ctx: &Context) -> Output {
+        match self.skip_reason(ctx) {
+            Ok(Some(reason)) => {
+                debug!("Script not executed: {}", reason);
+                return Output::new(Status::Unchanged).add_var("reason", reason.into());
+            }
+            Ok(None) => {}
+            Err(err) => {
+                error!("Unable to render guard path: {}", err);
+                return Output::new(Status::Failed).add_var("stderr", err.to_string().into());
+            }
+        }
+        let script = match template::render(&self.script, ctx) {
+            Ok(script) => script,
+            Err(err) => {
+                error!("Unable to render script: {}", err);
+                return Output::new(Status::Failed).add_var("stderr", err.to_string().into());
+            }
+        };
+        let script_file = match script_file(&script) {
+            Ok(script_file) => script_file,
+            Err(err) => {
+                error!("Unable to write script: {}", err);
+                return Output::new(Status::Failed).add_var("stderr", err.to_string().into());
+            }
+        };
+        let script_path = script_file.path().to_string_lossy();
+        let on_chunk = |stream: Stream, chunk: &str| {
+            ctx.notify(EventKind::OutputChunk {
+                action: self.name.clone(),
+                stream,
+                chunk: chunk.into(),
+            })
+        };
+        let cmd = Command::new("bash")
+            .with_args(vec!["-e", &script_path])
+            .with_secrets(ctx.secret_values())
+            .with_chunk_fn(&on_chunk);
         match (self.execute_fn)(&cmd) {
             Ok(output) => {
-                let stderr = output.stderr();
+                let stderr = cmd.mask(&output.stderr());
                 let status = if output.status().success() {
                     debug!("Script executed successfully");
                     Status::Changed
@@ -38,33 +110,63 @@ impl Action for BashAction {
                     debug!("Script execution failed:\n{}", stderr);
                     Status::Failed
                 };
-                Output::new(status)
-                    .add_var("stdout", output.stdout().into())
-                    .add_var("stderr", stderr.into())
+                let action_output = Output::new(status)
+                    .add_var("stdout", cmd.mask(&output.stdout()).into())
+                    .add_var("stderr", stderr.into());
+                match output.status().code() {
+                    Some(code) => action_output.add_var("rc", code.into()),
+                    None => action_output,
+                }
             }
             Err(err) => {
+                let err = cmd.mask(&err.to_string());
                 error!("Unable to execute script: {}", err);
-                Output::new(Status::Failed).add_var("stderr", err.to_string().into())
+                Output::new(Status::Failed).add_var("stderr", err.into())
             }
         }
     }
 }
 
+fn script_file(script: &str) -> io::Result<NamedTempFile> {
+    let mut file = NamedTempFile::new()?;
+    file.write_all(script.as_bytes())?;
+    Ok(file)
+}
+
 #[derive(Debug, Eq, PartialEq)]
 pub struct BashActionBuilder {
     name: String,
     script: String,
+    creates: Option<String>,
+    removes: Option<String>,
 }
 
 impl BashActionBuilder {
     pub fn new(name: String, script: String) -> Self {
-        Self { name, script }
+        Self {
+            name,
+            script,
+            creates: None,
+            removes: None,
+        }
+    }
+
+    pub fn with_creates(mut self, creates: Option<String>) -> Self {
+        self.creates = creates;
+        self
+    }
+
+    pub fn with_removes(mut self, removes: Option<String>) -> Self {
+        self.removes = removes;
+        self
     }
 }
 
 impl Builder for BashActionBuilder {
     fn build(self, _ctx: &Context) -> Result<Box<dyn Action>, BuildError> {
-        let action = BashAction::new(self.name, self.script);
+        let action = BashAction::new(self.name, self.script)
+            .with_creates(self.creates)
+            .with_removes(self.removes);
         Ok(Box::new(action))
     }
 }
@@ -74,7 +176,13 @@ type ExecuteFn = Box<dyn Fn(&Command) -> io::Result<Box<dyn CmdOutput>>>;
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::command::test::*;
+    use crate::{command::test::*, event::test::*, event::*, *};
+    use std::{fs, os::unix::fs::PermissionsExt, rc::Rc};
+
+    fn read_script(cmd: &Command) -> String {
+        assert_eq!(cmd.args()[0], "-e");
+        fs::read_to_string(cmd.args()[1]).unwrap()
+    }
 
     mod bash_action {
         use super::*;
@@ -101,6 +209,8 @@ mod test {
                 let action = BashAction {
                     name: name.into(),
                     script: String::from("echo 'it works!'"),
+                    creates: None,
+                    removes: None,
                     execute_fn: Box::new(|cmd| cmd.execute()),
                 };
                 assert_eq!(action.name(), name);
@@ -116,15 +226,18 @@ mod test {
                     let stderr = "stderr";
                     let expected = Output::new($status)
                         .add_var("stdout", stdout.into())
-                        .add_var("stderr", stderr.into());
+                        .add_var("stderr", stderr.into())
+                        .add_var("rc", Value::from($code));
                     let ctx = Context::new("workflow1");
                     let script = "echo 'it works!'";
                     let action = BashAction {
                         name: String::from("action1"),
                         script: script.into(),
+                        creates: None,
+                        removes: None,
                         execute_fn: Box::new(move |cmd| {
                             assert_eq!(cmd.program(), "bash");
-                            assert_eq!(cmd.args(), vec!["-ec", &script]);
+                            assert_eq!(read_script(cmd), script);
                             Ok(Box::new(OutputStub::new(
                                 $code,
                                 stdout.into(),
@@ -147,9 +260,11 @@ mod test {
                 let action = BashAction {
                     name: String::from("action1"),
                     script: script.into(),
+                    creates: None,
+                    removes: None,
                     execute_fn: Box::new(move |cmd| {
                         assert_eq!(cmd.program(), "bash");
-                        assert_eq!(cmd.args(), vec!["-ec", script]);
+                        assert_eq!(read_script(cmd), script);
                         Err(io::Error::from(err_kind))
                     }),
                 };
@@ -157,6 +272,176 @@ mod test {
                 assert_eq!(output, expected);
             }
 
+            #[test]
+            fn should_return_output_with_failed_status_if_render_err() {
+                let expected = Output::new(Status::Failed).add_var(
+                    "stderr",
+                    VarError::UndefinedVar(String::from("foo"))
+                        .to_string()
+                        .into(),
+                );
+                let ctx = Context::new("workflow1");
+                let action = BashAction {
+                    name: String::from("action1"),
+                    script: String::from("echo {{ vars.foo }}"),
+                    creates: None,
+                    removes: None,
+                    execute_fn: Box::new(|_| panic!("should not be executed")),
+                };
+                let output = action.run(&ctx);
+                assert_eq!(output, expected);
+            }
+
+            #[test]
+            fn should_notify_output_chunks() {
+                let observer = Rc::new(ObserverStub::default());
+                let ctx = Context::new("workflow1")
+                    .with_observers(Observers::new().with(observer.clone()));
+                let action = BashAction::new(String::from("action1"), String::from("echo foo"));
+                action.run(&ctx);
+                let expected = vec![EventKind::OutputChunk {
+                    action: String::from("action1"),
+                    stream: Stream::Stdout,
+                    chunk: String::from("foo\n"),
+                }];
+                assert_eq!(observer.kinds(), expected);
+            }
+
+            #[test]
+            fn should_render_script() {
+                let ctx = Context::new("workflow1").with_vars(hash!("foo", "it works!"));
+                let action = BashAction {
+                    name: String::from("action1"),
+                    script: String::from("echo '{{ vars.foo }}'"),
+                    creates: None,
+                    removes: None,
+                    execute_fn: Box::new(|cmd| {
+                        assert_eq!(read_script(cmd), "echo 'it works!'");
+                        Ok(Box::new(OutputStub::default()))
+                    }),
+                };
+                let output = action.run(&ctx);
+                assert_eq!(output.status(), Status::Changed);
+            }
+
+            #[test]
+            fn should_mask_secrets() {
+                let expected = Output::new(Status::Changed)
+                    .add_var("stdout", Value::from("token=***"))
+                    .add_var("stderr", Value::from("error: ***"))
+                    .add_var("rc", Value::from(0));
+                let ctx = Context::new("workflow1").with_secrets(hash!(
+                    "token",
+                    Value::Secret(Secret::new(String::from("password")))
+                ));
+                let action = BashAction {
+                    name: String::from("action1"),
+                    script: String::from("echo token={{ secrets.token }}"),
+                    creates: None,
+                    removes: None,
+                    execute_fn: Box::new(|cmd| {
+                        assert_eq!(read_script(cmd), "echo token=password");
+                        assert!(!cmd.args().join(" ").contains("password"));
+                        let mode = fs::metadata(cmd.args()[1]).unwrap().permissions().mode();
+                        assert_eq!(mode & 0o777, 0o600);
+                        assert_eq!(cmd.secrets(), vec!["password"]);
+                        Ok(Box::new(OutputStub::new(
+                            0,
+                            String::from("token=password"),
+                            String::from("error: password"),
+                        )))
+                    }),
+                };
+                let output = action.run(&ctx);
+                assert_eq!(output, expected);
+            }
+
+            #[test]
+            fn should_render_nested_secrets() {
+                let ctx = Context::new("workflow1").with_vars(hash!(
+                    "db",
+                    hash!(
+                        "password",
+                        Value::Secret(Secret::new(String::from("password")))
+                    )
+                ));
+                let action = BashAction {
+                    name: String::from("action1"),
+                    script: String::from("connect {{ vars.db.password }} '{{ vars.db }}'"),
+                    creates: None,
+                    removes: None,
+                    execute_fn: Box::new(|cmd| {
+                        assert_eq!(
+                            read_script(cmd),
+                            r#"connect password '{"password":"password"}'"#
+                        );
+                        assert_eq!(cmd.secrets(), vec!["password"]);
+                        Ok(Box::new(OutputStub::default()))
+                    }),
+                };
+                let output = action.run(&ctx);
+                assert_eq!(output.status(), Status::Changed);
+            }
+
+            macro_rules! test_guard {
+                ($name:ident, $creates:expr, $removes:expr, $expected:expr) => {
+                    #[test]
+                    fn $name() {
+                        let ctx = Context::new("workflow1").with_vars(hash!("dir", "./src"));
+                        let action = BashAction {
+                            name: String::from("action1"),
+                            script: String::from("echo 'it works!'"),
+                            creates: $creates.map(String::from),
+                            removes: $removes.map(String::from),
+                            execute_fn: Box::new(|_| Ok(Box::new(OutputStub::default()))),
+                        };
+                        let output = action.run(&ctx);
+                        let expected: Option<&str> = $expected;
+                        match expected {
+                            Some(reason) => {
+                                let expected =
+                                    Output::new(Status::Unchanged).add_var("reason", reason.into());
+                                assert_eq!(output, expected);
+                            }
+                            None => assert_eq!(output.status(), Status::Changed),
+                        }
+                    }
+                };
+            }
+
+            test_guard!(
+                should_skip_if_created_path_exists,
+                Some("{{ vars.dir }}"),
+                None::<&str>,
+                Some("./src exists")
+            );
+            test_guard!(
+                should_run_if_created_path_does_not_exist,
+                Some("./foo"),
+                None::<&str>,
+                None
+            );
+            test_guard!(
+                should_skip_if_removed_path_does_not_exist,
+                None::<&str>,
+                Some("./foo"),
+                Some("./foo does not exist")
+            );
+            test_guard!(
+                should_run_if_removed_path_exists,
+                None::<&str>,
+                Some("{{ vars.dir }}"),
+                None
+            );
+
+            #[test]
+            fn should_return_output_with_failed_status_if_guard_render_err() {
+                let action = BashAction::new(String::from("action1"), String::from("true"))
+                    .with_creates(Some(String::from("{{ vars.foo }}")));
+                let output = action.run(&Context::new("workflow1"));
+                assert_eq!(output.status(), Status::Failed);
+            }
+
             #[test]
             fn should_return_output_with_failed_status_if_exit_status_is_not_success() {
                 test!(1, Status::Failed);
@@ -182,6 +467,8 @@ mod test {
                 let expected = BashActionBuilder {
                     name: name.into(),
                     script: script.into(),
+                    creates: None,
+                    removes: None,
                 };
                 let builder = BashActionBuilder::new(name.into(), script.into());
                 assert_eq!(builder, expected);
@@ -199,6 +486,8 @@ mod test {
                 let builder = BashActionBuilder {
                     name: name.into(),
                     script: script.into(),
+                    creates: None,
+                    removes: None,
                 };
                 let action = builder.build(&ctx).unwrap();
                 assert_eq!(action.name(), name);
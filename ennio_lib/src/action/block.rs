@@ -0,0 +1,251 @@
+use crate::{action::*, workflow::Step};
+use log::debug;
+
+pub struct BlockAction {
+    name: String,
+    steps: Vec<Step>,
+    rescue: Vec<Step>,
+    always: Vec<Step>,
+}
+
+impl BlockAction {
+    pub fn new(name: String, steps: Vec<Step>) -> Self {
+        Self {
+            name,
+            steps,
+            rescue: vec![],
+            always: vec![],
+        }
+    }
+
+    pub fn with_always(mut self, always: Vec<Step>) -> Self {
+        self.always = always;
+        self
+    }
+
+    pub fn with_rescue(mut self, rescue: Vec<Step>) -> Self {
+        self.rescue = rescue;
+        self
+    }
+
+    fn run_steps(
+        steps: &[Step],
+        ctx: &mut Context,
+        outputs: &mut Vec<(String, Output)>,
+    ) -> Option<(String, Output)> {
+        for step in steps {
+            let action_name = step.action().name();
+            let output = step.run(ctx);
+            ctx.update(action_name, output.clone());
+            outputs.push((action_name.into(), output.clone()));
+            if output.status() == Status::Failed {
+                return Some((action_name.into(), output));
+            }
+        }
+        None
+    }
+}
+
+impl Action for BlockAction {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn run(&self, ctx: &Context) -> Output {
+        let mut block_ctx = ctx.clone();
+        let mut outputs = vec![];
+        let mut failed = match Self::run_steps(&self.steps, &mut block_ctx, &mut outputs) {
+            Some((action_name, output)) if !self.rescue.is_empty() => {
+                debug!("Action '{}' failed, running rescue actions", action_name);
+                block_ctx = block_ctx.with_failure(&action_name, &output);
+                Self::run_steps(&self.rescue, &mut block_ctx, &mut outputs).is_some()
+            }
+            Some(_) => true,
+            None => false,
+        };
+        failed |= Self::run_steps(&self.always, &mut block_ctx, &mut outputs).is_some();
+        let status = if failed {
+            Status::Failed
+        } else {
+            Status::aggregate(
+                outputs
+                    .iter()
+                    .map(|(_, output)| output.status())
+                    .filter(|status| *status != Status::Failed),
+            )
+        };
+        let vars = outputs
+            .into_iter()
+            .map(|(name, output)| (name, Value::from(output)))
+            .collect();
+        Output::new(status).with_vars(vars)
+    }
+}
+
+pub struct BlockActionBuilder {
+    name: String,
+    steps: Vec<Step>,
+    rescue: Vec<Step>,
+    always: Vec<Step>,
+}
+
+impl BlockActionBuilder {
+    pub fn new(name: String, steps: Vec<Step>) -> Self {
+        Self {
+            name,
+            steps,
+            rescue: vec![],
+            always: vec![],
+        }
+    }
+
+    pub fn with_always(mut self, always: Vec<Step>) -> Self {
+        self.always = always;
+        self
+    }
+
+    pub fn with_rescue(mut self, rescue: Vec<Step>) -> Self {
+        self.rescue = rescue;
+        self
+    }
+}
+
+impl Builder for BlockActionBuilder {
+    fn build(self, _ctx: &Context) -> Result<Box<dyn Action>, BuildError> {
+        let action = BlockAction::new(self.name, self.steps)
+            .with_rescue(self.rescue)
+            .with_always(self.always);
+        Ok(Box::new(action))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{action::test::*, *};
+
+    mod block_action {
+        use super::*;
+
+        mod name {
+            use super::*;
+
+            #[test]
+            fn should_return_name() {
+                let name = "block1";
+                let action = BlockAction::new(name.into(), vec![]);
+                assert_eq!(action.name(), name);
+            }
+        }
+
+        mod run {
+            use super::*;
+
+            #[test]
+            fn should_return_output() {
+                let expected = Output::new(Status::Changed).with_vars(hash!(
+                    "action1",
+                    hash!("status", "unchanged"),
+                    "action2",
+                    hash!("status", "changed"),
+                    "cleanup",
+                    hash!("status", "unchanged")
+                ));
+                let action = BlockAction::new(
+                    String::from("block1"),
+                    vec![
+                        Step::new(action_stub!("action1", |_| Output::new(Status::Unchanged))),
+                        Step::new(action_stub!("action2", |ctx| {
+                            assert!(ctx.output("action1").is_some());
+                            Output::new(Status::Changed)
+                        })),
+                    ],
+                )
+                .with_rescue(vec![Step::new(action_stub!("rescue", |_| panic!(
+                    "should not be run"
+                )))])
+                .with_always(vec![Step::new(action_stub!("cleanup", |_| {
+                    Output::new(Status::Unchanged)
+                }))]);
+                let output = action.run(&Context::new("workflow1"));
+                assert_eq!(untimed(&output), expected);
+            }
+
+            #[test]
+            fn should_return_output_with_failed_status_if_not_rescued() {
+                let action = BlockAction::new(
+                    String::from("block1"),
+                    vec![
+                        Step::new(action_stub!("action1", |_| Output::new(Status::Failed))),
+                        Step::new(action_stub!("action2", |_| panic!("should not be run"))),
+                    ],
+                )
+                .with_always(vec![Step::new(action_stub!("cleanup", |_| {
+                    Output::new(Status::Changed)
+                }))]);
+                let output = action.run(&Context::new("workflow1"));
+                assert_eq!(output.status(), Status::Failed);
+                assert!(output.value("cleanup").is_some());
+            }
+
+            #[test]
+            fn should_return_output_with_changed_status_if_rescued() {
+                let action = BlockAction::new(
+                    String::from("block1"),
+                    vec![Step::new(action_stub!("action1", |_| {
+                        Output::new(Status::Failed).add_var("stderr", "error".into())
+                    }))],
+                )
+                .with_rescue(vec![Step::new(action_stub!("rescue", |ctx| {
+                    assert_eq!(
+                        ctx.value("workflow.failed_action").unwrap(),
+                        &"action1".into()
+                    );
+                    assert_eq!(
+                        ctx.value("workflow.failed_output.stderr").unwrap(),
+                        &"error".into()
+                    );
+                    Output::new(Status::Changed)
+                }))]);
+                let output = action.run(&Context::new("workflow1"));
+                assert_eq!(output.status(), Status::Changed);
+                assert_eq!(
+                    output.value("action1.status").unwrap(),
+                    &Value::from("failed")
+                );
+            }
+
+            #[test]
+            fn should_return_output_with_failed_status_if_rescue_failed() {
+                let action = BlockAction::new(
+                    String::from("block1"),
+                    vec![Step::new(action_stub!("action1", |_| {
+                        Output::new(Status::Failed)
+                    }))],
+                )
+                .with_rescue(vec![Step::new(action_stub!("rescue", |_| {
+                    Output::new(Status::Failed)
+                }))]);
+                let output = action.run(&Context::new("workflow1"));
+                assert_eq!(output.status(), Status::Failed);
+            }
+        }
+    }
+
+    mod block_action_builder {
+        use super::*;
+
+        mod build {
+            use super::*;
+
+            #[test]
+            fn should_return_action() {
+                let ctx = Context::new("workflow1");
+                let name = "block1";
+                let builder = BlockActionBuilder::new(name.into(), vec![]);
+                let action = builder.build(&ctx).unwrap();
+                assert_eq!(action.name(), name);
+            }
+        }
+    }
+}
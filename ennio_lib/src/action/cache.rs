@@ -0,0 +1,785 @@
+use crate::{action::*, run, template};
+use blake2::{Blake2s256, Digest};
+use glob::{MatchOptions, Pattern};
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+pub static CACHE_DIR: &str = ".ennio/cache";
+static ENNIO_DIR: &str = ".ennio";
+
+const ENTRY_FILE: &str = "entry.json";
+const FILES_DIR: &str = "files";
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq)]
+pub struct CacheInputs {
+    #[serde(default)]
+    files: Vec<String>,
+    #[serde(default)]
+    vars: Vec<String>,
+}
+
+impl CacheInputs {
+    pub fn new(files: Vec<String>, vars: Vec<String>) -> Self {
+        Self { files, vars }
+    }
+
+    pub fn files(&self) -> &[String] {
+        &self.files
+    }
+
+    pub fn vars(&self) -> &[String] {
+        &self.vars
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct CacheEntry {
+    key: String,
+    created_at: u64,
+    output: Output,
+    files: Vec<PathBuf>,
+}
+
+impl CacheEntry {
+    pub fn created_at(&self) -> u64 {
+        self.created_at
+    }
+
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub fn output(&self) -> &Output {
+        &self.output
+    }
+}
+
+pub struct Cache {
+    dir: PathBuf,
+}
+
+impl Cache {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn entries(&self) -> Result<Vec<CacheEntry>, CacheError> {
+        if !self.dir.is_dir() {
+            return Ok(vec![]);
+        }
+        let dir_entries = fs::read_dir(&self.dir)
+            .map_err(|err| CacheError::Reading(self.dir.clone(), err.to_string()))?;
+        let mut entries = vec![];
+        for dir_entry in dir_entries.filter_map(Result::ok) {
+            let key = dir_entry.file_name().to_string_lossy().into_owned();
+            if let Some(entry) = self.load(&key)? {
+                entries.push(entry);
+            }
+        }
+        entries.sort_by_key(|entry| entry.created_at);
+        Ok(entries)
+    }
+
+    pub fn load(&self, key: &str) -> Result<Option<CacheEntry>, CacheError> {
+        let path = self.dir.join(key).join(ENTRY_FILE);
+        if !path.is_file() {
+            return Ok(None);
+        }
+        let content = fs::read_to_string(&path)
+            .map_err(|err| CacheError::Reading(path.clone(), err.to_string()))?;
+        serde_json::from_str(&content)
+            .map(Some)
+            .map_err(|err| CacheError::Parsing(path, err.to_string()))
+    }
+
+    pub fn prune(&self, max_age: Option<Duration>) -> Result<Vec<CacheEntry>, CacheError> {
+        let now = run::now();
+        let mut pruned = vec![];
+        for entry in self.entries()? {
+            let expired =
+                max_age.is_none_or(|max_age| entry.created_at + max_age.as_millis() as u64 <= now);
+            if expired {
+                let path = self.dir.join(&entry.key);
+                debug!("Removing cache entry {}", path.display());
+                fs::remove_dir_all(&path)
+                    .map_err(|err| CacheError::Writing(path, err.to_string()))?;
+                pruned.push(entry);
+            }
+        }
+        Ok(pruned)
+    }
+
+    pub fn restore(&self, entry: &CacheEntry) -> Result<(), CacheError> {
+        let files_dir = self.dir.join(&entry.key).join(FILES_DIR);
+        for (idx, path) in entry.files.iter().enumerate() {
+            if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+                fs::create_dir_all(dir)
+                    .map_err(|err| CacheError::Writing(dir.into(), err.to_string()))?;
+            }
+            fs::copy(files_dir.join(idx.to_string()), path)
+                .map_err(|err| CacheError::Writing(path.clone(), err.to_string()))?;
+        }
+        Ok(())
+    }
+
+    pub fn save(
+        &self,
+        key: &str,
+        output: &Output,
+        files: Vec<PathBuf>,
+    ) -> Result<CacheEntry, CacheError> {
+        let entry_dir = self.dir.join(key);
+        let files_dir = entry_dir.join(FILES_DIR);
+        fs::create_dir_all(&files_dir)
+            .map_err(|err| CacheError::Writing(files_dir.clone(), err.to_string()))?;
+        for (idx, path) in files.iter().enumerate() {
+            fs::copy(path, files_dir.join(idx.to_string()))
+                .map_err(|err| CacheError::Reading(path.clone(), err.to_string()))?;
+        }
+        let entry = CacheEntry {
+            key: key.into(),
+            created_at: run::now(),
+            output: output.clone(),
+            files,
+        };
+        let path = entry_dir.join(ENTRY_FILE);
+        let content = serde_json::to_string(&entry)
+            .map_err(|err| CacheError::Writing(path.clone(), err.to_string()))?;
+        fs::write(&path, content).map_err(|err| CacheError::Writing(path, err.to_string()))?;
+        Ok(entry)
+    }
+}
+
+#[derive(Debug)]
+pub enum CacheError {
+    Var(VarError),
+    Reading(PathBuf, String),
+    Writing(PathBuf, String),
+    Parsing(PathBuf, String),
+    Pattern(String, String),
+}
+
+impl Display for CacheError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Self::Var(err) => write!(f, "{}", err),
+            Self::Reading(path, err) => write!(f, "Unable to read {}: {}", path.display(), err),
+            Self::Writing(path, err) => write!(f, "Unable to write {}: {}", path.display(), err),
+            Self::Parsing(path, err) => write!(f, "Unable to parse {}: {}", path.display(), err),
+            Self::Pattern(pattern, err) => write!(f, "Invalid pattern {}: {}", pattern, err),
+        }
+    }
+}
+
+pub struct CachedAction {
+    action: Box<dyn Action>,
+    script: String,
+    inputs: CacheInputs,
+    outputs: Vec<String>,
+    cache: Cache,
+    base_dir: PathBuf,
+}
+
+impl CachedAction {
+    pub fn new(action: Box<dyn Action>, script: String, inputs: CacheInputs, cache: Cache) -> Self {
+        Self {
+            action,
+            script,
+            inputs,
+            outputs: vec![],
+            cache,
+            base_dir: PathBuf::new(),
+        }
+    }
+
+    pub fn with_base_dir(mut self, base_dir: PathBuf) -> Self {
+        self.base_dir = base_dir;
+        self
+    }
+
+    pub fn with_outputs(mut self, outputs: Vec<String>) -> Self {
+        self.outputs = outputs;
+        self
+    }
+
+    fn key(&self, ctx: &Context) -> Result<String, CacheError> {
+        let mut hasher = Blake2s256::new();
+        update(&mut hasher, ctx.workflow_name().as_bytes());
+        update(&mut hasher, self.name().as_bytes());
+        let script = template::render(&self.script, ctx).map_err(CacheError::Var)?;
+        update(&mut hasher, script.as_bytes());
+        for var_name in self.inputs.vars.iter() {
+            let val = ctx.value(var_name).map_err(CacheError::Var)?;
+            update(&mut hasher, var_name.as_bytes());
+            update(&mut hasher, val.expose().as_bytes());
+        }
+        for path in expand(&self.inputs.files, &self.base_dir, ctx)? {
+            let content = fs::read(&path)
+                .map_err(|err| CacheError::Reading(path.clone(), err.to_string()))?;
+            let rel_path = path.strip_prefix(&self.base_dir).unwrap_or(&path);
+            update(&mut hasher, rel_path.to_string_lossy().as_bytes());
+            update(&mut hasher, &content);
+        }
+        for pattern in self.outputs.iter() {
+            update(&mut hasher, pattern.as_bytes());
+        }
+        Ok(hasher
+            .finalize()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect())
+    }
+
+    fn restore(&self, key: &str) -> Option<Output> {
+        let entry = match self.cache.load(key) {
+            Ok(Some(entry)) => entry,
+            Ok(None) => {
+                debug!("No cache entry for action '{}'", self.name());
+                return None;
+            }
+            Err(err) => {
+                warn!("Unable to load cache entry: {}", err);
+                return None;
+            }
+        };
+        match self.cache.restore(&entry) {
+            Ok(()) => {
+                debug!("Restoring cached output of action '{}'", self.name());
+                Some(Output::new(Status::Unchanged).with_vars(entry.output.vars().clone()))
+            }
+            Err(err) => {
+                warn!("Unable to restore cache entry: {}", err);
+                None
+            }
+        }
+    }
+}
+
+impl Action for CachedAction {
+    fn name(&self) -> &str {
+        self.action.name()
+    }
+
+    fn run(&self, ctx: &Context) -> Output {
+        let key = match self.key(ctx) {
+            Ok(key) => key,
+            Err(err) => {
+                warn!(
+                    "Unable to compute cache key of action '{}': {}",
+                    self.name(),
+                    err
+                );
+                return self.action.run(ctx);
+            }
+        };
+        if let Some(output) = self.restore(&key) {
+            return output;
+        }
+        let output = self.action.run(ctx);
+        if output.status() != Status::Failed {
+            let res = expand(&self.outputs, &self.base_dir, ctx)
+                .and_then(|files| self.cache.save(&key, &output, files));
+            if let Err(err) = res {
+                warn!(
+                    "Unable to cache output of action '{}': {}",
+                    self.name(),
+                    err
+                );
+            }
+        }
+        output
+    }
+}
+
+pub struct CachedActionBuilder {
+    action: Box<dyn Action>,
+    script: String,
+    inputs: CacheInputs,
+    outputs: Vec<String>,
+    cache: Cache,
+    base_dir: PathBuf,
+}
+
+impl CachedActionBuilder {
+    pub fn new(action: Box<dyn Action>, script: String, inputs: CacheInputs, cache: Cache) -> Self {
+        Self {
+            action,
+            script,
+            inputs,
+            outputs: vec![],
+            cache,
+            base_dir: PathBuf::new(),
+        }
+    }
+
+    pub fn with_base_dir(mut self, base_dir: PathBuf) -> Self {
+        self.base_dir = base_dir;
+        self
+    }
+
+    pub fn with_outputs(mut self, outputs: Vec<String>) -> Self {
+        self.outputs = outputs;
+        self
+    }
+}
+
+impl Builder for CachedActionBuilder {
+    fn build(self, _ctx: &Context) -> Result<Box<dyn Action>, BuildError> {
+        let action = CachedAction::new(self.action, self.script, self.inputs, self.cache)
+            .with_outputs(self.outputs)
+            .with_base_dir(self.base_dir);
+        Ok(Box::new(action))
+    }
+}
+
+fn expand(patterns: &[String], base_dir: &Path, ctx: &Context) -> Result<Vec<PathBuf>, CacheError> {
+    let mut paths = vec![];
+    for pattern in patterns {
+        let pattern = template::render(pattern, ctx).map_err(CacheError::Var)?;
+        paths.extend(glob(base_dir, &pattern)?);
+    }
+    paths.sort();
+    paths.dedup();
+    Ok(paths)
+}
+
+fn glob(base_dir: &Path, pattern: &str) -> Result<Vec<PathBuf>, CacheError> {
+    let pattern = base_dir.join(pattern).to_string_lossy().into_owned();
+    let components: Vec<&str> = pattern.split('/').collect();
+    let base_len = match components
+        .iter()
+        .position(|component| component.contains(['*', '?', '[']))
+    {
+        Some(base_len) => base_len,
+        None => {
+            let path = PathBuf::from(&pattern);
+            return if path.is_file() {
+                Ok(vec![path])
+            } else {
+                Err(CacheError::Reading(path, String::from("File not found")))
+            };
+        }
+    };
+    let base = match base_len {
+        0 => PathBuf::from("."),
+        1 if components[0].is_empty() => PathBuf::from("/"),
+        _ => PathBuf::from(components[..base_len].join("/")),
+    };
+    let matcher = Pattern::new(&components[base_len..].join("/"))
+        .map_err(|err| CacheError::Pattern(pattern.clone(), err.to_string()))?;
+    let options = MatchOptions {
+        require_literal_separator: true,
+        ..MatchOptions::new()
+    };
+    let mut paths = vec![];
+    walk(&base, &mut paths)?;
+    Ok(paths
+        .into_iter()
+        .filter_map(|path| {
+            let rel_path = path
+                .strip_prefix(&base)
+                .ok()?
+                .to_string_lossy()
+                .into_owned();
+            matcher
+                .matches_with(&rel_path, options)
+                .then(|| match base_len {
+                    0 => PathBuf::from(rel_path),
+                    _ => path,
+                })
+        })
+        .collect())
+}
+
+fn update(hasher: &mut Blake2s256, data: &[u8]) {
+    hasher.update((data.len() as u64).to_le_bytes());
+    hasher.update(data);
+}
+
+fn walk(dir: &Path, paths: &mut Vec<PathBuf>) -> Result<(), CacheError> {
+    if !dir.is_dir() {
+        return Ok(());
+    }
+    let entries =
+        fs::read_dir(dir).map_err(|err| CacheError::Reading(dir.into(), err.to_string()))?;
+    for entry in entries.filter_map(Result::ok) {
+        let path = entry.path();
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        if file_type.is_dir() {
+            if entry.file_name() != ENNIO_DIR {
+                walk(&path, paths)?;
+            }
+        } else if !file_type.is_symlink() || path.is_file() {
+            paths.push(path);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{action::test::*, *};
+    use std::{cell::Cell, rc::Rc};
+    use tempfile::TempDir;
+
+    fn write(path: &Path, content: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    mod cache {
+        use super::*;
+
+        mod load {
+            use super::*;
+
+            #[test]
+            fn should_return_none() {
+                let dir = TempDir::new().unwrap();
+                let cache = Cache::new(dir.path().into());
+                assert!(cache.load("foo").unwrap().is_none());
+            }
+
+            #[test]
+            fn should_return_saved_entry() {
+                let dir = TempDir::new().unwrap();
+                let cache = Cache::new(dir.path().join("cache"));
+                let output = Output::new(Status::Changed).add_var("stdout", "foo".into());
+                let expected = cache.save("foo", &output, vec![]).unwrap();
+                let entry = cache.load("foo").unwrap().unwrap();
+                assert_eq!(entry, expected);
+                assert_eq!(*entry.output(), output);
+            }
+        }
+
+        mod prune {
+            use super::*;
+
+            #[test]
+            fn should_remove_all_entries() {
+                let dir = TempDir::new().unwrap();
+                let cache = Cache::new(dir.path().into());
+                let output = Output::new(Status::Changed);
+                cache.save("foo", &output, vec![]).unwrap();
+                cache.save("bar", &output, vec![]).unwrap();
+                let pruned = cache.prune(None).unwrap();
+                assert_eq!(pruned.len(), 2);
+                assert!(cache.entries().unwrap().is_empty());
+            }
+
+            #[test]
+            fn should_keep_recent_entries() {
+                let dir = TempDir::new().unwrap();
+                let cache = Cache::new(dir.path().into());
+                cache
+                    .save("foo", &Output::new(Status::Changed), vec![])
+                    .unwrap();
+                let pruned = cache.prune(Some(Duration::from_secs(3600))).unwrap();
+                assert!(pruned.is_empty());
+                assert_eq!(cache.entries().unwrap().len(), 1);
+            }
+        }
+
+        mod restore {
+            use super::*;
+
+            #[test]
+            fn should_restore_files() {
+                let dir = TempDir::new().unwrap();
+                let cache = Cache::new(dir.path().join("cache"));
+                let path = dir.path().join("target").join("app");
+                write(&path, "app");
+                let entry = cache
+                    .save("foo", &Output::new(Status::Changed), vec![path.clone()])
+                    .unwrap();
+                fs::remove_dir_all(dir.path().join("target")).unwrap();
+                cache.restore(&entry).unwrap();
+                assert_eq!(fs::read_to_string(&path).unwrap(), "app");
+            }
+        }
+    }
+
+    mod cache_error {
+        use super::*;
+
+        mod display {
+            use super::*;
+
+            macro_rules! test {
+                ($name:ident, $value:expr, $expected:expr) => {
+                    #[test]
+                    fn $name() {
+                        let err = $value(PathBuf::from("entry.json"), String::from("error"));
+                        assert_eq!(err.to_string(), $expected);
+                    }
+                };
+            }
+
+            test!(
+                reading,
+                CacheError::Reading,
+                "Unable to read entry.json: error"
+            );
+            test!(
+                writing,
+                CacheError::Writing,
+                "Unable to write entry.json: error"
+            );
+            test!(
+                parsing,
+                CacheError::Parsing,
+                "Unable to parse entry.json: error"
+            );
+
+            #[test]
+            fn pattern() {
+                let err = CacheError::Pattern(String::from("src/[a"), String::from("error"));
+                assert_eq!(err.to_string(), "Invalid pattern src/[a: error");
+            }
+
+            #[test]
+            fn var() {
+                let err = CacheError::Var(VarError::UndefinedItem);
+                assert_eq!(err.to_string(), VarError::UndefinedItem.to_string());
+            }
+        }
+    }
+
+    mod cached_action {
+        use super::*;
+
+        fn counting_action(count: Rc<Cell<u32>>) -> Box<dyn Action> {
+            action_stub!("build", move |_| {
+                count.set(count.get() + 1);
+                Output::new(Status::Changed).add_var("stdout", "built".into())
+            })
+        }
+
+        mod run {
+            use super::*;
+
+            #[test]
+            fn should_restore_cached_output() {
+                let dir = TempDir::new().unwrap();
+                let src = dir.path().join("src").join("main.c");
+                write(&src, "int main;");
+                let count = Rc::new(Cell::new(0));
+                let action = CachedAction::new(
+                    counting_action(count.clone()),
+                    String::from("make {{ vars.target }}"),
+                    CacheInputs::new(
+                        vec![format!("{}/src/*.c", dir.path().display())],
+                        vec![String::from("vars.target")],
+                    ),
+                    Cache::new(dir.path().join("cache")),
+                );
+                let ctx = Context::new("workflow1").with_vars(hash!("target", "app"));
+                let output = action.run(&ctx);
+                assert_eq!(output.status(), Status::Changed);
+                let output = action.run(&ctx);
+                assert_eq!(output.status(), Status::Unchanged);
+                assert_eq!(output.value("stdout").unwrap(), &"built".into());
+                assert_eq!(count.get(), 1);
+                write(&src, "int main();");
+                let output = action.run(&ctx);
+                assert_eq!(output.status(), Status::Changed);
+                let ctx = Context::new("workflow1").with_vars(hash!("target", "lib"));
+                action.run(&ctx);
+                assert_eq!(count.get(), 3);
+            }
+
+            #[test]
+            fn should_restore_output_files() {
+                let dir = TempDir::new().unwrap();
+                let target = dir.path().join("target").join("app");
+                let action = CachedAction::new(
+                    action_stub!("build", |_| Output::new(Status::Changed)),
+                    String::from("make"),
+                    CacheInputs::default(),
+                    Cache::new(dir.path().join("cache")),
+                )
+                .with_outputs(vec![format!("{}/target/**", dir.path().display())]);
+                write(&target, "app");
+                action.run(&Context::new("workflow1"));
+                fs::remove_file(&target).unwrap();
+                let output = action.run(&Context::new("workflow1"));
+                assert_eq!(output.status(), Status::Unchanged);
+                assert_eq!(fs::read_to_string(&target).unwrap(), "app");
+            }
+
+            #[test]
+            fn should_resolve_patterns_from_base_dir() {
+                let dir = TempDir::new().unwrap();
+                let target = dir.path().join("target").join("app");
+                write(&dir.path().join("src").join("main.c"), "int main;");
+                let action = CachedAction::new(
+                    action_stub!("build", |_| Output::new(Status::Changed)),
+                    String::from("make"),
+                    CacheInputs::new(vec![String::from("src/*.c")], vec![]),
+                    Cache::new(dir.path().join("cache")),
+                )
+                .with_outputs(vec![String::from("target/**")])
+                .with_base_dir(dir.path().into());
+                write(&target, "app");
+                action.run(&Context::new("workflow1"));
+                fs::remove_file(&target).unwrap();
+                let output = action.run(&Context::new("workflow1"));
+                assert_eq!(output.status(), Status::Unchanged);
+                assert_eq!(fs::read_to_string(&target).unwrap(), "app");
+            }
+
+            #[test]
+            fn should_not_share_entries_between_actions() {
+                let dir = TempDir::new().unwrap();
+                let count = Rc::new(Cell::new(0));
+                let cached_action = |action| {
+                    CachedAction::new(
+                        action,
+                        String::from("make"),
+                        CacheInputs::default(),
+                        Cache::new(dir.path().join("cache")),
+                    )
+                };
+                let count_build = count.clone();
+                cached_action(action_stub!("build", move |_| {
+                    count_build.set(count_build.get() + 1);
+                    Output::new(Status::Changed)
+                }))
+                .run(&Context::new("workflow1"));
+                let count_test = count.clone();
+                let test_action = cached_action(action_stub!("test", move |_| {
+                    count_test.set(count_test.get() + 1);
+                    Output::new(Status::Changed)
+                }));
+                test_action.run(&Context::new("workflow1"));
+                test_action.run(&Context::new("workflow2"));
+                assert_eq!(count.get(), 3);
+                assert_eq!(
+                    Cache::new(dir.path().join("cache"))
+                        .entries()
+                        .unwrap()
+                        .len(),
+                    3
+                );
+            }
+
+            #[test]
+            fn should_not_cache_failed_output() {
+                let dir = TempDir::new().unwrap();
+                let cache_dir = dir.path().join("cache");
+                let action = CachedAction::new(
+                    action_stub!("build", |_| Output::new(Status::Failed)),
+                    String::from("make"),
+                    CacheInputs::default(),
+                    Cache::new(cache_dir.clone()),
+                );
+                action.run(&Context::new("workflow1"));
+                assert!(Cache::new(cache_dir).entries().unwrap().is_empty());
+            }
+
+            #[test]
+            fn should_run_action_if_undefined_input() {
+                let dir = TempDir::new().unwrap();
+                let count = Rc::new(Cell::new(0));
+                let action = CachedAction::new(
+                    counting_action(count.clone()),
+                    String::from("make"),
+                    CacheInputs::new(vec![], vec![String::from("vars.target")]),
+                    Cache::new(dir.path().into()),
+                );
+                action.run(&Context::new("workflow1"));
+                action.run(&Context::new("workflow1"));
+                assert_eq!(count.get(), 2);
+            }
+        }
+    }
+
+    mod glob {
+        use super::*;
+
+        macro_rules! test {
+            ($name:ident, $pattern:expr, [$($expected:expr),*]) => {
+                #[test]
+                fn $name() {
+                    let dir = TempDir::new().unwrap();
+                    for path in ["Cargo.toml", "src/lib.rs", "src/action/mod.rs", "README.md"] {
+                        write(&dir.path().join(path), "");
+                    }
+                    let mut paths = glob(dir.path(), $pattern).unwrap();
+                    paths.sort();
+                    let expected: Vec<PathBuf> =
+                        vec![$(dir.path().join($expected)),*];
+                    assert_eq!(paths, expected);
+                }
+            };
+        }
+
+        test!(file, "Cargo.toml", ["Cargo.toml"]);
+        test!(star, "*.md", ["README.md"]);
+        test!(question_mark, "src/li?.rs", ["src/lib.rs"]);
+        test!(
+            double_star,
+            "src/**/*.rs",
+            ["src/action/mod.rs", "src/lib.rs"]
+        );
+        test!(no_match, "*.c", []);
+        test!(class, "src/[lm]*.rs", ["src/lib.rs"]);
+
+        #[test]
+        fn should_not_follow_symlinked_dirs_nor_walk_ennio_dir() {
+            let dir = TempDir::new().unwrap();
+            write(&dir.path().join("src/lib.rs"), "");
+            write(&dir.path().join(".ennio/cache/entry.json"), "");
+            std::os::unix::fs::symlink(dir.path(), dir.path().join("src/loop")).unwrap();
+            std::os::unix::fs::symlink(
+                dir.path().join("src/lib.rs"),
+                dir.path().join("src/link.rs"),
+            )
+            .unwrap();
+            let mut paths = glob(dir.path(), "**/*").unwrap();
+            paths.sort();
+            assert_eq!(
+                paths,
+                vec![
+                    dir.path().join("src/lib.rs"),
+                    dir.path().join("src/link.rs")
+                ]
+            );
+        }
+
+        #[test]
+        fn should_return_pattern_err_if_invalid_pattern() {
+            let dir = TempDir::new().unwrap();
+            match glob(dir.path(), "src/[a") {
+                Ok(_) => panic!("should fail"),
+                Err(CacheError::Pattern(_, _)) => {}
+                Err(err) => panic!("{}", err),
+            }
+        }
+
+        #[test]
+        fn should_return_reading_err_if_file_not_found() {
+            match glob(Path::new("."), "/not/found") {
+                Ok(_) => panic!("should fail"),
+                Err(CacheError::Reading(path, _)) => assert_eq!(path, PathBuf::from("/not/found")),
+                Err(err) => panic!("{}", err),
+            }
+        }
+    }
+}
@@ -0,0 +1,268 @@
+use crate::{action::*, template};
+use log::{debug, error};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Items {
+    List(Value),
+    Matrix(Hash),
+}
+
+impl Items {
+    pub fn resolve(&self, ctx: &Context) -> Result<Vec<Value>, ItemsError> {
+        match self {
+            Self::List(items) => resolve_list(items, ctx),
+            Self::Matrix(dims) => {
+                let mut names: Vec<&String> = dims.keys().collect();
+                names.sort();
+                names
+                    .into_iter()
+                    .try_fold(vec![Hash::new()], |combs, name| {
+                        let items = resolve_list(&dims[name], ctx)?;
+                        let combs = combs
+                            .iter()
+                            .flat_map(|comb| {
+                                items.iter().map(move |item| {
+                                    let mut comb = comb.clone();
+                                    comb.insert(name.clone(), item.clone());
+                                    comb
+                                })
+                            })
+                            .collect();
+                        Ok(combs)
+                    })
+            }
+            .map(|combs| combs.into_iter().map(Value::Hash).collect()),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ItemsError {
+    Var(VarError),
+    NotAList(String),
+}
+
+impl Display for ItemsError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Self::Var(err) => write!(f, "{}", err),
+            Self::NotAList(val) => write!(f, "Loop items must be a list: {}", val),
+        }
+    }
+}
+
+pub struct LoopAction {
+    action: Box<dyn Action>,
+    items: Items,
+}
+
+impl LoopAction {
+    pub fn new(action: Box<dyn Action>, items: Items) -> Self {
+        Self { action, items }
+    }
+}
+
+impl Action for LoopAction {
+    fn name(&self) -> &str {
+        self.action.name()
+    }
+
+    fn run(&self, ctx: &Context) -> Output {
+        let items = match self.items.resolve(ctx) {
+            Ok(items) => items,
+            Err(err) => {
+                error!("Unable to resolve loop items: {}", err);
+                return Output::new(Status::Failed).add_var("stderr", err.to_string().into());
+            }
+        };
+        if items.is_empty() {
+            debug!("No loop items, skipping action '{}'", self.name());
+            return Output::new(Status::Skipped)
+                .add_var("results", Array::from(Vec::<Value>::new()).into());
+        }
+        let outputs: Vec<Output> = items
+            .into_iter()
+            .map(|item| {
+                debug!("Running action '{}' with item {}", self.name(), item);
+                self.action.run(&ctx.clone().with_item(item))
+            })
+            .collect();
+        let status = Status::aggregate(outputs.iter().map(Output::status));
+        let results: Vec<Value> = outputs.into_iter().map(Value::from).collect();
+        Output::new(status).add_var("results", Array::from(results).into())
+    }
+}
+
+pub struct LoopActionBuilder {
+    action: Box<dyn Action>,
+    items: Items,
+}
+
+impl LoopActionBuilder {
+    pub fn new(action: Box<dyn Action>, items: Items) -> Self {
+        Self { action, items }
+    }
+}
+
+impl Builder for LoopActionBuilder {
+    fn build(self, _ctx: &Context) -> Result<Box<dyn Action>, BuildError> {
+        let action = LoopAction::new(self.action, self.items);
+        Ok(Box::new(action))
+    }
+}
+
+fn resolve_list(items: &Value, ctx: &Context) -> Result<Vec<Value>, ItemsError> {
+    match template::render_value(items, ctx).map_err(ItemsError::Var)? {
+        Value::Array(items) => Ok(items.items().to_vec()),
+        val => Err(ItemsError::NotAList(val.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{action::test::*, *};
+
+    mod items {
+        use super::*;
+
+        mod resolve {
+            use super::*;
+
+            #[test]
+            fn should_return_not_a_list_err() {
+                let ctx = Context::new("workflow1").with_vars(hash!("services", "api"));
+                let items = Items::List(Value::from("{{ vars.services }}"));
+                match items.resolve(&ctx) {
+                    Ok(_) => panic!("should fail"),
+                    Err(ItemsError::NotAList(val)) => assert_eq!(val, "api"),
+                    Err(err) => panic!("{}", err),
+                }
+            }
+
+            #[test]
+            fn should_return_list() {
+                let ctx = Context::new("workflow1").with_vars(hash!("service", "web"));
+                let items = Items::List(array!("api", "{{ vars.service }}").into());
+                let expected = vec![Value::from("api"), Value::from("web")];
+                assert_eq!(items.resolve(&ctx).unwrap(), expected);
+            }
+
+            #[test]
+            fn should_return_list_from_expression() {
+                let ctx =
+                    Context::new("workflow1").with_vars(hash!("services", array!("api", "web")));
+                let items = Items::List(Value::from("{{ vars.services }}"));
+                let expected = vec![Value::from("api"), Value::from("web")];
+                assert_eq!(items.resolve(&ctx).unwrap(), expected);
+            }
+
+            #[test]
+            fn should_return_matrix() {
+                let ctx = Context::new("workflow1");
+                let items = Items::Matrix(hash!(
+                    "service",
+                    array!("api", "web"),
+                    "region",
+                    array!("eu", "us")
+                ));
+                let expected = vec![
+                    Value::from(hash!("region", "eu", "service", "api")),
+                    Value::from(hash!("region", "eu", "service", "web")),
+                    Value::from(hash!("region", "us", "service", "api")),
+                    Value::from(hash!("region", "us", "service", "web")),
+                ];
+                assert_eq!(items.resolve(&ctx).unwrap(), expected);
+            }
+        }
+    }
+
+    mod items_error {
+        use super::*;
+
+        mod display {
+            use super::*;
+
+            #[test]
+            fn var() {
+                let err = ItemsError::Var(VarError::UndefinedItem);
+                assert_eq!(err.to_string(), VarError::UndefinedItem.to_string());
+            }
+
+            #[test]
+            fn not_a_list() {
+                let err = ItemsError::NotAList(String::from("api"));
+                assert_eq!(err.to_string(), "Loop items must be a list: api");
+            }
+        }
+    }
+
+    mod loop_action {
+        use super::*;
+
+        mod name {
+            use super::*;
+
+            #[test]
+            fn should_return_action_name() {
+                let action = LoopAction::new(
+                    action_stub!("action1", |_| Output::new(Status::Changed)),
+                    Items::List(array!("api").into()),
+                );
+                assert_eq!(action.name(), "action1");
+            }
+        }
+
+        mod run {
+            use super::*;
+
+            #[test]
+            fn should_return_output_with_failed_status_if_items_err() {
+                let action = LoopAction::new(
+                    action_stub!("action1", |_| panic!("should not be run")),
+                    Items::List(Value::from("{{ vars.services }}")),
+                );
+                let output = action.run(&Context::new("workflow1"));
+                assert_eq!(output.status(), Status::Failed);
+            }
+
+            #[test]
+            fn should_return_output_with_skipped_status_if_no_items() {
+                let expected = Output::new(Status::Skipped)
+                    .add_var("results", Array::from(Vec::<Value>::new()).into());
+                let action = LoopAction::new(
+                    action_stub!("action1", |_| panic!("should not be run")),
+                    Items::List(Array::from(Vec::<Value>::new()).into()),
+                );
+                let output = action.run(&Context::new("workflow1"));
+                assert_eq!(output, expected);
+            }
+
+            #[test]
+            fn should_return_aggregated_output() {
+                let expected = Output::new(Status::Failed).add_var(
+                    "results",
+                    Array::from(vec![
+                        Value::from(hash!("stdout", "api", "status", "changed")),
+                        Value::from(hash!("stdout", "web", "status", "failed")),
+                    ])
+                    .into(),
+                );
+                let action = LoopAction::new(
+                    action_stub!("action1", |ctx| {
+                        let item = ctx.item().unwrap().clone();
+                        let status = if item == Value::from("api") {
+                            Status::Changed
+                        } else {
+                            Status::Failed
+                        };
+                        Output::new(status).add_var("stdout", item)
+                    }),
+                    Items::List(array!("api", "web").into()),
+                );
+                let output = action.run(&Context::new("workflow1"));
+                assert_eq!(output, expected);
+            }
+        }
+    }
+}
@@ -1,7 +1,15 @@
 pub mod bash;
+pub mod block;
+pub mod cache;
+pub mod loops;
+pub mod workflow;
 
 use crate::{context::*, var::*};
-use std::fmt::{self, Display, Formatter};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::{self, Display, Formatter},
+    time::Duration,
+};
 
 pub static ACTION_NAME_PATTERN: &str = "[A-z0-9_]+";
 
@@ -16,12 +24,54 @@ pub trait Builder {
 }
 
 #[derive(Debug)]
-pub enum BuildError {}
+pub enum BuildError {
+    UnknownImport(String),
+    UnknownWorkflow(String),
+    NoDefaultWorkflow(String),
+    Recursion(Vec<String>),
+    UnknownHandler(String, String),
+    UnknownDependency(String, String),
+    UnknownStartAction(String, String),
+}
+
+impl Display for BuildError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Self::UnknownImport(path) => write!(f, "Workflow {} is not imported", path),
+            Self::UnknownWorkflow(name) => write!(f, "Workflow '{}' is not defined", name),
+            Self::NoDefaultWorkflow(path) => write!(f, "No default workflow defined in {}", path),
+            Self::Recursion(names) => {
+                write!(f, "Workflow recursion detected: {}", names.join(" -> "))
+            }
+            Self::UnknownHandler(action_name, handler_name) => write!(
+                f,
+                "Action '{}' notifies undefined handler '{}'",
+                action_name, handler_name
+            ),
+            Self::UnknownDependency(action_name, dep_name) => write!(
+                f,
+                "Action '{}' needs action '{}' which is not defined before it",
+                action_name, dep_name
+            ),
+            Self::UnknownStartAction(workflow_name, action_name) => write!(
+                f,
+                "Action '{}' is not defined in workflow '{}'",
+                action_name, workflow_name
+            ),
+        }
+    }
+}
 
-#[derive(Clone, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 pub struct Output {
     status: Status,
     vars: Hash,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    started_at: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    finished_at: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    duration: Option<Duration>,
 }
 
 impl Output {
@@ -29,6 +79,9 @@ impl Output {
         Self {
             status,
             vars: Hash::new(),
+            started_at: None,
+            finished_at: None,
+            duration: None,
         }
     }
 
@@ -37,25 +90,74 @@ impl Output {
         self
     }
 
+    pub fn duration(&self) -> Option<Duration> {
+        self.duration
+    }
+
+    pub fn finished_at(&self) -> Option<u64> {
+        self.finished_at
+    }
+
+    pub fn started_at(&self) -> Option<u64> {
+        self.started_at
+    }
+
     pub fn status(&self) -> Status {
         self.status
     }
 
+    pub fn reserved_vars(&self) -> Hash {
+        let mut vars = self.timing();
+        vars.insert(String::from("status"), self.status.to_string().into());
+        vars
+    }
+
+    pub fn timing(&self) -> Hash {
+        let mut timing = Hash::new();
+        if let Some(started_at) = self.started_at {
+            timing.insert(String::from("started_at"), started_at.into());
+        }
+        if let Some(finished_at) = self.finished_at {
+            timing.insert(String::from("finished_at"), finished_at.into());
+        }
+        if let Some(duration) = self.duration {
+            timing.insert(String::from("duration"), duration.as_secs_f64().into());
+        }
+        timing
+    }
+
     pub fn value(&self, name: &str) -> Option<&Value> {
-        self.vars.get(name)
+        lookup(&self.vars, name)
     }
 
     pub fn vars(&self) -> &Hash {
         &self.vars
     }
 
+    pub fn with_timing(mut self, started_at: u64, finished_at: u64, duration: Duration) -> Self {
+        self.started_at = Some(started_at);
+        self.finished_at = Some(finished_at);
+        self.duration = Some(duration);
+        self
+    }
+
     pub fn with_vars(mut self, vars: Hash) -> Self {
         self.vars = vars;
         self
     }
 }
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+impl From<Output> for Value {
+    fn from(output: Output) -> Self {
+        let reserved_vars = output.reserved_vars();
+        let mut vars = output.vars;
+        vars.extend(reserved_vars);
+        Self::Hash(vars)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Status {
     Unchanged,
     Changed,
@@ -63,6 +165,21 @@ pub enum Status {
     Skipped,
 }
 
+impl Status {
+    pub fn aggregate<I: IntoIterator<Item = Status>>(statuses: I) -> Self {
+        let statuses: Vec<Self> = statuses.into_iter().collect();
+        if statuses.contains(&Self::Failed) {
+            Self::Failed
+        } else if statuses.contains(&Self::Changed) {
+            Self::Changed
+        } else if !statuses.is_empty() && statuses.iter().all(|status| *status == Self::Skipped) {
+            Self::Skipped
+        } else {
+            Self::Unchanged
+        }
+    }
+}
+
 impl Display for Status {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         let status = match self {
@@ -88,6 +205,28 @@ pub mod test {
 
     pub(crate) use action_stub;
 
+    pub fn untimed(output: &Output) -> Output {
+        let vars = output
+            .vars()
+            .iter()
+            .map(|(name, val)| {
+                let val = match val {
+                    Value::Hash(hash) => Value::Hash(
+                        hash.iter()
+                            .filter(|(key, _)| {
+                                !matches!(key.as_str(), "started_at" | "finished_at" | "duration")
+                            })
+                            .map(|(key, val)| (key.clone(), val.clone()))
+                            .collect(),
+                    ),
+                    val => val.clone(),
+                };
+                (name.clone(), val)
+            })
+            .collect();
+        Output::new(output.status()).with_vars(vars)
+    }
+
     pub struct ActionStub {
         name: &'static str,
         run_fn: RunFn,
@@ -111,6 +250,78 @@ pub mod test {
 
     pub type RunFn = Box<dyn Fn(&Context) -> Output>;
 
+    mod build_error {
+        use super::*;
+
+        mod display {
+            use super::*;
+
+            #[test]
+            fn unknown_import() {
+                let err = BuildError::UnknownImport(String::from("deploy.yml"));
+                assert_eq!(err.to_string(), "Workflow deploy.yml is not imported");
+            }
+
+            #[test]
+            fn unknown_workflow() {
+                let err = BuildError::UnknownWorkflow(String::from("deploy"));
+                assert_eq!(err.to_string(), "Workflow 'deploy' is not defined");
+            }
+
+            #[test]
+            fn no_default_workflow() {
+                let err = BuildError::NoDefaultWorkflow(String::from("deploy.yml"));
+                assert_eq!(err.to_string(), "No default workflow defined in deploy.yml");
+            }
+
+            #[test]
+            fn recursion() {
+                let names = vec![
+                    String::from("build"),
+                    String::from("deploy"),
+                    String::from("build"),
+                ];
+                let err = BuildError::Recursion(names);
+                assert_eq!(
+                    err.to_string(),
+                    "Workflow recursion detected: build -> deploy -> build"
+                );
+            }
+
+            #[test]
+            fn unknown_handler() {
+                let err =
+                    BuildError::UnknownHandler(String::from("action1"), String::from("restart"));
+                assert_eq!(
+                    err.to_string(),
+                    "Action 'action1' notifies undefined handler 'restart'"
+                );
+            }
+
+            #[test]
+            fn unknown_dependency() {
+                let err =
+                    BuildError::UnknownDependency(String::from("deploy"), String::from("build"));
+                assert_eq!(
+                    err.to_string(),
+                    "Action 'deploy' needs action 'build' which is not defined before it"
+                );
+            }
+
+            #[test]
+            fn unknown_start_action() {
+                let err = BuildError::UnknownStartAction(
+                    String::from("workflow1"),
+                    String::from("deploy"),
+                );
+                assert_eq!(
+                    err.to_string(),
+                    "Action 'deploy' is not defined in workflow 'workflow1'"
+                );
+            }
+        }
+    }
+
     mod output {
         use super::*;
 
@@ -125,6 +336,9 @@ pub mod test {
                 let output = Output {
                     status: Status::Changed,
                     vars: Hash::new(),
+                    started_at: None,
+                    finished_at: None,
+                    duration: None,
                 };
                 let output = output.add_var(name, val.into());
                 assert_eq!(output.vars, expected);
@@ -140,6 +354,9 @@ pub mod test {
                 let expected = Output {
                     status,
                     vars: Hash::new(),
+                    started_at: None,
+                    finished_at: None,
+                    duration: None,
                 };
                 let output = Output::new(status);
                 assert_eq!(output, expected);
@@ -155,6 +372,9 @@ pub mod test {
                 let output = Output {
                     status: expected,
                     vars: Hash::new(),
+                    started_at: None,
+                    finished_at: None,
+                    duration: None,
                 };
                 assert_eq!(output.status(), expected);
             }
@@ -168,6 +388,9 @@ pub mod test {
                 let output = Output {
                     status: Status::Changed,
                     vars: Hash::new(),
+                    started_at: None,
+                    finished_at: None,
+                    duration: None,
                 };
                 let val = output.value("foo");
                 assert!(val.is_none());
@@ -180,6 +403,9 @@ pub mod test {
                 let output = Output {
                     status: Status::Changed,
                     vars: hash!(name, expected),
+                    started_at: None,
+                    finished_at: None,
+                    duration: None,
                 };
                 let val = output.value(name).unwrap();
                 assert_eq!(*val, expected.into());
@@ -195,12 +421,56 @@ pub mod test {
                 let output = Output {
                     status: Status::Changed,
                     vars: expected.clone(),
+                    started_at: None,
+                    finished_at: None,
+                    duration: None,
                 };
                 let vars = output.vars();
                 assert_eq!(*vars, expected);
             }
         }
 
+        mod timing {
+            use super::*;
+
+            #[test]
+            fn should_return_empty_hash_if_untimed() {
+                let output = Output::new(Status::Changed);
+                assert!(output.timing().is_empty());
+            }
+
+            #[test]
+            fn should_return_timing() {
+                let expected = hash!(
+                    "started_at",
+                    1000u64,
+                    "finished_at",
+                    2500u64,
+                    "duration",
+                    1.5
+                );
+                let output = Output::new(Status::Changed).with_timing(
+                    1000,
+                    2500,
+                    Duration::from_millis(1500),
+                );
+                assert_eq!(output.timing(), expected);
+            }
+        }
+
+        mod with_timing {
+            use super::*;
+
+            #[test]
+            fn should_set_timing() {
+                let expected = Duration::from_millis(15);
+                let output = Output::new(Status::Changed).with_timing(1000, 1015, expected);
+                assert_eq!(output.started_at(), Some(1000));
+                assert_eq!(output.finished_at(), Some(1015));
+                assert_eq!(output.duration(), Some(expected));
+            }
+        }
+
         mod with_vars {
             use super::*;
 
@@ -210,6 +480,9 @@ pub mod test {
                 let output = Output {
                     status: Status::Changed,
                     vars: expected.clone(),
+                    started_at: None,
+                    finished_at: None,
+                    duration: None,
                 };
                 let output = output.with_vars(expected.clone());
                 assert_eq!(output.vars, expected);
@@ -220,6 +493,37 @@ pub mod test {
     mod status {
         use super::*;
 
+        mod aggregate {
+            use super::*;
+
+            macro_rules! test {
+                ($name:ident, [$($statuses:expr),*], $expected:expr) => {
+                    #[test]
+                    fn $name() {
+                        assert_eq!(Status::aggregate([$($statuses),*]), $expected);
+                    }
+                };
+            }
+
+            test!(empty, [], Status::Unchanged);
+            test!(
+                failed,
+                [Status::Changed, Status::Failed, Status::Skipped],
+                Status::Failed
+            );
+            test!(
+                changed,
+                [Status::Unchanged, Status::Changed, Status::Skipped],
+                Status::Changed
+            );
+            test!(
+                unchanged,
+                [Status::Unchanged, Status::Skipped],
+                Status::Unchanged
+            );
+            test!(skipped, [Status::Skipped, Status::Skipped], Status::Skipped);
+        }
+
         mod display {
             use super::*;
 
@@ -242,4 +546,63 @@ pub mod test {
             test!(Status::Skipped, skipped);
         }
     }
+
+    mod value {
+        use super::*;
+
+        mod from {
+            use super::*;
+
+            #[test]
+            fn output() {
+                let expected = Value::Hash(hash!("stdout", "it works!", "status", "changed"));
+                let output = Output {
+                    status: Status::Changed,
+                    vars: hash!("stdout", "it works!"),
+                    started_at: None,
+                    finished_at: None,
+                    duration: None,
+                };
+                assert_eq!(Value::from(output), expected);
+            }
+
+            #[test]
+            fn output_with_status_and_timing_vars() {
+                let expected = Value::Hash(hash!(
+                    "status",
+                    "changed",
+                    "duration",
+                    0.25,
+                    "started_at",
+                    1000u64,
+                    "finished_at",
+                    1250u64
+                ));
+                let output = Output::new(Status::Changed)
+                    .with_vars(hash!("status", "custom", "duration", "long"))
+                    .with_timing(1000, 1250, Duration::from_millis(250));
+                assert_eq!(Value::from(output), expected);
+            }
+
+            #[test]
+            fn timed_output() {
+                let expected = Value::Hash(hash!(
+                    "status",
+                    "changed",
+                    "started_at",
+                    1000u64,
+                    "finished_at",
+                    1250u64,
+                    "duration",
+                    0.25
+                ));
+                let output = Output::new(Status::Changed).with_timing(
+                    1000,
+                    1250,
+                    Duration::from_millis(250),
+                );
+                assert_eq!(Value::from(output), expected);
+            }
+        }
+    }
 }
@@ -0,0 +1,211 @@
+use crate::{action::*, template, workflow::Workflow};
+use log::{debug, error};
+
+pub struct WorkflowAction {
+    name: String,
+    workflow: Workflow,
+    inputs: Hash,
+}
+
+impl WorkflowAction {
+    pub fn new(name: String, workflow: Workflow) -> Self {
+        Self {
+            name,
+            workflow,
+            inputs: Hash::new(),
+        }
+    }
+
+    pub fn with_inputs(mut self, inputs: Hash) -> Self {
+        self.inputs = inputs;
+        self
+    }
+}
+
+impl Action for WorkflowAction {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn run(&self, ctx: &Context) -> Output {
+        let inputs = match template::render_value(&Value::from(self.inputs.clone()), ctx) {
+            Ok(Value::Hash(inputs)) => inputs,
+            Ok(_) => Hash::new(),
+            Err(err) => {
+                error!("Unable to render inputs: {}", err);
+                return Output::new(Status::Failed).add_var("stderr", err.to_string().into());
+            }
+        };
+        debug!("Running workflow '{}'", self.workflow.name());
+        let outputs = self.workflow.run_observed(inputs, ctx.observers());
+        let status = Status::aggregate(outputs.values().map(Output::status));
+        let vars = outputs
+            .into_iter()
+            .map(|(name, output)| (name, Value::from(output)))
+            .collect();
+        Output::new(status).with_vars(vars)
+    }
+}
+
+pub struct WorkflowActionBuilder {
+    name: String,
+    workflow: Workflow,
+    inputs: Hash,
+}
+
+impl WorkflowActionBuilder {
+    pub fn new(name: String, workflow: Workflow) -> Self {
+        Self {
+            name,
+            workflow,
+            inputs: Hash::new(),
+        }
+    }
+
+    pub fn with_inputs(mut self, inputs: Hash) -> Self {
+        self.inputs = inputs;
+        self
+    }
+}
+
+impl Builder for WorkflowActionBuilder {
+    fn build(self, _ctx: &Context) -> Result<Box<dyn Action>, BuildError> {
+        let action = WorkflowAction::new(self.name, self.workflow).with_inputs(self.inputs);
+        Ok(Box::new(action))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{action::test::*, workflow::Step, *};
+
+    mod workflow_action {
+        use super::*;
+
+        mod new {
+            use super::*;
+
+            #[test]
+            fn should_return_action() {
+                let name = "action1";
+                let workflow_name = "workflow2";
+                let action = WorkflowAction::new(name.into(), Workflow::new(workflow_name.into()));
+                assert_eq!(action.name, name);
+                assert_eq!(action.workflow.name(), workflow_name);
+            }
+        }
+
+        mod name {
+            use super::*;
+
+            #[test]
+            fn should_return_name() {
+                let name = "action1";
+                let action = WorkflowAction {
+                    name: name.into(),
+                    workflow: Workflow::new(String::from("workflow2")),
+                    inputs: Hash::new(),
+                };
+                assert_eq!(action.name(), name);
+            }
+        }
+
+        mod run {
+            use super::*;
+
+            #[test]
+            fn should_return_output() {
+                let expected = Output::new(Status::Failed).with_vars(hash!(
+                    "action1",
+                    hash!("stdout", "it works!", "status", "changed"),
+                    "action2",
+                    hash!("status", "failed")
+                ));
+                let workflow = Workflow::new(String::from("workflow2")).with_steps(vec![
+                    Step::new(action_stub!("action1", |_| {
+                        Output::new(Status::Changed).add_var("stdout", "it works!".into())
+                    })),
+                    Step::new(action_stub!("action2", |_| Output::new(Status::Failed))),
+                ]);
+                let action = WorkflowAction {
+                    name: String::from("action1"),
+                    workflow,
+                    inputs: Hash::new(),
+                };
+                let output = action.run(&Context::new("workflow1"));
+                assert_eq!(untimed(&output), expected);
+            }
+
+            #[test]
+            fn should_pass_rendered_inputs() {
+                let workflow = Workflow::new(String::from("workflow2"))
+                    .with_vars(hash!("env", "dev", "region", "eu"))
+                    .with_steps(vec![Step::new(action_stub!("action1", |ctx| {
+                        assert_eq!(*ctx.vars(), hash!("env", "prod", "region", "eu"));
+                        Output::new(Status::Changed)
+                    }))]);
+                let action = WorkflowAction {
+                    name: String::from("action1"),
+                    workflow,
+                    inputs: hash!("env", "{{ vars.target }}"),
+                };
+                let ctx = Context::new("workflow1").with_vars(hash!("target", "prod"));
+                let output = action.run(&ctx);
+                assert_eq!(output.status(), Status::Changed);
+            }
+
+            #[test]
+            fn should_return_output_with_failed_status_if_render_err() {
+                let expected = Output::new(Status::Failed).add_var(
+                    "stderr",
+                    VarError::UndefinedVar(String::from("target"))
+                        .to_string()
+                        .into(),
+                );
+                let action = WorkflowAction {
+                    name: String::from("action1"),
+                    workflow: Workflow::new(String::from("workflow2")),
+                    inputs: hash!("env", "{{ vars.target }}"),
+                };
+                let output = action.run(&Context::new("workflow1"));
+                assert_eq!(output, expected);
+            }
+        }
+    }
+
+    mod workflow_action_builder {
+        use super::*;
+
+        mod new {
+            use super::*;
+
+            #[test]
+            fn should_return_builder() {
+                let name = "action1";
+                let builder = WorkflowActionBuilder::new(
+                    name.into(),
+                    Workflow::new(String::from("workflow2")),
+                );
+                assert_eq!(builder.name, name);
+            }
+        }
+
+        mod build {
+            use super::*;
+
+            #[test]
+            fn should_return_action() {
+                let ctx = Context::new("workflow1");
+                let name = "action1";
+                let builder = WorkflowActionBuilder {
+                    name: name.into(),
+                    workflow: Workflow::new(String::from("workflow2")),
+                    inputs: Hash::new(),
+                };
+                let action = builder.build(&ctx).unwrap();
+                assert_eq!(action.name(), name);
+            }
+        }
+    }
+}
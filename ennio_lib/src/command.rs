@@ -1,13 +1,36 @@
+use crate::secret;
 use log::{log_enabled, trace, Level};
+use serde::Serialize;
 use std::{
-    fmt::Display,
-    io,
+    cell::RefCell,
+    fmt::{self, Display, Formatter},
+    io::{self, BufRead, BufReader, Read},
     process::{Command as StdCommand, ExitStatus as StdExitStatus, Output as StdOutput, Stdio},
+    sync::mpsc,
+    thread,
 };
 
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+impl Display for Stream {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Self::Stdout => write!(f, "stdout"),
+            Self::Stderr => write!(f, "stderr"),
+        }
+    }
+}
+
 pub struct Command<'a> {
     program: &'a str,
     args: Vec<&'a str>,
+    secrets: Vec<&'a str>,
+    chunk_fn: &'a ChunkFn<'a>,
     execute_fn: ExecuteFn,
 }
 
@@ -16,14 +39,9 @@ impl<'a> Command<'a> {
         Self {
             program,
             args: vec![],
-            execute_fn: Box::new(|program, args| {
-                let output = StdCommand::new(program)
-                    .args(args)
-                    .stdout(Stdio::piped())
-                    .stderr(Stdio::piped())
-                    .output()?;
-                Ok(Box::new(output))
-            }),
+            secrets: vec![],
+            chunk_fn: &ignore_chunk,
+            execute_fn: Box::new(spawn),
         }
     }
 
@@ -36,32 +54,71 @@ impl<'a> Command<'a> {
             trace!(
                 "Executing command:\n{} {}",
                 self.program,
-                self.args.join(" ")
+                self.mask(&self.args.join(" "))
             );
         }
-        let output = match (self.execute_fn)(self.program, &self.args) {
+        let pending = RefCell::new([String::new(), String::new()]);
+        let on_chunk = |stream: Stream, chunk: &str| {
+            let mut pending = pending.borrow_mut();
+            let buffer = &mut pending[stream as usize];
+            buffer.push_str(chunk);
+            let masked = self.mask(buffer);
+            let idx = partial_secret_start(&masked, &self.secrets);
+            *buffer = masked[idx..].into();
+            if idx > 0 {
+                (self.chunk_fn)(stream, &masked[..idx]);
+            }
+        };
+        let res = (self.execute_fn)(self.program, &self.args, &on_chunk);
+        for (stream, buffer) in [Stream::Stdout, Stream::Stderr]
+            .into_iter()
+            .zip(pending.into_inner())
+        {
+            if !buffer.is_empty() {
+                (self.chunk_fn)(stream, &self.mask(&buffer));
+            }
+        }
+        let output = match res {
             Ok(output) => output,
             Err(err) => {
-                trace!("Unable to execute command: {}", err);
+                trace!("Unable to execute command: {}", self.mask(&err.to_string()));
                 return Err(err);
             }
         };
         if log_enabled!(Level::Trace) {
             trace!("Command terminated with {}", output.status());
-            trace!("Command stdout:\n{}", output.stdout());
-            trace!("Command stderr:\n{}", output.stderr());
+            trace!("Command stdout:\n{}", self.mask(&output.stdout()));
+            trace!("Command stderr:\n{}", self.mask(&output.stderr()));
         }
         Ok(output)
     }
 
+    pub fn mask(&self, text: &str) -> String {
+        secret::mask(text, &self.secrets)
+    }
+
     pub fn program(&self) -> &str {
         self.program
     }
 
+    pub fn secrets(&self) -> &[&str] {
+        &self.secrets
+    }
+
     pub fn with_args(mut self, args: Vec<&'a str>) -> Self {
         self.args = args;
         self
     }
+
+    pub fn with_chunk_fn(mut self, chunk_fn: &'a ChunkFn<'a>) -> Self {
+        self.chunk_fn = chunk_fn;
+        self
+    }
+
+    pub fn with_secrets(mut self, secrets: Vec<&'a str>) -> Self {
+        self.secrets = secrets;
+        self
+    }
 }
 
 pub trait ExitStatus: Display {
@@ -102,7 +159,78 @@ impl Output for StdOutput {
     }
 }
 
-type ExecuteFn = Box<dyn Fn(&str, &[&str]) -> io::Result<Box<dyn Output>>>;
+pub type ChunkFn<'a> = dyn Fn(Stream, &str) + 'a;
+
+type ExecuteFn = Box<dyn Fn(&str, &[&str], &ChunkFn) -> io::Result<Box<dyn Output>>>;
+
+fn ignore_chunk(_stream: Stream, _chunk: &str) {}
+
+fn partial_secret_start(text: &str, secrets: &[&str]) -> usize {
+    text.char_indices()
+        .map(|(idx, _)| idx)
+        .find(|idx| {
+            let tail = &text[*idx..];
+            secrets.iter().any(|secret| {
+                secret::is_masked(secret) && secret.len() > tail.len() && secret.starts_with(tail)
+            })
+        })
+        .unwrap_or(text.len())
+}
+
+fn spawn(program: &str, args: &[&str], on_chunk: &ChunkFn) -> io::Result<Box<dyn Output>> {
+    let mut child = StdCommand::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let (sender, receiver) = mpsc::channel();
+    let pipes: [(Stream, Option<Box<dyn Read + Send>>); 2] = [
+        (
+            Stream::Stdout,
+            child.stdout.take().map(|pipe| Box::new(pipe) as _),
+        ),
+        (
+            Stream::Stderr,
+            child.stderr.take().map(|pipe| Box::new(pipe) as _),
+        ),
+    ];
+    for (stream, pipe) in pipes {
+        let sender = sender.clone();
+        if let Some(pipe) = pipe {
+            thread::spawn(move || {
+                let mut reader = BufReader::new(pipe);
+                loop {
+                    let mut chunk = vec![];
+                    match reader.read_until(b'\n', &mut chunk) {
+                        Ok(0) | Err(_) => break,
+                        Ok(_) => {
+                            if sender.send((stream, chunk)).is_err() {
+                                break;
+                            }
+                        }
+                    }
+                }
+            });
+        }
+    }
+    drop(sender);
+    let mut stdout = vec![];
+    let mut stderr = vec![];
+    for (stream, chunk) in receiver {
+        on_chunk(stream, &String::from_utf8_lossy(&chunk));
+        match stream {
+            Stream::Stdout => stdout.extend(chunk),
+            Stream::Stderr => stderr.extend(chunk),
+        }
+    }
+    let status = child.wait()?;
+    Ok(Box::new(StdOutput {
+        status,
+        stdout,
+        stderr,
+    }))
+}
 
 #[cfg(test)]
 pub mod test {
@@ -182,7 +310,9 @@ pub mod test {
                 let cmd = Command {
                     program: "echo",
                     args: expected.clone(),
-                    execute_fn: Box::new(move |_, _| Ok(Box::new(OutputStub::default()))),
+                    secrets: vec![],
+                    chunk_fn: &ignore_chunk,
+                    execute_fn: Box::new(move |_, _, _| Ok(Box::new(OutputStub::default()))),
                 };
                 assert_eq!(cmd.args(), expected);
             }
@@ -197,7 +327,9 @@ pub mod test {
                 let cmd = Command {
                     program: "echo",
                     args: vec!["-n", "it works!"],
-                    execute_fn: Box::new(move |_, _| Err(io::Error::from(expected))),
+                    secrets: vec![],
+                    chunk_fn: &ignore_chunk,
+                    execute_fn: Box::new(move |_, _, _| Err(io::Error::from(expected))),
                 };
                 match cmd.execute() {
                     Ok(_) => panic!("should be fail"),
@@ -213,7 +345,9 @@ pub mod test {
                 let cmd = Command {
                     program: "echo",
                     args: vec!["-n", "it works!"],
-                    execute_fn: Box::new(move |_, _| {
+                    secrets: vec![],
+                    chunk_fn: &ignore_chunk,
+                    execute_fn: Box::new(move |_, _, _| {
                         Ok(Box::new(OutputStub::new(
                             code,
                             stdout.into(),
@@ -228,6 +362,90 @@ pub mod test {
             }
         }
 
+        mod execute_with_chunk_fn {
+            use super::*;
+
+            #[test]
+            fn should_stream_masked_chunks() {
+                let chunks = RefCell::new(vec![]);
+                let on_chunk = |stream: Stream, chunk: &str| {
+                    chunks.borrow_mut().push((stream, String::from(chunk)))
+                };
+                let output = Command::new("bash")
+                    .with_args(vec!["-c", "echo token=password; echo error >&2; echo done"])
+                    .with_secrets(vec!["password"])
+                    .with_chunk_fn(&on_chunk)
+                    .execute()
+                    .unwrap();
+                assert!(output.status().success());
+                assert_eq!(output.stdout(), "token=password\ndone\n");
+                assert_eq!(output.stderr(), "error\n");
+                let chunks = chunks.into_inner();
+                let stdout: Vec<&(Stream, String)> = chunks
+                    .iter()
+                    .filter(|(stream, _)| *stream == Stream::Stdout)
+                    .collect();
+                assert_eq!(
+                    stdout,
+                    vec![
+                        &(Stream::Stdout, String::from("token=***\n")),
+                        &(Stream::Stdout, String::from("done\n"))
+                    ]
+                );
+                assert!(chunks.contains(&(Stream::Stderr, String::from("error\n"))));
+            }
+
+            #[test]
+            fn should_mask_multi_line_secrets() {
+                let chunks = RefCell::new(vec![]);
+                let on_chunk = |stream: Stream, chunk: &str| {
+                    chunks.borrow_mut().push((stream, String::from(chunk)))
+                };
+                Command::new("bash")
+                    .with_args(vec!["-c", "printf 'a\\nline-one\\nline-two\\nb\\n'"])
+                    .with_secrets(vec!["line-one\nline-two"])
+                    .with_chunk_fn(&on_chunk)
+                    .execute()
+                    .unwrap();
+                assert_eq!(
+                    chunks.into_inner(),
+                    vec![
+                        (Stream::Stdout, String::from("a\n")),
+                        (Stream::Stdout, String::from("***\n")),
+                        (Stream::Stdout, String::from("b\n"))
+                    ]
+                );
+            }
+
+            #[test]
+            fn should_flush_partial_secret() {
+                let chunks = RefCell::new(vec![]);
+                let on_chunk = |stream: Stream, chunk: &str| {
+                    chunks.borrow_mut().push((stream, String::from(chunk)))
+                };
+                Command::new("bash")
+                    .with_args(vec!["-c", "echo line-one"])
+                    .with_secrets(vec!["line-one\nline-two"])
+                    .with_chunk_fn(&on_chunk)
+                    .execute()
+                    .unwrap();
+                assert_eq!(
+                    chunks.into_inner(),
+                    vec![(Stream::Stdout, String::from("line-one\n"))]
+                );
+            }
+        }
+
+        mod mask {
+            use super::*;
+
+            #[test]
+            fn should_mask_secrets() {
+                let cmd = Command::new("echo").with_secrets(vec!["password"]);
+                assert_eq!(cmd.mask("token=password"), "token=***");
+            }
+        }
+
         mod program {
             use super::*;
 
@@ -237,12 +455,31 @@ pub mod test {
                 let cmd = Command {
                     program: expected,
                     args: vec![],
-                    execute_fn: Box::new(move |_, _| Ok(Box::new(OutputStub::default()))),
+                    secrets: vec![],
+                    chunk_fn: &ignore_chunk,
+                    execute_fn: Box::new(move |_, _, _| Ok(Box::new(OutputStub::default()))),
                 };
                 assert_eq!(cmd.program(), expected);
             }
         }
 
+        mod secrets {
+            use super::*;
+
+            #[test]
+            fn should_return_secrets() {
+                let expected = vec!["password"];
+                let cmd = Command {
+                    program: "echo",
+                    args: vec![],
+                    secrets: expected.clone(),
+                    chunk_fn: &ignore_chunk,
+                    execute_fn: Box::new(move |_, _, _| Ok(Box::new(OutputStub::default()))),
+                };
+                assert_eq!(cmd.secrets(), expected);
+            }
+        }
+
         mod with_args {
             use super::*;
 
@@ -252,11 +489,43 @@ pub mod test {
                 let cmd = Command {
                     program: "echo",
                     args: vec![],
-                    execute_fn: Box::new(|_, _| Ok(Box::new(OutputStub::default()))),
+                    secrets: vec![],
+                    chunk_fn: &ignore_chunk,
+                    execute_fn: Box::new(|_, _, _| Ok(Box::new(OutputStub::default()))),
                 };
                 let cmd = cmd.with_args(expected.clone());
                 assert_eq!(cmd.args, expected);
             }
         }
+
+        mod with_secrets {
+            use super::*;
+
+            #[test]
+            fn should_set_secrets() {
+                let expected = vec!["password"];
+                let cmd = Command::new("echo").with_secrets(expected.clone());
+                assert_eq!(cmd.secrets, expected);
+            }
+        }
+    }
+
+    mod partial_secret_start {
+        use super::*;
+
+        #[test]
+        fn should_return_start_of_partial_secret() {
+            assert_eq!(partial_secret_start("foo=pass", &["password"]), 4);
+        }
+
+        #[test]
+        fn should_return_len_if_no_partial_secret() {
+            assert_eq!(partial_secret_start("foo=*** é", &["password"]), 10);
+        }
+
+        #[test]
+        fn should_ignore_short_secrets() {
+            assert_eq!(partial_secret_start("foo=t", &["false"]), 5);
+        }
     }
 }
@@ -1,39 +1,870 @@
+use crate::{
+    action::{bash::*, block::*, cache::*, loops::*, workflow::*, *},
+    context::*,
+    secret::*,
+    template,
+    var::*,
+    vault::{self, PassphraseFn},
+    workflow::*,
+};
 use jsonschema::JSONSchema;
 use log::{error, info};
+use regex::Regex;
+use serde::Deserialize;
 use serde_json;
 use serde_yaml;
 use std::{
+    collections::HashMap,
+    env,
     fmt::{self, Display, Formatter},
     fs,
+    path::{Path, PathBuf},
 };
 
-#[derive(Debug, Eq, PartialEq)]
-pub struct Config {}
+pub static ACTION_LIST_KEYS: &[&str] = &["actions", "handlers", "finally"];
+pub static BLOCK_LIST_KEYS: &[&str] = &["block", "rescue", "always"];
+pub static IMPORT_KEY: &str = "import";
+pub static INCLUDE_KEY: &str = "include";
+pub static NAMESPACES: &[&str] = &[
+    ENV_NAMESPACE,
+    ITEM_NAMESPACE,
+    SECRETS_NAMESPACE,
+    VARS_NAMESPACE,
+    WORKFLOW_NAMESPACE,
+];
+pub static RUN_VARS: &[&str] = &["stdout", "stderr", "rc", "reason"];
+pub static TIMING_VARS: &[&str] = &["started_at", "finished_at", "duration"];
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct ActionConfig {
+    name: String,
+    #[serde(default)]
+    vars: Hash,
+    #[serde(default, rename = "with")]
+    inputs: Hash,
+    #[serde(default, rename = "loop")]
+    items: Option<Value>,
+    #[serde(default)]
+    matrix: Option<Hash>,
+    #[serde(default)]
+    notify: Vec<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    needs: Vec<String>,
+    #[serde(default, rename = "inputs")]
+    cache_inputs: Option<CacheInputs>,
+    #[serde(default, rename = "outputs")]
+    cache_outputs: Vec<String>,
+    #[serde(default)]
+    creates: Option<String>,
+    #[serde(default)]
+    removes: Option<String>,
+    #[serde(default)]
+    rescue: Vec<ActionConfig>,
+    #[serde(default)]
+    always: Vec<ActionConfig>,
+    #[serde(flatten)]
+    kind: ActionKind,
+}
+
+impl ActionConfig {
+    pub fn always(&self) -> &[ActionConfig] {
+        &self.always
+    }
+
+    pub fn cache_inputs(&self) -> Option<&CacheInputs> {
+        self.cache_inputs.as_ref()
+    }
+
+    pub fn cache_outputs(&self) -> &[String] {
+        &self.cache_outputs
+    }
+
+    pub fn children(&self) -> Vec<&ActionConfig> {
+        let block: &[ActionConfig] = match &self.kind {
+            ActionKind::Block(block) => block,
+            _ => &[],
+        };
+        block
+            .iter()
+            .chain(self.rescue.iter())
+            .chain(self.always.iter())
+            .flat_map(|action_cfg| {
+                let mut action_cfgs = vec![action_cfg];
+                action_cfgs.extend(action_cfg.children());
+                action_cfgs
+            })
+            .collect()
+    }
+
+    pub fn creates(&self) -> Option<&str> {
+        self.creates.as_deref()
+    }
+
+    pub fn inputs(&self) -> &Hash {
+        &self.inputs
+    }
+
+    pub fn items(&self) -> Option<Items> {
+        match (&self.items, &self.matrix) {
+            (Some(items), _) => Some(Items::List(items.clone())),
+            (None, Some(dims)) => Some(Items::Matrix(dims.clone())),
+            (None, None) => None,
+        }
+    }
+
+    pub fn kind(&self) -> &ActionKind {
+        &self.kind
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn needs(&self) -> &[String] {
+        &self.needs
+    }
+
+    pub fn notify(&self) -> &[String] {
+        &self.notify
+    }
+
+    pub fn removes(&self) -> Option<&str> {
+        self.removes.as_deref()
+    }
+
+    pub fn references(&self) -> Vec<&str> {
+        let mut templates: Vec<&str> = vec![];
+        if let ActionKind::Run(script) = &self.kind {
+            templates.push(script);
+        }
+        templates.extend(self.creates.as_deref());
+        templates.extend(self.removes.as_deref());
+        if let Some(inputs) = &self.cache_inputs {
+            templates.extend(inputs.files().iter().map(String::as_str));
+        }
+        let mut values: Vec<&Value> = self.inputs.values().collect();
+        values.extend(self.items.as_ref());
+        values.extend(self.matrix.iter().flat_map(Hash::values));
+        for val in values {
+            collect_templates(val, &mut templates);
+        }
+        let mut var_names: Vec<&str> = templates
+            .into_iter()
+            .flat_map(template::expressions)
+            .filter(|expr| expr.default().is_none())
+            .map(|expr| expr.var_name())
+            .collect();
+        if let Some(inputs) = &self.cache_inputs {
+            var_names.extend(inputs.vars().iter().map(String::as_str));
+        }
+        var_names
+    }
+
+    pub fn rescue(&self) -> &[ActionConfig] {
+        &self.rescue
+    }
+
+    pub fn tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    pub fn vars(&self) -> &Hash {
+        &self.vars
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ActionKind {
+    Run(String),
+    Import(String),
+    Workflow(String),
+    Block(Vec<ActionConfig>),
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq)]
+pub struct WorkflowConfig {
+    #[serde(default)]
+    vars: Hash,
+    #[serde(default)]
+    actions: Vec<ActionConfig>,
+    #[serde(default)]
+    handlers: Vec<ActionConfig>,
+    #[serde(default)]
+    finally: Vec<ActionConfig>,
+}
+
+impl WorkflowConfig {
+    pub fn actions(&self) -> &[ActionConfig] {
+        &self.actions
+    }
+
+    pub fn finally(&self) -> &[ActionConfig] {
+        &self.finally
+    }
+
+    pub fn handlers(&self) -> &[ActionConfig] {
+        &self.handlers
+    }
+
+    pub fn steps(&self) -> impl Iterator<Item = &ActionConfig> {
+        self.actions
+            .iter()
+            .chain(self.handlers.iter())
+            .chain(self.finally.iter())
+    }
+
+    pub fn vars(&self) -> &Hash {
+        &self.vars
+    }
+
+    pub fn with_actions(mut self, actions: Vec<ActionConfig>) -> Self {
+        self.actions = actions;
+        self
+    }
+
+    pub fn with_finally(mut self, finally: Vec<ActionConfig>) -> Self {
+        self.finally = finally;
+        self
+    }
+
+    pub fn with_handlers(mut self, handlers: Vec<ActionConfig>) -> Self {
+        self.handlers = handlers;
+        self
+    }
+
+    pub fn with_vars(mut self, vars: Hash) -> Self {
+        self.vars = vars;
+        self
+    }
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq)]
+pub struct Config {
+    #[serde(default)]
+    default: Option<String>,
+    #[serde(default)]
+    strict_env: bool,
+    #[serde(default)]
+    vars: Hash,
+    #[serde(default, rename = "secrets")]
+    secret_sources: HashMap<String, SecretSource>,
+    #[serde(skip)]
+    secrets: Hash,
+    #[serde(default)]
+    vaults: Vec<String>,
+    #[serde(default)]
+    workflows: HashMap<String, WorkflowConfig>,
+    #[serde(skip)]
+    imports: HashMap<String, Config>,
+    #[serde(skip)]
+    base_dir: PathBuf,
+}
 
 impl Config {
+    pub fn base_dir(&self) -> &Path {
+        &self.base_dir
+    }
+
+    pub fn cache_dir(&self) -> PathBuf {
+        self.base_dir.join(CACHE_DIR)
+    }
+
+    pub fn default_workflow(&self) -> Option<&str> {
+        match &self.default {
+            Some(name) => Some(name),
+            None if self.workflows.len() == 1 => self.workflows.keys().next().map(String::as_str),
+            None => None,
+        }
+    }
+
+    pub fn imports(&self) -> &HashMap<String, Config> {
+        &self.imports
+    }
+
     pub fn load(filepath: &str) -> Result<Self, LoadingError> {
-        info!("Loading configuration from {}", filepath);
-        let file_content = fs::read_to_string(filepath).map_err(|err| {
-            let err = LoadingError::Reading(err.to_string());
-            error!("Unable to load configuration: {}", err);
-            err
-        })?;
-        let json = serde_yaml::from_str::<serde_json::Value>(&file_content).map_err(|err| {
-            let err = LoadingError::Parsing(err.to_string());
+        Self::load_with_passphrase(filepath, &vault::passphrase)
+    }
+
+    pub fn load_with_passphrase(
+        filepath: &str,
+        passphrase_fn: &PassphraseFn,
+    ) -> Result<Self, LoadingError> {
+        let filepath = Path::new(filepath);
+        let mut cfg = Self::load_file(filepath, &mut vec![], passphrase_fn).map_err(|err| {
             error!("Unable to load configuration: {}", err);
             err
         })?;
+        let base_dir = filepath.parent().unwrap_or_else(|| Path::new("."));
+        cfg.set_base_dir(base_dir);
+        Ok(cfg)
+    }
+
+    pub fn load_secrets(
+        &self,
+        base_dir: &Path,
+        passphrase_fn: &PassphraseFn,
+    ) -> Result<Hash, LoadingError> {
+        let mut secrets = Hash::new();
+        if !self.vaults.is_empty() {
+            let passphrase =
+                passphrase_fn().map_err(|err| LoadingError::Vault(self.vaults[0].clone(), err))?;
+            for path in self.vaults.iter() {
+                let vault_secrets = vault::load(&base_dir.join(path), &passphrase)
+                    .map_err(|err| LoadingError::Vault(path.clone(), err))?;
+                secrets.extend(vault_secrets);
+            }
+        }
+        for (name, src) in self.secret_sources.iter() {
+            let val = src
+                .load(base_dir)
+                .map_err(|err| LoadingError::Secret(name.clone(), err))?;
+            secrets.insert(name.clone(), val);
+        }
+        Ok(secrets)
+    }
+
+    pub fn invalid_references(&self) -> Vec<ReferenceError> {
+        let mut errs = vec![];
+        for workflow_name in self.workflow_names() {
+            let workflow_cfg = &self.workflows[workflow_name];
+            let mut previous = vec![];
+            for action_cfg in workflow_cfg.steps() {
+                self.check_references(
+                    workflow_name,
+                    workflow_cfg,
+                    action_cfg,
+                    &mut previous,
+                    &mut errs,
+                );
+            }
+        }
+        errs
+    }
+
+    pub fn missing_env_vars(&self) -> Vec<String> {
+        let env_prefix = format!("{}.", ENV_NAMESPACE);
+        let mut var_names: Vec<String> = self
+            .actions()
+            .flat_map(ActionConfig::references)
+            .filter_map(|var_name| var_name.strip_prefix(&env_prefix))
+            .filter(|var_name| env::var_os(var_name).is_none())
+            .map(String::from)
+            .collect();
+        var_names.sort();
+        var_names.dedup();
+        var_names
+    }
+
+    pub fn secret_sources(&self) -> &HashMap<String, SecretSource> {
+        &self.secret_sources
+    }
+
+    pub fn secrets(&self) -> &Hash {
+        &self.secrets
+    }
+
+    pub fn strict_env(&self) -> bool {
+        self.strict_env
+    }
+
+    pub fn vars(&self) -> &Hash {
+        &self.vars
+    }
+
+    pub fn vaults(&self) -> &[String] {
+        &self.vaults
+    }
+
+    pub fn workflow(&self, name: &str) -> Result<Workflow, BuildError> {
+        self.build_workflow(name, &mut vec![])
+    }
+
+    pub fn workflow_config(&self, name: &str) -> Option<&WorkflowConfig> {
+        self.workflows.get(name)
+    }
+
+    pub fn workflow_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.workflows.keys().map(String::as_str).collect();
+        names.sort();
+        names
+    }
+
+    pub fn workflows(&self) -> &HashMap<String, WorkflowConfig> {
+        &self.workflows
+    }
+
+    fn actions(&self) -> impl Iterator<Item = &ActionConfig> {
+        self.workflows.values().flat_map(|workflow_cfg| {
+            workflow_cfg.steps().flat_map(|action_cfg| {
+                let mut action_cfgs = vec![action_cfg];
+                action_cfgs.extend(action_cfg.children());
+                action_cfgs
+            })
+        })
+    }
+
+    fn check_references<'a>(
+        &self,
+        workflow_name: &str,
+        workflow_cfg: &WorkflowConfig,
+        action_cfg: &'a ActionConfig,
+        previous: &mut Vec<&'a ActionConfig>,
+        errs: &mut Vec<ReferenceError>,
+    ) {
+        let re = Regex::new(ACTION_NAME_PATTERN).unwrap();
+        for var_name in action_cfg.references() {
+            let Some(mat) = re.find(var_name) else {
+                continue;
+            };
+            let ref_name = mat.as_str();
+            if NAMESPACES.contains(&ref_name) {
+                continue;
+            }
+            let err = |err: fn(String, String, String) -> ReferenceError, reference: &str| {
+                err(
+                    workflow_name.into(),
+                    action_cfg.name.clone(),
+                    reference.into(),
+                )
+            };
+            match previous
+                .iter()
+                .rev()
+                .find(|prev_cfg| prev_cfg.name == ref_name)
+            {
+                Some(ref_cfg) => {
+                    let path = var_name[mat.end()..].strip_prefix('.').unwrap_or_default();
+                    if !path.is_empty() && !self.produces(ref_cfg, path) {
+                        errs.push(err(ReferenceError::UnknownVar, var_name));
+                    }
+                }
+                None if workflow_cfg.steps().any(|step_cfg| {
+                    step_cfg.name == ref_name
+                        || step_cfg
+                            .children()
+                            .iter()
+                            .any(|child_cfg| child_cfg.name == ref_name)
+                }) =>
+                {
+                    errs.push(err(ReferenceError::NotRunBefore, ref_name))
+                }
+                None => errs.push(err(ReferenceError::UnknownAction, ref_name)),
+            }
+        }
+        let block: &[ActionConfig] = match &action_cfg.kind {
+            ActionKind::Block(block) => block,
+            _ => &[],
+        };
+        let mut block_previous = previous.clone();
+        for child_cfg in block
+            .iter()
+            .chain(action_cfg.rescue.iter())
+            .chain(action_cfg.always.iter())
+        {
+            self.check_references(
+                workflow_name,
+                workflow_cfg,
+                child_cfg,
+                &mut block_previous,
+                errs,
+            );
+        }
+        previous.push(action_cfg);
+    }
+
+    fn produces(&self, action_cfg: &ActionConfig, path: &str) -> bool {
+        let (var_name, path) = match path.split_once('.') {
+            Some((var_name, path)) => (var_name, Some(path)),
+            None => (path, None),
+        };
+        if TIMING_VARS.contains(&var_name) || var_name == "status" {
+            return true;
+        }
+        if action_cfg.items().is_some() {
+            return var_name == "results";
+        }
+        let (cfg, children): (&Config, Vec<&ActionConfig>) = match &action_cfg.kind {
+            ActionKind::Run(_) => return RUN_VARS.contains(&var_name),
+            ActionKind::Block(block) => (
+                self,
+                block
+                    .iter()
+                    .chain(action_cfg.rescue.iter())
+                    .chain(action_cfg.always.iter())
+                    .collect(),
+            ),
+            ActionKind::Workflow(workflow_name) => match self.workflows.get(workflow_name) {
+                Some(workflow_cfg) => (self, workflow_cfg.steps().collect()),
+                None => return true,
+            },
+            ActionKind::Import(path) => {
+                let imported = self.imports.get(path).and_then(|cfg| {
+                    cfg.default_workflow()
+                        .and_then(|workflow_name| cfg.workflows.get(workflow_name))
+                        .map(|workflow_cfg| (cfg, workflow_cfg.steps().collect()))
+                });
+                match imported {
+                    Some(imported) => imported,
+                    None => return true,
+                }
+            }
+        };
+        match children.iter().find(|child_cfg| child_cfg.name == var_name) {
+            Some(child_cfg) => path.is_none_or(|path| cfg.produces(child_cfg, path)),
+            None => false,
+        }
+    }
+
+    fn build_workflow(&self, name: &str, stack: &mut Vec<String>) -> Result<Workflow, BuildError> {
+        let workflow_cfg = self
+            .workflows
+            .get(name)
+            .ok_or_else(|| BuildError::UnknownWorkflow(name.into()))?;
+        if stack.iter().any(|workflow_name| workflow_name == name) {
+            let mut names = stack.clone();
+            names.push(name.into());
+            return Err(BuildError::Recursion(names));
+        }
+        stack.push(name.into());
+        let mut vars = self.vars.clone();
+        vars.extend(workflow_cfg.vars.clone());
+        let ctx = Context::new(name)
+            .with_vars(vars.clone())
+            .with_secrets(self.secrets.clone());
+        let steps = workflow_cfg
+            .actions
+            .iter()
+            .map(|action_cfg| self.build_step(action_cfg, &ctx, stack))
+            .collect::<Result<Vec<Step>, BuildError>>()?;
+        let handlers = workflow_cfg
+            .handlers
+            .iter()
+            .map(|action_cfg| self.build_step(action_cfg, &ctx, stack))
+            .collect::<Result<Vec<Step>, BuildError>>()?;
+        let finally = workflow_cfg
+            .finally
+            .iter()
+            .map(|action_cfg| self.build_step(action_cfg, &ctx, stack))
+            .collect::<Result<Vec<Step>, BuildError>>()?;
+        for (idx, action_cfg) in workflow_cfg.actions.iter().enumerate() {
+            for dep_name in action_cfg.needs.iter() {
+                if !workflow_cfg.actions[..idx]
+                    .iter()
+                    .any(|dep_cfg| dep_cfg.name == *dep_name)
+                {
+                    return Err(BuildError::UnknownDependency(
+                        action_cfg.name.clone(),
+                        dep_name.clone(),
+                    ));
+                }
+            }
+        }
+        for action_cfg in workflow_cfg.actions.iter().flat_map(|action_cfg| {
+            let mut action_cfgs = vec![action_cfg];
+            action_cfgs.extend(action_cfg.children());
+            action_cfgs
+        }) {
+            for handler_name in action_cfg.notify.iter() {
+                if !workflow_cfg
+                    .handlers
+                    .iter()
+                    .any(|handler_cfg| handler_cfg.name == *handler_name)
+                {
+                    return Err(BuildError::UnknownHandler(
+                        action_cfg.name.clone(),
+                        handler_name.clone(),
+                    ));
+                }
+            }
+        }
+        stack.pop();
+        Ok(Workflow::new(name.into())
+            .with_vars(vars)
+            .with_secrets(self.secrets.clone())
+            .with_steps(steps)
+            .with_handlers(handlers)
+            .with_finally(finally))
+    }
+
+    fn build_step(
+        &self,
+        action_cfg: &ActionConfig,
+        ctx: &Context,
+        stack: &mut Vec<String>,
+    ) -> Result<Step, BuildError> {
+        let action_name = action_cfg.name.clone();
+        let ctx = ctx.scope(&action_cfg.vars);
+        let action = match &action_cfg.kind {
+            ActionKind::Run(script) => {
+                let action = BashActionBuilder::new(action_name, script.clone())
+                    .with_creates(action_cfg.creates.clone())
+                    .with_removes(action_cfg.removes.clone())
+                    .build(&ctx)?;
+                match &action_cfg.cache_inputs {
+                    Some(inputs) => CachedActionBuilder::new(
+                        action,
+                        script.clone(),
+                        inputs.clone(),
+                        Cache::new(self.cache_dir()),
+                    )
+                    .with_outputs(action_cfg.cache_outputs.clone())
+                    .with_base_dir(self.base_dir.clone())
+                    .build(&ctx)?,
+                    None => action,
+                }
+            }
+            ActionKind::Import(path) => {
+                let cfg = self
+                    .imports
+                    .get(path)
+                    .ok_or_else(|| BuildError::UnknownImport(path.clone()))?;
+                let workflow_name = cfg
+                    .default_workflow()
+                    .ok_or_else(|| BuildError::NoDefaultWorkflow(path.clone()))?;
+                WorkflowActionBuilder::new(action_name, cfg.workflow(workflow_name)?)
+                    .with_inputs(action_cfg.inputs.clone())
+                    .build(&ctx)?
+            }
+            ActionKind::Workflow(workflow_name) => {
+                let workflow = self.build_workflow(workflow_name, stack)?;
+                WorkflowActionBuilder::new(action_name, workflow)
+                    .with_inputs(action_cfg.inputs.clone())
+                    .build(&ctx)?
+            }
+            ActionKind::Block(block) => {
+                let mut build_steps = |action_cfgs: &[ActionConfig]| {
+                    action_cfgs
+                        .iter()
+                        .map(|action_cfg| self.build_step(action_cfg, &ctx, stack))
+                        .collect::<Result<Vec<Step>, BuildError>>()
+                };
+                let steps = build_steps(block)?;
+                let rescue = build_steps(&action_cfg.rescue)?;
+                let always = build_steps(&action_cfg.always)?;
+                BlockActionBuilder::new(action_name, steps)
+                    .with_rescue(rescue)
+                    .with_always(always)
+                    .build(&ctx)?
+            }
+        };
+        let action = match action_cfg.items() {
+            Some(items) => LoopActionBuilder::new(action, items).build(&ctx)?,
+            None => action,
+        };
+        Ok(Step::new(action)
+            .with_vars(action_cfg.vars.clone())
+            .with_notify(action_cfg.notify.clone())
+            .with_tags(action_cfg.tags.clone())
+            .with_needs(action_cfg.needs.clone()))
+    }
+
+    fn load_file(
+        filepath: &Path,
+        stack: &mut Vec<PathBuf>,
+        passphrase_fn: &PassphraseFn,
+    ) -> Result<Self, LoadingError> {
+        info!("Loading configuration from {}", filepath.display());
+        let base_dir = filepath.parent().unwrap_or_else(|| Path::new("."));
+        enter(filepath, stack)?;
+        let mut json = read_yaml(filepath)?;
+        resolve_workflow_includes(&mut json, base_dir, stack)?;
+        if let Some(workflows) = json
+            .get_mut("workflows")
+            .and_then(|val| val.as_object_mut())
+        {
+            for workflow in workflows.values_mut() {
+                resolve_workflow_includes(workflow, base_dir, stack)?;
+            }
+        }
         let schema_json = String::from_utf8_lossy(include_bytes!("../resources/ennio.schema.json"));
         let schema = serde_json::from_str::<serde_json::Value>(&schema_json).unwrap();
         let schema = JSONSchema::compile(&schema).unwrap();
         schema.validate(&json).map_err(|errs| {
             let msgs: Vec<String> = errs.map(|err| err.to_string()).collect();
-            let err = LoadingError::Validating(msgs);
-            error!("Unable to load configuration: {}", err);
-            err
+            LoadingError::Validating(msgs)
         })?;
-        Ok(Config {})
+        let mut cfg: Self = serde_json::from_value(single_workflow_to_workflows(json))
+            .map_err(|err| LoadingError::Parsing(err.to_string()))?;
+        if let Some(name) = &cfg.default {
+            if !cfg.workflows.contains_key(name) {
+                return Err(LoadingError::UnknownDefaultWorkflow(name.clone()));
+            }
+        }
+        for workflow_name in cfg.workflow_names() {
+            let workflow_cfg = &cfg.workflows[workflow_name];
+            let not_notifying = workflow_cfg
+                .handlers
+                .iter()
+                .chain(workflow_cfg.finally.iter())
+                .flat_map(|action_cfg| {
+                    let mut action_cfgs = vec![action_cfg];
+                    action_cfgs.extend(action_cfg.children());
+                    action_cfgs
+                });
+            for action_cfg in not_notifying {
+                if !action_cfg.notify.is_empty() {
+                    return Err(LoadingError::UnexpectedNotify(
+                        workflow_name.into(),
+                        action_cfg.name.clone(),
+                    ));
+                }
+            }
+        }
+        if cfg.strict_env {
+            let missing_env_vars = cfg.missing_env_vars();
+            if !missing_env_vars.is_empty() {
+                return Err(LoadingError::MissingEnvVars(missing_env_vars));
+            }
+        }
+        cfg.secrets = cfg.load_secrets(base_dir, passphrase_fn)?;
+        cfg.imports = cfg.load_imports(stack, passphrase_fn)?;
+        let invalid_references = cfg.invalid_references();
+        if !invalid_references.is_empty() {
+            return Err(LoadingError::InvalidReferences(invalid_references));
+        }
+        stack.pop();
+        Ok(cfg)
+    }
+
+    fn set_base_dir(&mut self, base_dir: &Path) {
+        self.base_dir = base_dir.into();
+        for cfg in self.imports.values_mut() {
+            cfg.set_base_dir(base_dir);
+        }
+    }
+
+    fn load_imports(
+        &self,
+        stack: &mut Vec<PathBuf>,
+        passphrase_fn: &PassphraseFn,
+    ) -> Result<HashMap<String, Config>, LoadingError> {
+        let mut imports = HashMap::new();
+        for action_cfg in self.actions() {
+            if let ActionKind::Import(path) = &action_cfg.kind {
+                if !imports.contains_key(path) {
+                    let cfg = Self::load_file(Path::new(path), stack, passphrase_fn)?;
+                    imports.insert(path.clone(), cfg);
+                }
+            }
+        }
+        Ok(imports)
+    }
+}
+
+fn collect_templates<'a>(val: &'a Value, templates: &mut Vec<&'a str>) {
+    match val {
+        Value::String(template) => templates.push(template),
+        Value::Array(array) => array
+            .items()
+            .iter()
+            .for_each(|item| collect_templates(item, templates)),
+        Value::Hash(hash) => hash
+            .values()
+            .for_each(|item| collect_templates(item, templates)),
+        _ => {}
+    }
+}
+
+fn enter(filepath: &Path, stack: &mut Vec<PathBuf>) -> Result<(), LoadingError> {
+    let filepath = filepath
+        .canonicalize()
+        .map_err(|err| LoadingError::Reading(format!("{}: {}", filepath.display(), err)))?;
+    if stack.contains(&filepath) {
+        let mut cycle: Vec<String> = stack
+            .iter()
+            .skip_while(|path| **path != filepath)
+            .map(|path| path.display().to_string())
+            .collect();
+        cycle.push(filepath.display().to_string());
+        return Err(LoadingError::Cycle(cycle));
+    }
+    stack.push(filepath);
+    Ok(())
+}
+
+fn read_yaml(filepath: &Path) -> Result<serde_json::Value, LoadingError> {
+    let file_content = fs::read_to_string(filepath)
+        .map_err(|err| LoadingError::Reading(format!("{}: {}", filepath.display(), err)))?;
+    serde_yaml::from_str(&file_content).map_err(|err| LoadingError::Parsing(err.to_string()))
+}
+
+fn single_workflow_to_workflows(mut json: serde_json::Value) -> serde_json::Value {
+    if let Some(root) = json.as_object_mut() {
+        if let Some(serde_json::Value::String(name)) = root.remove("name") {
+            let workflow: serde_json::Map<String, serde_json::Value> = ACTION_LIST_KEYS
+                .iter()
+                .filter_map(|key| {
+                    root.remove(*key)
+                        .map(|actions| (String::from(*key), actions))
+                })
+                .collect();
+            root.insert(
+                String::from("workflows"),
+                serde_json::json!({ name.clone(): workflow }),
+            );
+            root.entry("default")
+                .or_insert(serde_json::Value::String(name));
+        }
+    }
+    json
+}
+
+fn resolve_workflow_includes(
+    workflow: &mut serde_json::Value,
+    base_dir: &Path,
+    stack: &mut Vec<PathBuf>,
+) -> Result<(), LoadingError> {
+    for key in ACTION_LIST_KEYS {
+        if let Some(actions) = workflow.get_mut(*key).and_then(|val| val.as_array_mut()) {
+            *actions = resolve_includes(std::mem::take(actions), base_dir, stack)?;
+        }
+    }
+    Ok(())
+}
+
+fn resolve_includes(
+    actions: Vec<serde_json::Value>,
+    base_dir: &Path,
+    stack: &mut Vec<PathBuf>,
+) -> Result<Vec<serde_json::Value>, LoadingError> {
+    let mut resolved = vec![];
+    for mut action in actions {
+        match action.get(INCLUDE_KEY).and_then(|val| val.as_str()) {
+            Some(path) => {
+                let path = base_dir.join(path);
+                info!("Including actions from {}", path.display());
+                enter(&path, stack)?;
+                let included = match read_yaml(&path)? {
+                    serde_json::Value::Array(actions) => actions,
+                    serde_json::Value::Null => vec![],
+                    _ => {
+                        return Err(LoadingError::Validating(vec![format!(
+                            "{} is not a list of actions",
+                            path.display()
+                        )]))
+                    }
+                };
+                let include_dir = path.parent().unwrap_or_else(|| Path::new("."));
+                resolved.extend(resolve_includes(included, include_dir, stack)?);
+                stack.pop();
+            }
+            None => {
+                if let Some(serde_json::Value::String(path)) = action.get_mut(IMPORT_KEY) {
+                    *path = base_dir.join(&path).display().to_string();
+                }
+                for key in BLOCK_LIST_KEYS {
+                    if let Some(actions) = action.get_mut(*key).and_then(|val| val.as_array_mut()) {
+                        *actions = resolve_includes(std::mem::take(actions), base_dir, stack)?;
+                    }
+                }
+                resolved.push(action);
+            }
+        }
     }
+    Ok(resolved)
 }
 
 #[derive(Debug)]
@@ -41,6 +872,13 @@ pub enum LoadingError {
     Reading(String),
     Parsing(String),
     Validating(Vec<String>),
+    MissingEnvVars(Vec<String>),
+    Secret(String, SecretError),
+    Vault(String, SecretError),
+    Cycle(Vec<String>),
+    UnknownDefaultWorkflow(String),
+    UnexpectedNotify(String, String),
+    InvalidReferences(Vec<ReferenceError>),
 }
 
 impl Display for LoadingError {
@@ -49,6 +887,55 @@ impl Display for LoadingError {
             Self::Reading(err) => write!(f, "{}", err),
             Self::Parsing(err) => write!(f, "{}", err),
             Self::Validating(msgs) => write!(f, "{}", msgs.join(", ")),
+            Self::MissingEnvVars(var_names) => write!(
+                f,
+                "Environment variables are not defined: {}",
+                var_names.join(", ")
+            ),
+            Self::Secret(name, err) => write!(f, "Unable to load secret '{}': {}", name, err),
+            Self::Vault(path, err) => write!(f, "Unable to load vault {}: {}", path, err),
+            Self::Cycle(paths) => write!(f, "Include cycle detected: {}", paths.join(" -> ")),
+            Self::UnknownDefaultWorkflow(name) => {
+                write!(f, "Default workflow '{}' is not defined", name)
+            }
+            Self::UnexpectedNotify(workflow_name, action_name) => write!(
+                f,
+                "Action '{}' of workflow '{}' cannot notify handlers, only actions and their nested actions can",
+                action_name, workflow_name
+            ),
+            Self::InvalidReferences(errs) => {
+                let msgs: Vec<String> = errs.iter().map(ReferenceError::to_string).collect();
+                write!(f, "{}", msgs.join(", "))
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ReferenceError {
+    UnknownAction(String, String, String),
+    NotRunBefore(String, String, String),
+    UnknownVar(String, String, String),
+}
+
+impl Display for ReferenceError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Self::UnknownAction(workflow_name, action_name, ref_name) => write!(
+                f,
+                "Action '{}' of workflow '{}' references undefined action '{}'",
+                action_name, workflow_name, ref_name
+            ),
+            Self::NotRunBefore(workflow_name, action_name, ref_name) => write!(
+                f,
+                "Action '{}' of workflow '{}' references action '{}' which does not run before it",
+                action_name, workflow_name, ref_name
+            ),
+            Self::UnknownVar(workflow_name, action_name, var_name) => write!(
+                f,
+                "Action '{}' of workflow '{}' references '{}' which is never set",
+                action_name, workflow_name, var_name
+            ),
         }
     }
 }
@@ -56,66 +943,1082 @@ impl Display for LoadingError {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::*;
 
-    mod config {
+    fn action_config(name: &str, kind: ActionKind) -> ActionConfig {
+        ActionConfig {
+            name: name.into(),
+            vars: Hash::new(),
+            inputs: Hash::new(),
+            items: None,
+            matrix: None,
+            notify: vec![],
+            tags: vec![],
+            needs: vec![],
+            cache_inputs: None,
+            cache_outputs: vec![],
+            creates: None,
+            removes: None,
+            rescue: vec![],
+            always: vec![],
+            kind,
+        }
+    }
+
+    mod action_config {
         use super::*;
 
-        mod load {
+        mod children {
             use super::*;
 
             #[test]
-            fn should_return_reading_err() {
-                match Config::load("./null") {
-                    Ok(_) => panic!("should fail"),
-                    Err(LoadingError::Reading(_)) => {}
-                    Err(err) => panic!("{}", err),
-                }
-            }
-
-            #[test]
-            fn should_return_parsing_err() {
-                match Config::load("./Cargo.toml") {
-                    Ok(_) => panic!("should fail"),
-                    Err(LoadingError::Parsing(_)) => {}
-                    Err(err) => panic!("{}", err),
-                }
-            }
-
-            #[test]
-            fn should_return_config() {
-                let expected = Config {};
-                let cfg = Config::load("./test/ennio.yml").unwrap();
-                assert_eq!(cfg, expected);
+            fn should_return_children() {
+                let run = |name: &str| action_config(name, ActionKind::Run(String::from("true")));
+                let mut block = action_config(
+                    "block1",
+                    ActionKind::Block(vec![
+                        run("action1"),
+                        action_config("block2", ActionKind::Block(vec![run("action2")])),
+                    ]),
+                );
+                block.rescue = vec![run("action3")];
+                block.always = vec![run("action4")];
+                let names: Vec<&str> = block
+                    .children()
+                    .into_iter()
+                    .map(ActionConfig::name)
+                    .collect();
+                assert_eq!(
+                    names,
+                    vec!["action1", "block2", "action2", "action3", "action4"]
+                );
             }
         }
-    }
-
-    mod loading_error {
-        use super::*;
 
-        mod display {
+        mod items {
             use super::*;
 
             macro_rules! test {
-                ($name:ident, $value:expr) => {
+                ($name:ident, $items:expr, $matrix:expr, $expected:expr) => {
                     #[test]
                     fn $name() {
-                        let msg = "error";
-                        let err = $value(String::from(msg));
-                        assert_eq!(err.to_string(), msg);
+                        let action_cfg = ActionConfig {
+                            items: $items,
+                            matrix: $matrix,
+                            ..action_config(
+                                "action1",
+                                ActionKind::Run(String::from("echo {{ item }}")),
+                            )
+                        };
+                        assert_eq!(action_cfg.items(), $expected);
                     }
                 };
             }
 
-            test!(parsing, LoadingError::Parsing);
-            test!(reading, LoadingError::Reading);
+            test!(should_return_none, None, None, None);
+            test!(
+                should_return_list,
+                Some(Value::from("{{ vars.services }}")),
+                None,
+                Some(Items::List(Value::from("{{ vars.services }}")))
+            );
+            test!(
+                should_return_matrix,
+                None,
+                Some(hash!("region", array!("eu", "us"))),
+                Some(Items::Matrix(hash!("region", array!("eu", "us"))))
+            );
+        }
+
+        mod inputs {
+            use super::*;
 
             #[test]
-            fn validating() {
-                let msgs = vec![String::from("error1"), String::from("error2")];
-                let err = LoadingError::Validating(msgs.clone());
-                assert_eq!(err.to_string(), msgs.join(", "));
+            fn should_return_inputs() {
+                let expected = hash!("service", "api");
+                let action_cfg = ActionConfig {
+                    inputs: expected.clone(),
+                    ..action_config("action1", ActionKind::Workflow(String::from("deploy")))
+                };
+                assert_eq!(*action_cfg.inputs(), expected);
+            }
+        }
+
+        mod kind {
+            use super::*;
+
+            #[test]
+            fn should_return_kind() {
+                let expected = ActionKind::Run(String::from("echo it works!"));
+                let action_cfg =
+                    action_config("action1", ActionKind::Run(String::from("echo it works!")));
+                assert_eq!(*action_cfg.kind(), expected);
             }
         }
+
+        mod name {
+            use super::*;
+
+            #[test]
+            fn should_return_name() {
+                let expected = "action1";
+                let action_cfg =
+                    action_config(expected, ActionKind::Run(String::from("echo it works!")));
+                assert_eq!(action_cfg.name(), expected);
+            }
+        }
+
+        mod references {
+            use super::*;
+
+            #[test]
+            fn should_return_references() {
+                let action_cfg = ActionConfig {
+                    vars: hash!("ignored", "{{ action0.stdout }}"),
+                    items: Some(Value::from(array!("{{ action0.results }}"))),
+                    cache_inputs: Some(CacheInputs::new(
+                        vec![String::from("{{ vars.dir }}/*.rs")],
+                        vec![String::from("vars.target")],
+                    )),
+                    creates: Some(String::from("{{ action0.stdout }}")),
+                    ..action_config(
+                        "action1",
+                        ActionKind::Run(String::from(
+                            "echo {{ item }} {{ env.HOME | default(/root) }}",
+                        )),
+                    )
+                };
+                assert_eq!(
+                    action_cfg.references(),
+                    vec![
+                        "item",
+                        "action0.stdout",
+                        "vars.dir",
+                        "action0.results",
+                        "vars.target"
+                    ]
+                );
+            }
+        }
+
+        mod vars {
+            use super::*;
+
+            #[test]
+            fn should_return_vars() {
+                let expected = hash!("foo", true);
+                let action_cfg = ActionConfig {
+                    vars: expected.clone(),
+                    ..action_config("action1", ActionKind::Run(String::from("echo it works!")))
+                };
+                assert_eq!(*action_cfg.vars(), expected);
+            }
+        }
+    }
+
+    mod config {
+        use super::*;
+
+        mod default_workflow {
+            use super::*;
+
+            macro_rules! test {
+                ($name:ident, $default:expr, $names:expr, $expected:expr) => {
+                    #[test]
+                    fn $name() {
+                        let workflows = $names
+                            .iter()
+                            .map(|name: &&str| (String::from(*name), WorkflowConfig::default()))
+                            .collect();
+                        let cfg = Config {
+                            default: $default.map(String::from),
+                            workflows,
+                            ..Config::default()
+                        };
+                        assert_eq!(cfg.default_workflow(), $expected);
+                    }
+                };
+            }
+
+            test!(
+                should_return_default,
+                Some("deploy"),
+                ["build", "deploy"],
+                Some("deploy")
+            );
+            test!(
+                should_return_single_workflow,
+                None::<&str>,
+                ["build"],
+                Some("build")
+            );
+            test!(should_return_none, None::<&str>, ["build", "deploy"], None);
+        }
+
+        mod invalid_references {
+            use super::*;
+
+            #[test]
+            fn should_return_empty_vec_if_references_are_valid() {
+                let cfg = Config::load("./test/references.yml").unwrap();
+                assert!(cfg.invalid_references().is_empty());
+            }
+        }
+
+        mod load {
+            use super::*;
+
+            #[test]
+            fn should_return_invalid_references_err() {
+                let err = |variant: fn(String, String, String) -> ReferenceError,
+                           action_name: &str,
+                           reference: &str| {
+                    variant(
+                        String::from("workflow1"),
+                        String::from(action_name),
+                        String::from(reference),
+                    )
+                };
+                match Config::load("./test/invalid_references.yml") {
+                    Ok(_) => panic!("should fail"),
+                    Err(LoadingError::InvalidReferences(errs)) => assert_eq!(
+                        errs,
+                        vec![
+                            err(ReferenceError::NotRunBefore, "build", "deploy"),
+                            err(ReferenceError::UnknownAction, "build", "buidl"),
+                            err(ReferenceError::UnknownVar, "migrate", "build.output"),
+                            err(ReferenceError::UnknownVar, "notify", "deploy.migrate.stdot"),
+                            err(ReferenceError::NotRunBefore, "notify", "migrate"),
+                        ]
+                    ),
+                    Err(err) => panic!("{}", err),
+                }
+            }
+
+            #[test]
+            fn should_return_reading_err() {
+                match Config::load("./null") {
+                    Ok(_) => panic!("should fail"),
+                    Err(LoadingError::Reading(_)) => {}
+                    Err(err) => panic!("{}", err),
+                }
+            }
+
+            #[test]
+            fn should_return_parsing_err() {
+                match Config::load("./Cargo.toml") {
+                    Ok(_) => panic!("should fail"),
+                    Err(LoadingError::Parsing(_)) => {}
+                    Err(err) => panic!("{}", err),
+                }
+            }
+
+            #[test]
+            fn should_return_missing_env_vars_err() {
+                match Config::load("./test/strict_env.yml") {
+                    Ok(_) => panic!("should fail"),
+                    Err(LoadingError::MissingEnvVars(var_names)) => {
+                        assert_eq!(var_names, vec![String::from("ENNIO_UNDEFINED_VAR")])
+                    }
+                    Err(err) => panic!("{}", err),
+                }
+            }
+
+            #[test]
+            fn should_return_secret_err() {
+                match Config::load("./test/missing_secret.yml") {
+                    Ok(_) => panic!("should fail"),
+                    Err(LoadingError::Secret(name, SecretError::Reading(_, _))) => {
+                        assert_eq!(name, "token")
+                    }
+                    Err(err) => panic!("{}", err),
+                }
+            }
+
+            #[test]
+            fn should_return_include_cycle_err() {
+                match Config::load("./test/include_cycle.yml") {
+                    Ok(_) => panic!("should fail"),
+                    Err(LoadingError::Cycle(paths)) => {
+                        assert_eq!(paths.len(), 3);
+                        assert!(paths[0].ends_with("a.yml"));
+                        assert!(paths[1].ends_with("b.yml"));
+                        assert!(paths[2].ends_with("a.yml"));
+                    }
+                    Err(err) => panic!("{}", err),
+                }
+            }
+
+            #[test]
+            fn should_return_import_cycle_err() {
+                match Config::load("./test/import_cycle.yml") {
+                    Ok(_) => panic!("should fail"),
+                    Err(LoadingError::Cycle(paths)) => {
+                        assert_eq!(paths.len(), 2);
+                        assert!(paths[0].ends_with("import_cycle.yml"));
+                        assert!(paths[1].ends_with("import_cycle.yml"));
+                    }
+                    Err(err) => panic!("{}", err),
+                }
+            }
+
+            #[test]
+            fn should_return_config_with_cache_dir_next_to_file() {
+                let cfg = Config::load("./test/include.yml").unwrap();
+                let cache_dir = Path::new("./test").join(CACHE_DIR);
+                assert_eq!(cfg.base_dir(), Path::new("./test"));
+                assert_eq!(cfg.cache_dir(), cache_dir);
+                assert!(cfg
+                    .imports()
+                    .values()
+                    .all(|import_cfg| import_cfg.cache_dir() == cache_dir));
+            }
+
+            #[test]
+            fn should_return_config_with_included_actions() {
+                let cfg = Config::load("./test/include.yml").unwrap();
+                let actions = cfg.workflow_config("workflow1").unwrap().actions();
+                let names: Vec<&str> = actions.iter().map(ActionConfig::name).collect();
+                assert_eq!(
+                    names,
+                    vec!["before", "included_echo", "nested_echo", "deploy", "after"]
+                );
+                let import_path = Path::new("./test/included/deploy.yml")
+                    .display()
+                    .to_string();
+                assert_eq!(*actions[3].kind(), ActionKind::Import(import_path.clone()));
+                assert_eq!(
+                    cfg.imports()[&import_path].default_workflow(),
+                    Some("deploy")
+                );
+            }
+
+            #[test]
+            fn should_return_unknown_default_workflow_err() {
+                match Config::load("./test/unknown_default.yml") {
+                    Ok(_) => panic!("should fail"),
+                    Err(LoadingError::UnknownDefaultWorkflow(name)) => assert_eq!(name, "deploy"),
+                    Err(err) => panic!("{}", err),
+                }
+            }
+
+            #[test]
+            fn should_return_unexpected_notify_err_if_handler_notifies() {
+                match Config::load("./test/notify_in_handler.yml") {
+                    Ok(_) => panic!("should fail"),
+                    Err(LoadingError::UnexpectedNotify(workflow_name, action_name)) => {
+                        assert_eq!(workflow_name, "workflow1");
+                        assert_eq!(action_name, "restart");
+                    }
+                    Err(err) => panic!("{}", err),
+                }
+            }
+
+            #[test]
+            fn should_return_validating_err_if_outputs_without_inputs() {
+                match Config::load("./test/cache_without_inputs.yml") {
+                    Ok(_) => panic!("should fail"),
+                    Err(LoadingError::Validating(_)) => {}
+                    Err(err) => panic!("{}", err),
+                }
+            }
+
+            #[test]
+            fn should_return_validating_err_if_unknown_action_kind() {
+                match Config::load("./test/unknown_kind.yml") {
+                    Ok(_) => panic!("should fail"),
+                    Err(LoadingError::Validating(_)) => {}
+                    Err(err) => panic!("{}", err),
+                }
+            }
+
+            #[test]
+            fn should_return_validating_err_if_reserved_action_name() {
+                match Config::load("./test/reserved_name.yml") {
+                    Ok(_) => panic!("should fail"),
+                    Err(LoadingError::Validating(_)) => {}
+                    Err(err) => panic!("{}", err),
+                }
+            }
+
+            #[test]
+            fn should_return_validating_err_if_action_named_status() {
+                match Config::load("./test/reserved_status.yml") {
+                    Ok(_) => panic!("should fail"),
+                    Err(LoadingError::Validating(_)) => {}
+                    Err(err) => panic!("{}", err),
+                }
+            }
+
+            #[test]
+            fn should_return_config_with_cache() {
+                let cfg = Config::load("./test/cache.yml").unwrap();
+                let action_cfg = &cfg.workflow_config("build").unwrap().actions()[0];
+                let expected = CacheInputs::new(
+                    vec![String::from("src/**/*.c")],
+                    vec![String::from("vars.target")],
+                );
+                assert_eq!(action_cfg.cache_inputs(), Some(&expected));
+                assert_eq!(action_cfg.cache_outputs(), vec!["target/{{ vars.target }}"]);
+                assert!(cfg.workflow("build").is_ok());
+            }
+
+            #[test]
+            fn should_return_config_with_guards() {
+                let cfg = Config::load("./test/guards.yml").unwrap();
+                let actions = cfg.workflow_config("guards").unwrap().actions();
+                assert_eq!(actions[0].creates(), Some("./test"));
+                assert_eq!(actions[1].removes(), Some("./test/{{ vars.missing }}"));
+                let outputs = cfg.workflow("guards").unwrap().run();
+                assert_eq!(outputs["init"].status(), Status::Unchanged);
+                assert_eq!(
+                    outputs["init"].value("reason").unwrap(),
+                    &"./test exists".into()
+                );
+                assert_eq!(outputs["clean"].status(), Status::Unchanged);
+            }
+
+            #[test]
+            fn should_return_config_with_workflows() {
+                let cfg = Config::load("./test/workflows.yml").unwrap();
+                assert_eq!(cfg.default_workflow(), Some("build"));
+                assert_eq!(cfg.workflow_names(), vec!["build", "deploy"]);
+                let deploy_cfg = cfg.workflow_config("deploy").unwrap();
+                assert_eq!(*deploy_cfg.vars(), hash!("env", "prod"));
+                assert_eq!(deploy_cfg.actions()[0].name(), "deploy_echo");
+            }
+
+            #[test]
+            fn should_return_config() {
+                let expected = Config {
+                    default: Some(String::from("workflow1")),
+                    vars: hash!(
+                        "greeting",
+                        "it works!",
+                        "retries",
+                        3u8,
+                        "ratio",
+                        0.5,
+                        "nothing",
+                        Value::Null,
+                        "envs",
+                        array!("dev", "prod"),
+                        "db",
+                        hash!("host", "localhost", "port", 5432u16)
+                    ),
+                    secret_sources: HashMap::from([(
+                        String::from("token"),
+                        SecretSource::File(String::from("secret.txt")),
+                    )]),
+                    secrets: hash!(
+                        "token",
+                        Value::Secret(Secret::new(String::from("password"))),
+                        "vault_token",
+                        Value::Secret(Secret::new(String::from("vault_password"))),
+                        "vault_port",
+                        5432u16
+                    ),
+                    vaults: vec![String::from("secrets.vault")],
+                    base_dir: PathBuf::from("./test"),
+                    workflows: HashMap::from([(
+                        String::from("workflow1"),
+                        WorkflowConfig::default().with_actions(vec![ActionConfig {
+                            vars: hash!("greeting", "hello world!"),
+                            ..action_config(
+                                "run_echo",
+                                ActionKind::Run(String::from("echo {{ vars.greeting }}\n")),
+                            )
+                        }]),
+                    )]),
+                    ..Config::default()
+                };
+                let cfg =
+                    Config::load_with_passphrase("./test/ennio.yml", &|| Ok(String::from("ennio")))
+                        .unwrap();
+                assert_eq!(cfg, expected);
+            }
+        }
+
+        mod load_secrets {
+            use super::*;
+
+            #[test]
+            fn should_return_secrets() {
+                let expected = hash!("token", Value::Secret(Secret::new("password".into())));
+                let cfg = Config {
+                    secret_sources: HashMap::from([(
+                        String::from("token"),
+                        SecretSource::EncryptedFile {
+                            path: String::from("secret.enc"),
+                            key_file: Some(String::from("secret.key")),
+                        },
+                    )]),
+                    ..Config::default()
+                };
+                let secrets = cfg
+                    .load_secrets(Path::new("./test"), &vault::passphrase)
+                    .unwrap();
+                assert_eq!(secrets, expected);
+            }
+
+            #[test]
+            fn should_return_vault_secrets() {
+                let expected = hash!(
+                    "vault_token",
+                    Value::Secret(Secret::new("vault_password".into())),
+                    "vault_port",
+                    Value::Secret(Secret::new("password".into()))
+                );
+                let cfg = Config {
+                    secret_sources: HashMap::from([(
+                        String::from("vault_port"),
+                        SecretSource::File(String::from("secret.txt")),
+                    )]),
+                    vaults: vec![String::from("secrets.vault")],
+                    ..Config::default()
+                };
+                let secrets = cfg
+                    .load_secrets(Path::new("./test"), &|| Ok(String::from("ennio")))
+                    .unwrap();
+                assert_eq!(secrets, expected);
+            }
+        }
+
+        mod missing_env_vars {
+            use super::*;
+
+            #[test]
+            fn should_return_missing_env_vars() {
+                let run = |script: &str| {
+                    WorkflowConfig::default().with_actions(vec![action_config(
+                        "action1",
+                        ActionKind::Run(script.into()),
+                    )])
+                };
+                let cfg = Config {
+                    strict_env: true,
+                    workflows: HashMap::from([
+                        (
+                            String::from("workflow1"),
+                            run("echo {{ env.ENNIO_UNDEFINED_VAR2 }} {{ env.ENNIO_UNDEFINED_VAR1 }} \
+                                {{ env.ENNIO_UNDEFINED_VAR3 | default(1) }} {{ vars.foo }}"),
+                        ),
+                        (
+                            String::from("workflow2"),
+                            run("echo {{ env.ENNIO_UNDEFINED_VAR1 }} {{ env.PATH }}"),
+                        ),
+                    ]),
+                    ..Config::default()
+                };
+                let expected = vec![
+                    String::from("ENNIO_UNDEFINED_VAR1"),
+                    String::from("ENNIO_UNDEFINED_VAR2"),
+                ];
+                assert_eq!(cfg.missing_env_vars(), expected);
+            }
+
+            #[test]
+            fn should_return_missing_env_vars_of_all_templates() {
+                let workflow_cfg: WorkflowConfig = serde_yaml::from_str(
+                    r#"
+actions:
+  - name: action1
+    workflow: workflow2
+    with:
+      target: "{{ env.ENNIO_UNDEFINED_INPUT }}"
+  - name: action2
+    block:
+      - name: action3
+        block:
+          - name: action4
+            loop: "{{ env.ENNIO_UNDEFINED_ITEMS }}"
+            creates: "{{ env.ENNIO_UNDEFINED_CREATES }}"
+            run: echo {{ item }}
+finally:
+  - name: action5
+    matrix:
+      region: "{{ env.ENNIO_UNDEFINED_MATRIX }}"
+    run: echo {{ item.region }}
+"#,
+                )
+                .unwrap();
+                let cfg = Config {
+                    strict_env: true,
+                    workflows: HashMap::from([(String::from("workflow1"), workflow_cfg)]),
+                    ..Config::default()
+                };
+                let expected = vec![
+                    String::from("ENNIO_UNDEFINED_CREATES"),
+                    String::from("ENNIO_UNDEFINED_INPUT"),
+                    String::from("ENNIO_UNDEFINED_ITEMS"),
+                    String::from("ENNIO_UNDEFINED_MATRIX"),
+                ];
+                assert_eq!(cfg.missing_env_vars(), expected);
+            }
+        }
+
+        mod secret_sources {
+            use super::*;
+
+            #[test]
+            fn should_return_secret_sources() {
+                let expected = HashMap::from([(
+                    String::from("token"),
+                    SecretSource::Env(String::from("TOKEN")),
+                )]);
+                let cfg = Config {
+                    secret_sources: HashMap::from([(
+                        String::from("token"),
+                        SecretSource::Env(String::from("TOKEN")),
+                    )]),
+                    ..Config::default()
+                };
+                assert_eq!(*cfg.secret_sources(), expected);
+            }
+        }
+
+        mod secrets {
+            use super::*;
+
+            #[test]
+            fn should_return_secrets() {
+                let expected = hash!("token", Value::Secret(Secret::new("password".into())));
+                let cfg = Config {
+                    secrets: expected.clone(),
+                    ..Config::default()
+                };
+                assert_eq!(*cfg.secrets(), expected);
+            }
+        }
+
+        mod strict_env {
+            use super::*;
+
+            #[test]
+            fn should_return_strict_env() {
+                let cfg = Config {
+                    strict_env: true,
+                    ..Config::default()
+                };
+                assert!(cfg.strict_env());
+            }
+        }
+
+        mod vars {
+            use super::*;
+
+            #[test]
+            fn should_return_vars() {
+                let expected = hash!("foo", true);
+                let cfg = Config {
+                    vars: expected.clone(),
+                    ..Config::default()
+                };
+                assert_eq!(*cfg.vars(), expected);
+            }
+        }
+
+        mod vaults {
+            use super::*;
+
+            #[test]
+            fn should_return_vaults() {
+                let expected = vec![String::from("secrets.vault")];
+                let cfg = Config {
+                    vaults: expected.clone(),
+                    ..Config::default()
+                };
+                assert_eq!(cfg.vaults(), expected);
+            }
+        }
+
+        mod workflow_names {
+            use super::*;
+
+            #[test]
+            fn should_return_sorted_names() {
+                let cfg = Config {
+                    workflows: HashMap::from([
+                        (String::from("deploy"), WorkflowConfig::default()),
+                        (String::from("build"), WorkflowConfig::default()),
+                    ]),
+                    ..Config::default()
+                };
+                assert_eq!(cfg.workflow_names(), vec!["build", "deploy"]);
+            }
+        }
+
+        mod workflow {
+            use super::*;
+
+            #[test]
+            fn should_return_workflow() {
+                let name = "workflow1";
+                let action_vars = hash!("foo", 3u8);
+                let cfg = Config {
+                    vars: hash!("foo", 1u8, "bar", 1u8),
+                    workflows: HashMap::from([(
+                        name.into(),
+                        WorkflowConfig::default()
+                            .with_vars(hash!("foo", 2u8))
+                            .with_actions(vec![ActionConfig {
+                                vars: action_vars.clone(),
+                                ..action_config(
+                                    "action1",
+                                    ActionKind::Run(String::from("echo {{ vars.foo }}")),
+                                )
+                            }]),
+                    )]),
+                    ..Config::default()
+                };
+                let workflow = cfg.workflow(name).unwrap();
+                assert_eq!(workflow.name(), name);
+                assert_eq!(*workflow.vars(), hash!("foo", 2u8, "bar", 1u8));
+                assert_eq!(workflow.steps().len(), 1);
+                assert_eq!(workflow.steps()[0].action().name(), "action1");
+                assert_eq!(*workflow.steps()[0].vars(), action_vars);
+            }
+
+            #[test]
+            fn should_return_unknown_import_err() {
+                let cfg = Config {
+                    workflows: HashMap::from([(
+                        String::from("workflow1"),
+                        WorkflowConfig::default().with_actions(vec![action_config(
+                            "action1",
+                            ActionKind::Import(String::from("deploy.yml")),
+                        )]),
+                    )]),
+                    ..Config::default()
+                };
+                match cfg.workflow("workflow1") {
+                    Ok(_) => panic!("should fail"),
+                    Err(BuildError::UnknownImport(path)) => assert_eq!(path, "deploy.yml"),
+                    Err(err) => panic!("{}", err),
+                }
+            }
+
+            #[test]
+            fn should_return_unknown_workflow_err() {
+                let cfg = Config::default();
+                match cfg.workflow("workflow1") {
+                    Ok(_) => panic!("should fail"),
+                    Err(BuildError::UnknownWorkflow(name)) => assert_eq!(name, "workflow1"),
+                    Err(err) => panic!("{}", err),
+                }
+            }
+
+            #[test]
+            fn should_return_recursion_err() {
+                let cfg = Config::load("./test/recursion.yml").unwrap();
+                match cfg.workflow("build") {
+                    Ok(_) => panic!("should fail"),
+                    Err(BuildError::Recursion(names)) => {
+                        assert_eq!(names, vec!["build", "deploy", "build"])
+                    }
+                    Err(err) => panic!("{}", err),
+                }
+            }
+
+            #[test]
+            fn should_return_workflow_with_sub_workflow() {
+                let cfg = Config::load("./test/sub_workflow.yml").unwrap();
+                let workflow = cfg.workflow("main").unwrap();
+                let outputs = workflow.run();
+                assert_eq!(outputs["deploy_api"].status(), Status::Changed);
+                assert_eq!(
+                    outputs["deploy_api"].value("deploy_echo.stdout").unwrap(),
+                    &Value::from("deploying api\n")
+                );
+            }
+
+            #[test]
+            fn should_return_unknown_handler_err() {
+                let cfg = Config::load("./test/unknown_handler.yml").unwrap();
+                match cfg.workflow("workflow1") {
+                    Ok(_) => panic!("should fail"),
+                    Err(BuildError::UnknownHandler(action_name, handler_name)) => {
+                        assert_eq!(action_name, "write_config");
+                        assert_eq!(handler_name, "restart");
+                    }
+                    Err(err) => panic!("{}", err),
+                }
+            }
+
+            #[test]
+            fn should_return_workflow_with_handlers() {
+                let cfg = Config::load("./test/handlers.yml").unwrap();
+                let workflow = cfg.workflow("workflow1").unwrap();
+                assert_eq!(workflow.handlers().len(), 2);
+                assert_eq!(workflow.steps()[0].notify(), vec!["restart"]);
+                let outputs = workflow.run();
+                assert_eq!(outputs["restart"].status(), Status::Changed);
+                assert_eq!(outputs["reload"].status(), Status::Skipped);
+            }
+
+            #[test]
+            fn should_return_workflow_with_handlers_notified_by_nested_actions() {
+                let cfg = Config::load("./test/notify_in_block.yml").unwrap();
+                let workflow = cfg.workflow("workflow1").unwrap();
+                let outputs = workflow.run();
+                assert_eq!(outputs["configure"].status(), Status::Changed);
+                assert_eq!(outputs["restart"].status(), Status::Changed);
+            }
+
+            #[test]
+            fn should_return_unknown_handler_err_if_nested_action_notifies() {
+                let cfg = Config::load("./test/unknown_nested_handler.yml").unwrap();
+                match cfg.workflow("workflow1") {
+                    Ok(_) => panic!("should fail"),
+                    Err(BuildError::UnknownHandler(action_name, handler_name)) => {
+                        assert_eq!(action_name, "write_config");
+                        assert_eq!(handler_name, "reload");
+                    }
+                    Err(err) => panic!("{}", err),
+                }
+            }
+
+            #[test]
+            fn should_return_unknown_dependency_err() {
+                let cfg = Config::load("./test/unknown_dependency.yml").unwrap();
+                match cfg.workflow("workflow1") {
+                    Ok(_) => panic!("should fail"),
+                    Err(BuildError::UnknownDependency(action_name, dep_name)) => {
+                        assert_eq!(action_name, "build");
+                        assert_eq!(dep_name, "deploy");
+                    }
+                    Err(err) => panic!("{}", err),
+                }
+            }
+
+            #[test]
+            fn should_return_workflow_with_tags() {
+                let cfg = Config::load("./test/tags.yml").unwrap();
+                let workflow = cfg
+                    .workflow("workflow1")
+                    .unwrap()
+                    .with_selection(Selection::new(vec![String::from("deploy")], vec![]));
+                assert_eq!(workflow.steps()[2].tags(), vec!["deploy"]);
+                assert_eq!(workflow.steps()[2].needs(), vec!["build"]);
+                let outputs = workflow.run();
+                assert_eq!(outputs["build"].status(), Status::Changed);
+                assert_eq!(outputs["test"].status(), Status::Skipped);
+                assert_eq!(outputs["deploy"].status(), Status::Changed);
+            }
+
+            #[test]
+            fn should_return_workflow_with_block() {
+                let cfg = Config::load("./test/block.yml").unwrap();
+                let workflow = cfg.workflow("workflow1").unwrap();
+                let outputs = workflow.run();
+                assert_eq!(outputs["deploy"].status(), Status::Changed);
+                assert_eq!(
+                    outputs["deploy"].value("migrate.status").unwrap(),
+                    &Value::from("failed")
+                );
+                assert!(outputs["deploy"].value("release").is_none());
+                assert!(outputs["deploy"].value("nested_echo").is_some());
+                assert_eq!(
+                    outputs["notify"].value("stdout").unwrap(),
+                    &Value::from("rolling back after migrate\n")
+                );
+            }
+
+            #[test]
+            fn should_return_workflow_with_finally() {
+                let cfg = Config::load("./test/finally.yml").unwrap();
+                let workflow = cfg.workflow("workflow1").unwrap();
+                assert_eq!(workflow.finally().len(), 1);
+                let outputs = workflow.run();
+                assert_eq!(outputs["fail"].status(), Status::Failed);
+                assert_eq!(
+                    outputs["cleanup"].value("stdout").unwrap(),
+                    &Value::from("failed fail\n")
+                );
+            }
+
+            #[test]
+            fn should_return_workflow_with_loops() {
+                let cfg = Config::load("./test/loops.yml").unwrap();
+                let workflow = cfg.workflow("workflow1").unwrap();
+                let outputs = workflow.run();
+                assert_eq!(outputs["deploy"].status(), Status::Changed);
+                assert_eq!(
+                    outputs["deploy"].value("results.1.stdout").unwrap(),
+                    &Value::from("deploying web\n")
+                );
+                assert_eq!(
+                    outputs["deploy_matrix"].value("results.3.stdout").unwrap(),
+                    &Value::from("deploying web to us\n")
+                );
+            }
+
+            #[test]
+            fn should_return_workflow_with_imported_workflow() {
+                let cfg = Config::load("./test/include.yml").unwrap();
+                let workflow = cfg.workflow("workflow1").unwrap();
+                let outputs = workflow.run();
+                assert_eq!(outputs["deploy"].status(), Status::Changed);
+                assert_eq!(
+                    outputs["deploy"].value("deploy_echo.stdout").unwrap(),
+                    &Value::from("deploying to dev\n")
+                );
+            }
+        }
+    }
+
+    mod loading_error {
+        use super::*;
+
+        mod display {
+            use super::*;
+
+            macro_rules! test {
+                ($name:ident, $value:expr) => {
+                    #[test]
+                    fn $name() {
+                        let msg = "error";
+                        let err = $value(String::from(msg));
+                        assert_eq!(err.to_string(), msg);
+                    }
+                };
+            }
+
+            test!(parsing, LoadingError::Parsing);
+            test!(reading, LoadingError::Reading);
+
+            #[test]
+            fn missing_env_vars() {
+                let var_names = vec![String::from("FOO"), String::from("BAR")];
+                let err = LoadingError::MissingEnvVars(var_names);
+                assert_eq!(
+                    err.to_string(),
+                    "Environment variables are not defined: FOO, BAR"
+                );
+            }
+
+            #[test]
+            fn cycle() {
+                let paths = vec![
+                    String::from("a.yml"),
+                    String::from("b.yml"),
+                    String::from("a.yml"),
+                ];
+                let err = LoadingError::Cycle(paths);
+                assert_eq!(
+                    err.to_string(),
+                    "Include cycle detected: a.yml -> b.yml -> a.yml"
+                );
+            }
+
+            #[test]
+            fn unknown_default_workflow() {
+                let err = LoadingError::UnknownDefaultWorkflow(String::from("deploy"));
+                assert_eq!(err.to_string(), "Default workflow 'deploy' is not defined");
+            }
+
+            #[test]
+            fn secret() {
+                let err = LoadingError::Secret(String::from("token"), SecretError::InvalidKey);
+                assert_eq!(
+                    err.to_string(),
+                    "Unable to load secret 'token': Key must be 32 bytes encoded in base64"
+                );
+            }
+
+            #[test]
+            fn vault() {
+                let err =
+                    LoadingError::Vault(String::from("secrets.vault"), SecretError::InvalidVault);
+                assert_eq!(
+                    err.to_string(),
+                    "Unable to load vault secrets.vault: File is not a valid vault"
+                );
+            }
+
+            #[test]
+            fn validating() {
+                let msgs = vec![String::from("error1"), String::from("error2")];
+                let err = LoadingError::Validating(msgs.clone());
+                assert_eq!(err.to_string(), msgs.join(", "));
+            }
+
+            #[test]
+            fn unexpected_notify() {
+                let err = LoadingError::UnexpectedNotify(
+                    String::from("workflow1"),
+                    String::from("action1"),
+                );
+                assert_eq!(
+                    err.to_string(),
+                    "Action 'action1' of workflow 'workflow1' cannot notify handlers, only actions and their nested actions can"
+                );
+            }
+
+            #[test]
+            fn invalid_references() {
+                let err = LoadingError::InvalidReferences(vec![
+                    ReferenceError::UnknownAction(
+                        String::from("workflow1"),
+                        String::from("action1"),
+                        String::from("action2"),
+                    ),
+                    ReferenceError::UnknownAction(
+                        String::from("workflow1"),
+                        String::from("action1"),
+                        String::from("action3"),
+                    ),
+                ]);
+                assert_eq!(
+                    err.to_string(),
+                    "Action 'action1' of workflow 'workflow1' references undefined action 'action2', Action 'action1' of workflow 'workflow1' references undefined action 'action3'"
+                );
+            }
+        }
+    }
+
+    mod reference_error {
+        use super::*;
+
+        mod to_string {
+            use super::*;
+
+            macro_rules! test {
+                ($name:ident, $err:expr, $expected:expr) => {
+                    #[test]
+                    fn $name() {
+                        assert_eq!($err.to_string(), $expected);
+                    }
+                };
+            }
+
+            test!(
+                unknown_action,
+                ReferenceError::UnknownAction(
+                    String::from("workflow1"),
+                    String::from("action1"),
+                    String::from("action2")
+                ),
+                "Action 'action1' of workflow 'workflow1' references undefined action 'action2'"
+            );
+            test!(
+                not_run_before,
+                ReferenceError::NotRunBefore(
+                    String::from("workflow1"),
+                    String::from("action1"),
+                    String::from("action2")
+                ),
+                "Action 'action1' of workflow 'workflow1' references action 'action2' which does not run before it"
+            );
+            test!(
+                unknown_var,
+                ReferenceError::UnknownVar(
+                    String::from("workflow1"),
+                    String::from("action1"),
+                    String::from("action2.stdot")
+                ),
+                "Action 'action1' of workflow 'workflow1' references 'action2.stdot' which is never set"
+            );
+        }
     }
 }
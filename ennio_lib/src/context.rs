@@ -1,24 +1,84 @@
-use crate::{action::*, var::*};
+use crate::{action::*, event::*, hash, var::*};
 use regex::Regex;
 use std::{
-    collections::HashMap,
+    cell::RefCell,
+    collections::{HashMap, HashSet},
     fmt::{self, Display, Formatter},
+    rc::Rc,
 };
 
-#[derive(Debug, Eq, PartialEq)]
+pub static ENV_NAMESPACE: &str = "env";
+pub static ITEM_NAMESPACE: &str = "item";
+pub static SECRETS_NAMESPACE: &str = "secrets";
+pub static VARS_NAMESPACE: &str = "vars";
+pub static WORKFLOW_NAMESPACE: &str = "workflow";
+
+#[derive(Clone, Debug, PartialEq)]
 pub struct Context<'a> {
     workflow_name: &'a str,
+    vars: Hash,
+    env: Hash,
+    secrets: Hash,
     outputs: Outputs,
+    reserved_vars: HashMap<String, Hash>,
+    item: Option<Value>,
+    workflow: Hash,
+    observers: Observers,
+    notified: Rc<RefCell<HashSet<String>>>,
 }
 
 impl<'a> Context<'a> {
     pub fn new(workflow_name: &'a str) -> Self {
         Self {
             workflow_name,
+            vars: Hash::new(),
+            env: Hash::new(),
+            secrets: Hash::new(),
             outputs: Outputs::new(),
+            reserved_vars: HashMap::new(),
+            item: None,
+            workflow: hash!(
+                "name",
+                workflow_name,
+                "status",
+                Status::Unchanged.to_string(),
+                "failed_action",
+                Value::Null
+            ),
+            observers: Observers::new(),
+            notified: Rc::default(),
+        }
+    }
+
+    pub fn env(&self) -> &Hash {
+        &self.env
+    }
+
+    pub fn is_notified(&self, handler_name: &str) -> bool {
+        self.notified.borrow().contains(handler_name)
+    }
+
+    pub fn item(&self) -> Option<&Value> {
+        self.item.as_ref()
+    }
+
+    pub fn notify(&self, kind: EventKind) {
+        if !self.observers.is_empty() {
+            self.observers
+                .notify(&Event::new(self.workflow_name.into(), kind));
         }
     }
 
+    pub fn notify_handlers(&self, handler_names: &[String]) {
+        self.notified
+            .borrow_mut()
+            .extend(handler_names.iter().cloned());
+    }
+
+    pub fn observers(&self) -> &Observers {
+        &self.observers
+    }
+
     pub fn output(&self, action_name: &str) -> Option<&Output> {
         self.outputs.get(action_name)
     }
@@ -27,39 +87,154 @@ impl<'a> Context<'a> {
         &self.outputs
     }
 
+    pub fn scope(&self, vars: &Hash) -> Self {
+        let mut ctx = self.clone();
+        ctx.vars
+            .extend(vars.iter().map(|(name, val)| (name.clone(), val.clone())));
+        ctx
+    }
+
+    pub fn secret_values(&self) -> Vec<&str> {
+        self.secrets
+            .values()
+            .chain(self.vars.values())
+            .chain(
+                self.outputs
+                    .values()
+                    .flat_map(|output| output.vars().values()),
+            )
+            .chain(self.item.iter())
+            .flat_map(Value::secrets)
+            .collect()
+    }
+
+    pub fn secrets(&self) -> &Hash {
+        &self.secrets
+    }
+
     pub fn take_outputs(self) -> Outputs {
         self.outputs
     }
 
     pub fn update(&mut self, name: &str, output: Output) {
+        if output.status() == Status::Failed
+            && self.workflow.get("failed_action") == Some(&Value::Null)
+        {
+            self.workflow
+                .insert(String::from("failed_action"), name.into());
+        }
+        self.reserved_vars
+            .insert(name.into(), output.reserved_vars());
         self.outputs.insert(name.into(), output);
+        let status = Status::aggregate(self.outputs.values().map(Output::status));
+        self.workflow
+            .insert(String::from("status"), status.to_string().into());
     }
 
     pub fn value(&self, var_name: &str) -> Result<&Value, VarError> {
         let re = Regex::new(ACTION_NAME_PATTERN).unwrap();
-        match re.find(var_name) {
+        match re.find(var_name).filter(|mat| mat.start() == 0) {
+            Some(mat) if mat.as_str() == VARS_NAMESPACE => {
+                let var_name = Self::strip_namespace(var_name, VARS_NAMESPACE)?;
+                lookup(&self.vars, var_name).ok_or_else(|| VarError::UndefinedVar(var_name.into()))
+            }
+            Some(mat) if mat.as_str() == ENV_NAMESPACE => {
+                let var_name = Self::strip_namespace(var_name, ENV_NAMESPACE)?;
+                lookup(&self.env, var_name)
+                    .ok_or_else(|| VarError::UndefinedEnvVar(var_name.into()))
+            }
+            Some(mat) if mat.as_str() == ITEM_NAMESPACE => {
+                let item = self.item.as_ref().ok_or(VarError::UndefinedItem)?;
+                match &var_name[ITEM_NAMESPACE.len()..] {
+                    "" => Ok(item),
+                    path => {
+                        let path = path
+                            .strip_prefix('.')
+                            .ok_or_else(|| VarError::InvalidSyntax(var_name.into()))?;
+                        item.get(path)
+                            .ok_or_else(|| VarError::UndefinedVar(var_name.into()))
+                    }
+                }
+            }
+            Some(mat) if mat.as_str() == WORKFLOW_NAMESPACE => {
+                let var_name = Self::strip_namespace(var_name, WORKFLOW_NAMESPACE)?;
+                lookup(&self.workflow, var_name)
+                    .ok_or_else(|| VarError::UndefinedVar(format!("workflow.{}", var_name)))
+            }
+            Some(mat) if mat.as_str() == SECRETS_NAMESPACE => {
+                let var_name = Self::strip_namespace(var_name, SECRETS_NAMESPACE)?;
+                lookup(&self.secrets, var_name)
+                    .ok_or_else(|| VarError::UndefinedSecret(var_name.into()))
+            }
             Some(mat) => {
                 let action_name = mat.as_str();
                 let output = self
                     .output(action_name)
                     .ok_or_else(|| VarError::UnknownAction(action_name.into()))?;
-                let var_name = &var_name[action_name.len()..];
-                if var_name.is_empty() {
-                    Err(VarError::MissingVarName)
-                } else {
-                    let var_name = &var_name[1..];
-                    output
-                        .value(var_name)
-                        .ok_or_else(|| VarError::UnknownVar(action_name.into(), var_name.into()))
-                }
+                let var_name = Self::strip_namespace(var_name, action_name)?;
+                self.reserved_vars
+                    .get(action_name)
+                    .and_then(|vars| lookup(vars, var_name))
+                    .or_else(|| output.value(var_name))
+                    .ok_or_else(|| VarError::UnknownVar(action_name.into(), var_name.into()))
             }
             None => Err(VarError::InvalidSyntax(var_name.into())),
         }
     }
 
+    pub fn vars(&self) -> &Hash {
+        &self.vars
+    }
+
+    pub fn with_env(mut self, env: Hash) -> Self {
+        self.env = env;
+        self
+    }
+
+    pub fn with_failure(mut self, action_name: &str, output: &Output) -> Self {
+        self.workflow
+            .insert(String::from("failed_action"), action_name.into());
+        self.workflow
+            .insert(String::from("failed_output"), output.clone().into());
+        self
+    }
+
+    pub fn with_item(mut self, item: Value) -> Self {
+        self.item = Some(item);
+        self
+    }
+
+    pub fn with_observers(mut self, observers: Observers) -> Self {
+        self.observers = observers;
+        self
+    }
+
+    pub fn with_secrets(mut self, secrets: Hash) -> Self {
+        self.secrets = secrets;
+        self
+    }
+
+    pub fn with_vars(mut self, vars: Hash) -> Self {
+        self.vars = vars;
+        self
+    }
+
+    pub fn workflow(&self) -> &Hash {
+        &self.workflow
+    }
+
     pub fn workflow_name(&self) -> &str {
         self.workflow_name
     }
+
+    fn strip_namespace<'b>(var_name: &'b str, namespace: &str) -> Result<&'b str, VarError> {
+        match &var_name[namespace.len()..] {
+            "" => Err(VarError::MissingVarName),
+            path => path
+                .strip_prefix('.')
+                .ok_or_else(|| VarError::InvalidSyntax(var_name.into())),
+        }
+    }
 }
 
 pub type Outputs = HashMap<String, Output>;
@@ -70,6 +245,10 @@ pub enum VarError {
     UnknownAction(String),
     MissingVarName,
     UnknownVar(String, String),
+    UndefinedVar(String),
+    UndefinedEnvVar(String),
+    UndefinedSecret(String),
+    UndefinedItem,
 }
 
 impl Display for VarError {
@@ -81,6 +260,14 @@ impl Display for VarError {
             Self::UnknownVar(action_name, var_name) => {
                 format!("No variable '{}' in '{}' outputs", var_name, action_name)
             }
+            Self::UndefinedVar(var_name) => format!("Variable '{}' is not defined", var_name),
+            Self::UndefinedEnvVar(var_name) => {
+                format!("Environment variable '{}' is not defined", var_name)
+            }
+            Self::UndefinedSecret(secret_name) => {
+                format!("Secret '{}' is not defined", secret_name)
+            }
+            Self::UndefinedItem => String::from("Variable 'item' is only defined in loops"),
         };
         write!(f, "{}", s)
     }
@@ -89,6 +276,8 @@ impl Display for VarError {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::*;
+    use std::time::Duration;
 
     mod new {
         use super::*;
@@ -98,13 +287,50 @@ mod test {
             let workflow_name = "workflow1";
             let exepcted = Context {
                 workflow_name,
+                vars: Hash::new(),
+                env: Hash::new(),
+                secrets: Hash::new(),
                 outputs: Outputs::new(),
+                reserved_vars: HashMap::new(),
+                notified: Rc::default(),
+                item: None,
+                workflow: hash!(
+                    "name",
+                    workflow_name,
+                    "status",
+                    "unchanged",
+                    "failed_action",
+                    Value::Null
+                ),
+                observers: Observers::new(),
             };
             let ctx = Context::new(workflow_name);
             assert_eq!(ctx, exepcted);
         }
     }
 
+    mod env {
+        use super::*;
+
+        #[test]
+        fn should_return_env() {
+            let expected = hash!("HOME", "/root");
+            let ctx = Context {
+                workflow_name: "workflow1",
+                vars: Hash::new(),
+                env: expected.clone(),
+                secrets: Hash::new(),
+                outputs: Outputs::new(),
+                reserved_vars: HashMap::new(),
+                notified: Rc::default(),
+                item: None,
+                workflow: Hash::new(),
+                observers: Observers::new(),
+            };
+            assert_eq!(*ctx.env(), expected);
+        }
+    }
+
     mod output {
         use super::*;
 
@@ -112,7 +338,15 @@ mod test {
         fn should_return_none() {
             let ctx = Context {
                 workflow_name: "workflow1",
+                vars: Hash::new(),
+                env: Hash::new(),
+                secrets: Hash::new(),
                 outputs: Outputs::new(),
+                reserved_vars: HashMap::new(),
+                notified: Rc::default(),
+                item: None,
+                workflow: Hash::new(),
+                observers: Observers::new(),
             };
             let output = ctx.output("action1");
             assert!(output.is_none());
@@ -124,7 +358,15 @@ mod test {
             let expected = Output::new(Status::Changed);
             let ctx = Context {
                 workflow_name: "workflow1",
+                vars: Hash::new(),
+                env: Hash::new(),
+                secrets: Hash::new(),
                 outputs: Outputs::from([(name.into(), expected.clone())]),
+                reserved_vars: HashMap::new(),
+                notified: Rc::default(),
+                item: None,
+                workflow: Hash::new(),
+                observers: Observers::new(),
             };
             let output = ctx.output(name).unwrap();
             assert_eq!(*output, expected);
@@ -140,13 +382,73 @@ mod test {
             let expected = Outputs::from([(String::from("action1"), output)]);
             let ctx = Context {
                 workflow_name: "workflow1",
+                vars: Hash::new(),
+                env: Hash::new(),
+                secrets: Hash::new(),
                 outputs: expected.clone(),
+                reserved_vars: HashMap::new(),
+                notified: Rc::default(),
+                item: None,
+                workflow: Hash::new(),
+                observers: Observers::new(),
             };
             let outputs = ctx.outputs();
             assert_eq!(*outputs, expected);
         }
     }
 
+    mod scope {
+        use super::*;
+
+        #[test]
+        fn should_return_context_with_overridden_vars() {
+            let expected = hash!("foo", 2u8, "bar", true);
+            let ctx = Context {
+                workflow_name: "workflow1",
+                vars: hash!("foo", 1u8, "bar", true),
+                env: Hash::new(),
+                secrets: Hash::new(),
+                outputs: Outputs::new(),
+                reserved_vars: HashMap::new(),
+                notified: Rc::default(),
+                item: None,
+                workflow: Hash::new(),
+                observers: Observers::new(),
+            };
+            let scoped_ctx = ctx.scope(&hash!("foo", 2u8));
+            assert_eq!(scoped_ctx.vars, expected);
+            assert_eq!(ctx.vars, hash!("foo", 1u8, "bar", true));
+        }
+    }
+
+    mod secret_values {
+        use super::*;
+
+        #[test]
+        fn should_return_secret_values() {
+            let secret = |plaintext: &str| Value::Secret(Secret::new(plaintext.into()));
+            let output = Output::new(Status::Changed).add_var("token", secret("password3"));
+            let mut ctx = Context::new("workflow1")
+                .with_secrets(hash!("token", secret("password1")))
+                .with_vars(hash!("token", secret("password2"), "foo", "bar"));
+            ctx.update("action1", output);
+            let mut secret_values = ctx.secret_values();
+            secret_values.sort();
+            assert_eq!(secret_values, vec!["password1", "password2", "password3"]);
+        }
+    }
+
+    mod secrets {
+        use super::*;
+
+        #[test]
+        fn should_return_secrets() {
+            let expected = hash!("token", Value::Secret(Secret::new("password".into())));
+            let ctx = Context::new("workflow1").with_secrets(expected.clone());
+            assert_eq!(*ctx.secrets(), expected);
+        }
+    }
+
     mod take_outputs {
         use super::*;
 
@@ -156,7 +458,15 @@ mod test {
             let expected = Outputs::from([(String::from("action1"), output)]);
             let ctx = Context {
                 workflow_name: "workflow1",
+                vars: Hash::new(),
+                env: Hash::new(),
+                secrets: Hash::new(),
                 outputs: expected.clone(),
+                reserved_vars: HashMap::new(),
+                notified: Rc::default(),
+                item: None,
+                workflow: Hash::new(),
+                observers: Observers::new(),
             };
             let outputs = ctx.take_outputs();
             assert_eq!(outputs, expected);
@@ -173,11 +483,36 @@ mod test {
             let expected = Outputs::from([(name.into(), output.clone())]);
             let mut ctx = Context {
                 workflow_name: "workflow1",
+                vars: Hash::new(),
+                env: Hash::new(),
+                secrets: Hash::new(),
                 outputs: Outputs::new(),
+                reserved_vars: HashMap::new(),
+                notified: Rc::default(),
+                item: None,
+                workflow: Hash::new(),
+                observers: Observers::new(),
             };
             ctx.update(name, output);
             assert_eq!(ctx.outputs, expected);
         }
+
+        #[test]
+        fn should_update_workflow_status() {
+            let mut ctx = Context::new("workflow1");
+            ctx.update("action1", Output::new(Status::Changed));
+            ctx.update("action2", Output::new(Status::Failed));
+            ctx.update("action3", Output::new(Status::Failed));
+            let expected = hash!(
+                "name",
+                "workflow1",
+                "status",
+                "failed",
+                "failed_action",
+                "action2"
+            );
+            assert_eq!(*ctx.workflow(), expected);
+        }
     }
 
     mod value {
@@ -188,7 +523,15 @@ mod test {
             let expected = "éè";
             let ctx = Context {
                 workflow_name: "workflow1",
+                vars: Hash::new(),
+                env: Hash::new(),
+                secrets: Hash::new(),
                 outputs: Outputs::new(),
+                reserved_vars: HashMap::new(),
+                notified: Rc::default(),
+                item: None,
+                workflow: Hash::new(),
+                observers: Observers::new(),
             };
             match ctx.value(expected) {
                 Ok(_) => panic!("should fail"),
@@ -197,12 +540,50 @@ mod test {
             }
         }
 
+        #[test]
+        fn should_return_invalid_syntax_if_no_separator() {
+            let ctx = Context::new("workflow1").with_vars(hash!("foo", "bar"));
+            match ctx.value("varsé") {
+                Ok(_) => panic!("should fail"),
+                Err(VarError::InvalidSyntax(var_name)) => assert_eq!(var_name, "varsé"),
+                Err(err) => panic!("{}", err),
+            }
+        }
+
+        #[test]
+        fn should_return_invalid_syntax_if_no_item_separator() {
+            let ctx = Context::new("workflow1").with_item(Value::from(hash!("foo", "bar")));
+            match ctx.value("itemé") {
+                Ok(_) => panic!("should fail"),
+                Err(VarError::InvalidSyntax(var_name)) => assert_eq!(var_name, "itemé"),
+                Err(err) => panic!("{}", err),
+            }
+        }
+
+        #[test]
+        fn should_return_invalid_syntax_if_not_at_start() {
+            let ctx = Context::new("workflow1").with_vars(hash!("foo", "bar"));
+            match ctx.value("évars.foo") {
+                Ok(_) => panic!("should fail"),
+                Err(VarError::InvalidSyntax(var_name)) => assert_eq!(var_name, "évars.foo"),
+                Err(err) => panic!("{}", err),
+            }
+        }
+
         #[test]
         fn should_return_unknown_action() {
             let expected = "foo";
             let ctx = Context {
                 workflow_name: "workflow1",
+                vars: Hash::new(),
+                env: Hash::new(),
+                secrets: Hash::new(),
                 outputs: Outputs::new(),
+                reserved_vars: HashMap::new(),
+                notified: Rc::default(),
+                item: None,
+                workflow: Hash::new(),
+                observers: Observers::new(),
             };
             match ctx.value(expected) {
                 Ok(_) => panic!("should fail"),
@@ -219,7 +600,15 @@ mod test {
             let output = Output::new(Status::Changed);
             let ctx = Context {
                 workflow_name: "workflow1",
+                vars: Hash::new(),
+                env: Hash::new(),
+                secrets: Hash::new(),
                 outputs: Outputs::from([(action_name.into(), output)]),
+                reserved_vars: HashMap::new(),
+                notified: Rc::default(),
+                item: None,
+                workflow: Hash::new(),
+                observers: Observers::new(),
             };
             match ctx.value(action_name) {
                 Ok(_) => panic!("should fail"),
@@ -235,7 +624,15 @@ mod test {
             let expected_var_name = "foo";
             let ctx = Context {
                 workflow_name: "workflow1",
+                vars: Hash::new(),
+                env: Hash::new(),
+                secrets: Hash::new(),
                 outputs: Outputs::from([(expected_action_name.into(), output)]),
+                reserved_vars: HashMap::new(),
+                notified: Rc::default(),
+                item: None,
+                workflow: Hash::new(),
+                observers: Observers::new(),
             };
             match ctx.value(&format!("{}.{}", expected_action_name, expected_var_name)) {
                 Ok(_) => panic!("should fail"),
@@ -255,11 +652,258 @@ mod test {
             let output = Output::new(Status::Changed).add_var(var_name, expected.clone());
             let ctx = Context {
                 workflow_name: "workflow1",
+                vars: Hash::new(),
+                env: Hash::new(),
+                secrets: Hash::new(),
                 outputs: Outputs::from([(action_name.into(), output)]),
+                reserved_vars: HashMap::new(),
+                notified: Rc::default(),
+                item: None,
+                workflow: Hash::new(),
+                observers: Observers::new(),
             };
             let val = ctx.value(&format!("{}.{}", action_name, var_name)).unwrap();
             assert_eq!(val.clone(), expected);
         }
+
+        #[test]
+        fn should_return_timing() {
+            let mut ctx = Context::new("workflow1");
+            let output =
+                Output::new(Status::Changed).with_timing(1000, 2500, Duration::from_millis(1500));
+            ctx.update("action1", output);
+            assert_eq!(ctx.value("action1.started_at").unwrap(), &1000u64.into());
+            assert_eq!(ctx.value("action1.finished_at").unwrap(), &2500u64.into());
+            assert_eq!(ctx.value("action1.duration").unwrap(), &1.5.into());
+        }
+
+        #[test]
+        fn should_return_status() {
+            let mut ctx = Context::new("workflow1");
+            ctx.update("action1", Output::new(Status::Changed));
+            assert_eq!(ctx.value("action1.status").unwrap(), &"changed".into());
+        }
+
+        #[test]
+        fn should_prefer_reserved_vars_to_output_vars() {
+            let mut ctx = Context::new("workflow1");
+            let output = Output::new(Status::Changed)
+                .add_var("status", "custom".into())
+                .add_var("duration", "long".into())
+                .with_timing(1000, 2500, Duration::from_millis(1500));
+            ctx.update("action1", output);
+            assert_eq!(ctx.value("action1.status").unwrap(), &"changed".into());
+            assert_eq!(ctx.value("action1.duration").unwrap(), &1.5.into());
+        }
+
+        #[test]
+        fn should_return_missing_var_name_if_vars_namespace() {
+            let ctx = Context {
+                workflow_name: "workflow1",
+                vars: Hash::new(),
+                env: Hash::new(),
+                secrets: Hash::new(),
+                outputs: Outputs::new(),
+                reserved_vars: HashMap::new(),
+                notified: Rc::default(),
+                item: None,
+                workflow: Hash::new(),
+                observers: Observers::new(),
+            };
+            match ctx.value(VARS_NAMESPACE) {
+                Ok(_) => panic!("should fail"),
+                Err(VarError::MissingVarName) => {}
+                Err(err) => panic!("{}", err),
+            }
+        }
+
+        #[test]
+        fn should_return_undefined_var() {
+            let expected = "foo";
+            let ctx = Context {
+                workflow_name: "workflow1",
+                vars: Hash::new(),
+                env: Hash::new(),
+                secrets: Hash::new(),
+                outputs: Outputs::new(),
+                reserved_vars: HashMap::new(),
+                notified: Rc::default(),
+                item: None,
+                workflow: Hash::new(),
+                observers: Observers::new(),
+            };
+            match ctx.value(&format!("{}.{}", VARS_NAMESPACE, expected)) {
+                Ok(_) => panic!("should fail"),
+                Err(VarError::UndefinedVar(var_name)) => assert_eq!(var_name, expected),
+                Err(err) => panic!("{}", err),
+            }
+        }
+
+        #[test]
+        fn should_return_workflow_var() {
+            let var_name = "foo";
+            let expected = Value::Bool(true);
+            let ctx = Context {
+                workflow_name: "workflow1",
+                vars: hash!(var_name, expected.clone()),
+                env: Hash::new(),
+                secrets: Hash::new(),
+                outputs: Outputs::new(),
+                reserved_vars: HashMap::new(),
+                notified: Rc::default(),
+                item: None,
+                workflow: Hash::new(),
+                observers: Observers::new(),
+            };
+            let val = ctx
+                .value(&format!("{}.{}", VARS_NAMESPACE, var_name))
+                .unwrap();
+            assert_eq!(val.clone(), expected);
+        }
+
+        #[test]
+        fn should_return_nested_workflow_var() {
+            let expected = Value::from("localhost");
+            let ctx = Context::new("workflow1")
+                .with_vars(hash!("db", hash!("hosts", array!("localhost"))));
+            let val = ctx
+                .value(&format!("{}.db.hosts.0", VARS_NAMESPACE))
+                .unwrap();
+            assert_eq!(val.clone(), expected);
+        }
+
+        #[test]
+        fn should_return_undefined_secret() {
+            let expected = "token";
+            let ctx = Context::new("workflow1");
+            match ctx.value(&format!("{}.{}", SECRETS_NAMESPACE, expected)) {
+                Ok(_) => panic!("should fail"),
+                Err(VarError::UndefinedSecret(secret_name)) => {
+                    assert_eq!(secret_name, expected)
+                }
+                Err(err) => panic!("{}", err),
+            }
+        }
+
+        #[test]
+        fn should_return_secret() {
+            let expected = Value::Secret(Secret::new("password".into()));
+            let ctx = Context::new("workflow1").with_secrets(hash!("token", expected.clone()));
+            let val = ctx.value(&format!("{}.token", SECRETS_NAMESPACE)).unwrap();
+            assert_eq!(val.clone(), expected);
+        }
+
+        #[test]
+        fn should_return_workflow_status() {
+            let mut ctx = Context::new("workflow1");
+            ctx.update("action1", Output::new(Status::Changed));
+            let val = ctx
+                .value(&format!("{}.status", WORKFLOW_NAMESPACE))
+                .unwrap();
+            assert_eq!(val.clone(), Value::from("changed"));
+        }
+
+        #[test]
+        fn should_return_failed_output() {
+            let ctx = Context::new("workflow1").with_failure(
+                "action1",
+                &Output::new(Status::Failed).add_var("stderr", "error".into()),
+            );
+            let val = ctx
+                .value(&format!("{}.failed_output.stderr", WORKFLOW_NAMESPACE))
+                .unwrap();
+            assert_eq!(val.clone(), Value::from("error"));
+            let val = ctx
+                .value(&format!("{}.failed_action", WORKFLOW_NAMESPACE))
+                .unwrap();
+            assert_eq!(val.clone(), Value::from("action1"));
+        }
+
+        #[test]
+        fn should_return_undefined_item() {
+            let ctx = Context::new("workflow1");
+            match ctx.value(ITEM_NAMESPACE) {
+                Ok(_) => panic!("should fail"),
+                Err(VarError::UndefinedItem) => {}
+                Err(err) => panic!("{}", err),
+            }
+        }
+
+        #[test]
+        fn should_return_item() {
+            let expected = Value::from("api");
+            let ctx = Context::new("workflow1").with_item(expected.clone());
+            let val = ctx.value(ITEM_NAMESPACE).unwrap();
+            assert_eq!(val.clone(), expected);
+        }
+
+        #[test]
+        fn should_return_nested_item() {
+            let expected = Value::from("eu");
+            let ctx = Context::new("workflow1").with_item(Value::from(hash!(
+                "service",
+                "api",
+                "region",
+                expected.clone()
+            )));
+            let val = ctx.value(&format!("{}.region", ITEM_NAMESPACE)).unwrap();
+            assert_eq!(val.clone(), expected);
+        }
+    }
+
+    mod vars {
+        use super::*;
+
+        #[test]
+        fn should_return_vars() {
+            let expected = hash!("foo", true);
+            let ctx = Context {
+                workflow_name: "workflow1",
+                vars: expected.clone(),
+                env: Hash::new(),
+                secrets: Hash::new(),
+                outputs: Outputs::new(),
+                reserved_vars: HashMap::new(),
+                notified: Rc::default(),
+                item: None,
+                workflow: Hash::new(),
+                observers: Observers::new(),
+            };
+            assert_eq!(*ctx.vars(), expected);
+        }
+    }
+
+    mod with_env {
+        use super::*;
+
+        #[test]
+        fn should_set_env() {
+            let expected = hash!("HOME", "/root");
+            let ctx = Context::new("workflow1").with_env(expected.clone());
+            assert_eq!(ctx.env, expected);
+        }
+    }
+
+    mod with_secrets {
+        use super::*;
+
+        #[test]
+        fn should_set_secrets() {
+            let expected = hash!("token", Value::Secret(Secret::new("password".into())));
+            let ctx = Context::new("workflow1").with_secrets(expected.clone());
+            assert_eq!(ctx.secrets, expected);
+        }
+    }
+
+    mod with_vars {
+        use super::*;
+
+        #[test]
+        fn should_set_vars() {
+            let expected = hash!("foo", true);
+            let ctx = Context::new("workflow1").with_vars(expected.clone());
+            assert_eq!(ctx.vars, expected);
+        }
     }
 
     mod workflow_name {
@@ -270,7 +914,15 @@ mod test {
             let expected = "workflow1";
             let ctx = Context {
                 workflow_name: expected,
+                vars: Hash::new(),
+                env: Hash::new(),
+                secrets: Hash::new(),
                 outputs: Outputs::new(),
+                reserved_vars: HashMap::new(),
+                notified: Rc::default(),
+                item: None,
+                workflow: Hash::new(),
+                observers: Observers::new(),
             };
             assert_eq!(ctx.workflow_name(), expected);
         }
@@ -311,6 +963,26 @@ mod test {
                 VarError::UnknownVar(String::from("action1"), String::from("foo")),
                 "No variable 'foo' in 'action1' outputs"
             );
+            test!(
+                undefined_var,
+                VarError::UndefinedVar(String::from("foo")),
+                "Variable 'foo' is not defined"
+            );
+            test!(
+                undefined_env_var,
+                VarError::UndefinedEnvVar(String::from("HOME")),
+                "Environment variable 'HOME' is not defined"
+            );
+            test!(
+                undefined_secret,
+                VarError::UndefinedSecret(String::from("token")),
+                "Secret 'token' is not defined"
+            );
+            test!(
+                undefined_item,
+                VarError::UndefinedItem,
+                "Variable 'item' is only defined in loops"
+            );
         }
     }
 }
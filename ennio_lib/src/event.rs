@@ -0,0 +1,219 @@
+use crate::{action::*, command::Stream, run};
+use log::warn;
+use serde::Serialize;
+use std::{
+    cell::RefCell,
+    fmt::{self, Debug, Formatter},
+    io::Write,
+    rc::Rc,
+};
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Event {
+    timestamp: u64,
+    workflow: String,
+    #[serde(flatten)]
+    kind: EventKind,
+}
+
+impl Event {
+    pub fn new(workflow: String, kind: EventKind) -> Self {
+        Self {
+            timestamp: run::now(),
+            workflow,
+            kind,
+        }
+    }
+
+    pub fn kind(&self) -> &EventKind {
+        &self.kind
+    }
+
+    pub fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
+    pub fn workflow(&self) -> &str {
+        &self.workflow
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum EventKind {
+    WorkflowStarted,
+    ActionStarted {
+        action: String,
+    },
+    OutputChunk {
+        action: String,
+        stream: Stream,
+        chunk: String,
+    },
+    ActionFinished {
+        action: String,
+        output: Output,
+    },
+    WorkflowFinished {
+        status: Status,
+    },
+}
+
+pub trait Observer {
+    fn notify(&self, event: &Event);
+}
+
+#[derive(Clone, Default)]
+pub struct Observers(Vec<Rc<dyn Observer>>);
+
+impl Observers {
+    pub fn new() -> Self {
+        Self(vec![])
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn notify(&self, event: &Event) {
+        for observer in self.0.iter() {
+            observer.notify(event);
+        }
+    }
+
+    pub fn with(mut self, observer: Rc<dyn Observer>) -> Self {
+        self.0.push(observer);
+        self
+    }
+}
+
+impl Debug for Observers {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "Observers({})", self.0.len())
+    }
+}
+
+impl PartialEq for Observers {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.len() == other.0.len()
+            && self
+                .0
+                .iter()
+                .zip(other.0.iter())
+                .all(|(observer, other)| Rc::ptr_eq(observer, other))
+    }
+}
+
+pub struct JsonLinesObserver<W: Write> {
+    writer: RefCell<W>,
+}
+
+impl<W: Write> JsonLinesObserver<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer: RefCell::new(writer),
+        }
+    }
+}
+
+impl<W: Write> Observer for JsonLinesObserver<W> {
+    fn notify(&self, event: &Event) {
+        let mut writer = self.writer.borrow_mut();
+        let res = serde_json::to_string(event)
+            .map_err(|err| err.to_string())
+            .and_then(|line| {
+                writeln!(writer, "{}", line)
+                    .and_then(|_| writer.flush())
+                    .map_err(|err| err.to_string())
+            });
+        if let Err(err) = res {
+            warn!("Unable to write event: {}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+
+    #[derive(Default)]
+    pub struct ObserverStub(RefCell<Vec<Event>>);
+
+    impl ObserverStub {
+        pub fn events(&self) -> Vec<Event> {
+            self.0.borrow().clone()
+        }
+
+        pub fn kinds(&self) -> Vec<EventKind> {
+            self.0
+                .borrow()
+                .iter()
+                .map(|event| event.kind.clone())
+                .collect()
+        }
+    }
+
+    impl Observer for ObserverStub {
+        fn notify(&self, event: &Event) {
+            self.0.borrow_mut().push(event.clone());
+        }
+    }
+
+    mod observers {
+        use super::*;
+
+        mod notify {
+            use super::*;
+
+            #[test]
+            fn should_notify_all_observers() {
+                let observer1 = Rc::new(ObserverStub::default());
+                let observer2 = Rc::new(ObserverStub::default());
+                let observers = Observers::new()
+                    .with(observer1.clone())
+                    .with(observer2.clone());
+                let event = Event::new(String::from("workflow1"), EventKind::WorkflowStarted);
+                observers.notify(&event);
+                assert_eq!(observer1.events(), vec![event.clone()]);
+                assert_eq!(observer2.events(), vec![event]);
+            }
+        }
+    }
+
+    mod json_lines_observer {
+        use super::*;
+
+        mod notify {
+            use super::*;
+
+            #[test]
+            fn should_write_json_line() {
+                let observer = JsonLinesObserver::new(vec![]);
+                let mut event = Event::new(
+                    String::from("workflow1"),
+                    EventKind::ActionFinished {
+                        action: String::from("action1"),
+                        output: Output::new(Status::Changed).add_var("stdout", "foo".into()),
+                    },
+                );
+                event.timestamp = 1;
+                observer.notify(&event);
+                observer.notify(&Event {
+                    timestamp: 2,
+                    workflow: String::from("workflow1"),
+                    kind: EventKind::WorkflowFinished {
+                        status: Status::Changed,
+                    },
+                });
+                let content = String::from_utf8(observer.writer.into_inner()).unwrap();
+                let expected = concat!(
+                    r#"{"timestamp":1,"workflow":"workflow1","event":"action_finished","action":"action1","output":{"status":"changed","vars":{"stdout":"foo"}}}"#,
+                    "\n",
+                    r#"{"timestamp":2,"workflow":"workflow1","event":"workflow_finished","status":"changed"}"#,
+                    "\n"
+                );
+                assert_eq!(content, expected);
+            }
+        }
+    }
+}
@@ -2,5 +2,14 @@ pub mod action;
 pub mod command;
 pub mod config;
 pub mod context;
+pub mod event;
+pub mod logs;
+pub mod progress;
+pub mod report;
+pub mod run;
+pub mod secret;
+pub mod template;
+pub mod trace;
 pub mod var;
+pub mod vault;
 pub mod workflow;
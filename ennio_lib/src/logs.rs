@@ -0,0 +1,398 @@
+use crate::{event::*, run};
+use log::{debug, warn};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fmt::{self, Display, Formatter},
+    fs::{self, File, OpenOptions},
+    io::Write,
+    path::PathBuf,
+    time::Duration,
+};
+
+pub static LOG_DIR: &str = ".ennio/logs";
+
+pub struct LogDir {
+    path: PathBuf,
+}
+
+impl LogDir {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    pub fn prune(
+        &self,
+        keep: Option<usize>,
+        max_age: Option<Duration>,
+    ) -> Result<Vec<PathBuf>, LogError> {
+        let now = run::now();
+        let mut runs = self.runs()?;
+        runs.sort_by(|(id1, _), (id2, _)| id2.cmp(id1));
+        let mut pruned = vec![];
+        for (idx, (id, path)) in runs.into_iter().enumerate() {
+            let extra = keep.is_some_and(|keep| idx >= keep);
+            let expired = max_age.is_some_and(|max_age| id + max_age.as_millis() as u64 <= now);
+            if extra || expired {
+                debug!("Removing logs {}", path.display());
+                fs::remove_dir_all(&path)
+                    .map_err(|err| LogError::Writing(path.clone(), err.to_string()))?;
+                pruned.push(path);
+            }
+        }
+        Ok(pruned)
+    }
+
+    pub fn run_dir(&self, run_id: &str) -> PathBuf {
+        self.path.join(run_id)
+    }
+
+    fn runs(&self) -> Result<Vec<(u64, PathBuf)>, LogError> {
+        if !self.path.exists() {
+            return Ok(vec![]);
+        }
+        let entries = fs::read_dir(&self.path)
+            .map_err(|err| LogError::Reading(self.path.clone(), err.to_string()))?;
+        let mut runs = vec![];
+        for entry in entries {
+            let entry =
+                entry.map_err(|err| LogError::Reading(self.path.clone(), err.to_string()))?;
+            let path = entry.path();
+            let id = entry
+                .file_name()
+                .to_str()
+                .and_then(|name| name.parse::<u64>().ok());
+            if let (Some(id), true) = (id, path.is_dir()) {
+                runs.push((id, path));
+            }
+        }
+        Ok(runs)
+    }
+}
+
+#[derive(Debug)]
+pub enum LogError {
+    Reading(PathBuf, String),
+    Writing(PathBuf, String),
+}
+
+impl Display for LogError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Self::Reading(path, err) => write!(f, "Unable to read {}: {}", path.display(), err),
+            Self::Writing(path, err) => write!(f, "Unable to write {}: {}", path.display(), err),
+        }
+    }
+}
+
+pub struct LogObserver {
+    dir: PathBuf,
+    files: RefCell<HashMap<String, File>>,
+    errors: RefCell<Vec<LogError>>,
+}
+
+impl LogObserver {
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            files: RefCell::new(HashMap::new()),
+            errors: RefCell::new(vec![]),
+        }
+    }
+
+    pub fn take_errors(&self) -> Vec<LogError> {
+        self.errors.take()
+    }
+
+    fn open(&self, action_name: &str) -> Result<(), LogError> {
+        if self.files.borrow().contains_key(action_name) {
+            return Ok(());
+        }
+        fs::create_dir_all(&self.dir)
+            .map_err(|err| LogError::Writing(self.dir.clone(), err.to_string()))?;
+        let path = self.path(action_name);
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|err| LogError::Writing(path, err.to_string()))?;
+        self.files.borrow_mut().insert(action_name.into(), file);
+        Ok(())
+    }
+
+    fn path(&self, action_name: &str) -> PathBuf {
+        self.dir.join(format!("{}.log", action_name))
+    }
+
+    fn write(
+        &self,
+        action_name: &str,
+        timestamp: u64,
+        prefix: &str,
+        text: &str,
+    ) -> Result<(), LogError> {
+        let mut files = self.files.borrow_mut();
+        let Some(file) = files.get_mut(action_name) else {
+            return Ok(());
+        };
+        let timestamp = format!(
+            "{}.{:03}Z",
+            run::format_timestamp(timestamp).trim_end_matches('Z'),
+            timestamp % 1000
+        );
+        text.lines()
+            .try_for_each(|line| writeln!(file, "{} {} {}", timestamp, prefix, line))
+            .map_err(|err| LogError::Writing(self.path(action_name), err.to_string()))
+    }
+}
+
+impl Observer for LogObserver {
+    fn notify(&self, event: &Event) {
+        let res = match event.kind() {
+            EventKind::OutputChunk {
+                action,
+                stream,
+                chunk,
+            } if !chunk.is_empty() => self
+                .open(action)
+                .and_then(|_| self.write(action, event.timestamp(), &stream.to_string(), chunk)),
+            EventKind::ActionFinished { action, output } => {
+                let res = self.write(
+                    action,
+                    event.timestamp(),
+                    "ennio",
+                    &format!(
+                        "Action '{}' terminated with status: {}",
+                        action,
+                        output.status()
+                    ),
+                );
+                self.files.borrow_mut().remove(action);
+                res
+            }
+            EventKind::WorkflowStarted
+            | EventKind::WorkflowFinished { .. }
+            | EventKind::ActionStarted { .. }
+            | EventKind::OutputChunk { .. } => Ok(()),
+        };
+        if let Err(err) = res {
+            warn!("Unable to write action log: {}", err);
+            self.errors.borrow_mut().push(err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        action::{bash::*, *},
+        command::Stream,
+        context::Context,
+        var::*,
+        workflow::*,
+    };
+    use std::rc::Rc;
+    use tempfile::TempDir;
+
+    mod log_dir {
+        use super::*;
+
+        mod prune {
+            use super::*;
+
+            fn log_dir(ids: &[u64]) -> (TempDir, LogDir) {
+                let dir = TempDir::new().unwrap();
+                for id in ids {
+                    fs::create_dir_all(dir.path().join(id.to_string())).unwrap();
+                }
+                fs::write(dir.path().join("README"), "").unwrap();
+                fs::create_dir_all(dir.path().join("other")).unwrap();
+                let log_dir = LogDir::new(dir.path().into());
+                (dir, log_dir)
+            }
+
+            #[test]
+            fn should_keep_last_runs() {
+                let (dir, log_dir) = log_dir(&[1000, 3000, 2000]);
+                let pruned = log_dir.prune(Some(2), None).unwrap();
+                assert_eq!(pruned, vec![dir.path().join("1000")]);
+                assert!(dir.path().join("2000").exists());
+                assert!(dir.path().join("3000").exists());
+                assert!(dir.path().join("other").exists());
+            }
+
+            #[test]
+            fn should_remove_expired_runs() {
+                let now = run::now();
+                let (dir, log_dir) = log_dir(&[now - 10_000, now]);
+                let pruned = log_dir.prune(None, Some(Duration::from_secs(5))).unwrap();
+                assert_eq!(pruned, vec![dir.path().join((now - 10_000).to_string())]);
+                assert!(dir.path().join(now.to_string()).exists());
+            }
+
+            #[test]
+            fn should_return_empty_vec_if_no_dir() {
+                let dir = TempDir::new().unwrap();
+                let log_dir = LogDir::new(dir.path().join("logs"));
+                assert!(log_dir.prune(Some(0), None).unwrap().is_empty());
+            }
+        }
+    }
+
+    mod log_error {
+        use super::*;
+
+        mod to_string {
+            use super::*;
+
+            macro_rules! test {
+                ($name:ident, $err:expr, $expected:expr) => {
+                    #[test]
+                    fn $name() {
+                        assert_eq!($err.to_string(), $expected);
+                    }
+                };
+            }
+
+            test!(
+                reading,
+                LogError::Reading(PathBuf::from("logs"), String::from("denied")),
+                "Unable to read logs: denied"
+            );
+            test!(
+                writing,
+                LogError::Writing(PathBuf::from("logs/1/action1.log"), String::from("denied")),
+                "Unable to write logs/1/action1.log: denied"
+            );
+        }
+    }
+
+    mod log_observer {
+        use super::*;
+
+        mod notify {
+            use super::*;
+
+            fn event(kind: EventKind) -> Event {
+                Event::new(String::from("workflow1"), kind)
+            }
+
+            #[test]
+            fn should_write_action_output() {
+                let dir = TempDir::new().unwrap();
+                let run_dir = LogDir::new(dir.path().into()).run_dir("1000");
+                let observer = LogObserver::new(run_dir.clone());
+                observer.notify(&event(EventKind::WorkflowStarted));
+                observer.notify(&event(EventKind::ActionStarted {
+                    action: String::from("action1"),
+                }));
+                observer.notify(&event(EventKind::OutputChunk {
+                    action: String::from("action1"),
+                    stream: Stream::Stdout,
+                    chunk: String::from("foo\nbar\n"),
+                }));
+                observer.notify(&event(EventKind::OutputChunk {
+                    action: String::from("action1"),
+                    stream: Stream::Stderr,
+                    chunk: String::from("baz\n"),
+                }));
+                observer.notify(&event(EventKind::ActionFinished {
+                    action: String::from("action1"),
+                    output: Output::new(Status::Changed),
+                }));
+                observer.notify(&event(EventKind::ActionStarted {
+                    action: String::from("action2"),
+                }));
+                observer.notify(&event(EventKind::OutputChunk {
+                    action: String::from("action2"),
+                    stream: Stream::Stderr,
+                    chunk: String::from("error\n"),
+                }));
+                observer.notify(&event(EventKind::ActionFinished {
+                    action: String::from("action2"),
+                    output: Output::new(Status::Failed),
+                }));
+                let content = fs::read_to_string(run_dir.join("action1.log")).unwrap();
+                let lines: Vec<&str> = content
+                    .lines()
+                    .map(|line| line.split_once(' ').unwrap().1)
+                    .collect();
+                assert_eq!(
+                    lines,
+                    vec![
+                        "stdout foo",
+                        "stdout bar",
+                        "stderr baz",
+                        "ennio Action 'action1' terminated with status: changed"
+                    ]
+                );
+                let content = fs::read_to_string(run_dir.join("action2.log")).unwrap();
+                assert!(
+                    content.ends_with(" ennio Action 'action2' terminated with status: failed\n")
+                );
+                assert!(observer.take_errors().is_empty());
+            }
+
+            #[test]
+            fn should_not_create_log_without_output() {
+                let dir = TempDir::new().unwrap();
+                let run_dir = LogDir::new(dir.path().into()).run_dir("1000");
+                let observer = LogObserver::new(run_dir.clone());
+                observer.notify(&event(EventKind::ActionStarted {
+                    action: String::from("action1"),
+                }));
+                observer.notify(&event(EventKind::OutputChunk {
+                    action: String::from("action1"),
+                    stream: Stream::Stdout,
+                    chunk: String::new(),
+                }));
+                observer.notify(&event(EventKind::ActionFinished {
+                    action: String::from("action1"),
+                    output: Output::new(Status::Skipped),
+                }));
+                assert!(!run_dir.exists());
+                assert!(observer.take_errors().is_empty());
+            }
+
+            #[test]
+            fn should_not_write_multi_line_secrets() {
+                let dir = TempDir::new().unwrap();
+                let observer = Rc::new(LogObserver::new(dir.path().into()));
+                let action = BashActionBuilder::new(
+                    String::from("action1"),
+                    String::from("echo \"{{ secrets.key }}\""),
+                )
+                .build(&Context::new("workflow1"))
+                .unwrap();
+                let secret = Value::Secret(Secret::new(String::from("line-one\nline-two")));
+                let mut secrets = Hash::new();
+                secrets.insert(String::from("key"), secret);
+                Workflow::new(String::from("workflow1"))
+                    .with_secrets(secrets)
+                    .with_steps(vec![Step::new(action)])
+                    .run_observed(Hash::new(), &Observers::new().with(observer.clone()));
+                let content = fs::read_to_string(dir.path().join("action1.log")).unwrap();
+                assert!(content.contains(" stdout ***\n"));
+                assert!(!content.contains("line-"));
+                assert!(observer.take_errors().is_empty());
+            }
+
+            #[test]
+            fn should_keep_errors() {
+                let dir = TempDir::new().unwrap();
+                let file = dir.path().join("file");
+                fs::write(&file, "").unwrap();
+                let observer = LogObserver::new(file.join("1000"));
+                observer.notify(&event(EventKind::OutputChunk {
+                    action: String::from("action1"),
+                    stream: Stream::Stdout,
+                    chunk: String::from("foo\n"),
+                }));
+                let errors = observer.take_errors();
+                assert_eq!(errors.len(), 1);
+                assert!(matches!(errors[0], LogError::Writing(_, _)));
+            }
+        }
+    }
+}
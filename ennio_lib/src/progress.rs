@@ -0,0 +1,531 @@
+use crate::{action::*, event::*};
+use log::warn;
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    io::Write,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+pub const DEFAULT_WIDTH: usize = 80;
+
+const TAIL_LINES: usize = 5;
+const TICK: Duration = Duration::from_millis(100);
+const SPINNER: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+
+const RESET: &str = "\x1b[0m";
+const DIM: &str = "\x1b[2m";
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const YELLOW: &str = "\x1b[33m";
+const CYAN: &str = "\x1b[36m";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ActionState {
+    Pending,
+    Running,
+    Done(Status),
+}
+
+#[derive(Debug)]
+struct Entry {
+    name: String,
+    depth: usize,
+    state: ActionState,
+    started: Option<Instant>,
+    duration: Option<Duration>,
+}
+
+#[derive(Debug, Default)]
+pub struct Progress {
+    entries: Vec<Entry>,
+    stack: Vec<usize>,
+    last_started: Option<usize>,
+    workflows: usize,
+    finished: bool,
+    tail: VecDeque<String>,
+    frame: usize,
+}
+
+impl Progress {
+    pub fn new(action_names: Vec<String>) -> Self {
+        let entries = action_names
+            .into_iter()
+            .map(|name| Entry {
+                name,
+                depth: 0,
+                state: ActionState::Pending,
+                started: None,
+                duration: None,
+            })
+            .collect();
+        Self {
+            entries,
+            ..Self::default()
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    pub fn render(&self, width: usize) -> Vec<String> {
+        let mut lines: Vec<String> = self
+            .entries
+            .iter()
+            .map(|entry| {
+                let indent = "  ".repeat(entry.depth);
+                match entry.state {
+                    ActionState::Pending => {
+                        format!("{}{}· {}{}", indent, DIM, entry.name, RESET)
+                    }
+                    ActionState::Running => format!(
+                        "{}{}{}{} {} {}",
+                        indent,
+                        CYAN,
+                        SPINNER[self.frame % SPINNER.len()],
+                        RESET,
+                        entry.name,
+                        seconds(entry.started.map(|started| started.elapsed()))
+                    ),
+                    ActionState::Done(status) => {
+                        let (color, symbol) = match status {
+                            Status::Unchanged => (GREEN, '✓'),
+                            Status::Changed => (YELLOW, '✓'),
+                            Status::Failed => (RED, '✗'),
+                            Status::Skipped => (DIM, '-'),
+                        };
+                        let duration = match entry.duration {
+                            Some(duration) => format!(" {}", seconds(Some(duration))),
+                            None => String::new(),
+                        };
+                        format!(
+                            "{}{}{}{} {} {}{}",
+                            indent, color, symbol, RESET, entry.name, status, duration
+                        )
+                    }
+                }
+            })
+            .collect();
+        let tail_width = width.saturating_sub(4);
+        lines.extend(self.tail.iter().map(|line| {
+            let line: String = line.chars().take(tail_width).collect();
+            format!("{}  │ {}{}", DIM, line, RESET)
+        }));
+        lines
+    }
+
+    pub fn state(&self, action_name: &str) -> Option<ActionState> {
+        self.entries
+            .iter()
+            .find(|entry| entry.name == action_name)
+            .map(|entry| entry.state)
+    }
+
+    pub fn tick(&mut self) {
+        self.frame = self.frame.wrapping_add(1);
+    }
+
+    pub fn update(&mut self, event: &Event) {
+        match event.kind() {
+            EventKind::WorkflowStarted => self.workflows += 1,
+            EventKind::ActionStarted { action } => {
+                let depth = self.stack.len();
+                let from = self.last_started.map_or(0, |idx| idx + 1);
+                let pending = self.entries[from.min(self.entries.len())..]
+                    .iter()
+                    .position(|entry| {
+                        entry.name == *action
+                            && entry.depth == depth
+                            && entry.state == ActionState::Pending
+                    })
+                    .map(|idx| from + idx);
+                let idx = match pending {
+                    Some(idx) => idx,
+                    None => {
+                        let idx = from.min(self.entries.len());
+                        self.entries.insert(
+                            idx,
+                            Entry {
+                                name: action.clone(),
+                                depth,
+                                state: ActionState::Pending,
+                                started: None,
+                                duration: None,
+                            },
+                        );
+                        idx
+                    }
+                };
+                let entry = &mut self.entries[idx];
+                entry.state = ActionState::Running;
+                entry.started = Some(Instant::now());
+                self.stack.push(idx);
+                self.last_started = Some(idx);
+                self.tail.clear();
+            }
+            EventKind::OutputChunk { chunk, .. } => {
+                self.tail.extend(chunk.lines().map(String::from));
+                while self.tail.len() > TAIL_LINES {
+                    self.tail.pop_front();
+                }
+            }
+            EventKind::ActionFinished { output, .. } => {
+                if let Some(idx) = self.stack.pop() {
+                    let entry = &mut self.entries[idx];
+                    entry.state = ActionState::Done(output.status());
+                    entry.duration = output.duration();
+                }
+            }
+            EventKind::WorkflowFinished { .. } => {
+                self.workflows = self.workflows.saturating_sub(1);
+                if self.workflows == 0 {
+                    for entry in self.entries.iter_mut() {
+                        if entry.state == ActionState::Pending {
+                            entry.state = ActionState::Done(Status::Skipped);
+                        }
+                    }
+                    self.tail.clear();
+                    self.finished = true;
+                }
+            }
+        }
+    }
+}
+
+struct Screen<W: Write> {
+    progress: Progress,
+    writer: W,
+    width: usize,
+    lines: usize,
+}
+
+impl<W: Write> Screen<W> {
+    fn draw(&mut self) {
+        let lines = self.progress.render(self.width);
+        let mut frame = String::new();
+        if self.lines > 0 {
+            frame.push_str(&format!("\x1b[{}F\x1b[J", self.lines));
+        } else {
+            frame.push_str("\x1b[?25l");
+        }
+        for line in lines.iter() {
+            frame.push_str(line);
+            frame.push('\n');
+        }
+        if self.progress.is_finished() {
+            frame.push_str("\x1b[?25h");
+        }
+        self.lines = lines.len();
+        if let Err(err) = self
+            .writer
+            .write_all(frame.as_bytes())
+            .and_then(|_| self.writer.flush())
+        {
+            warn!("Unable to draw progress: {}", err);
+        }
+    }
+}
+
+struct Ticker {
+    stop: Arc<AtomicBool>,
+    handle: JoinHandle<()>,
+}
+
+pub struct ProgressObserver<W: Write + Send + 'static> {
+    screen: Arc<Mutex<Screen<W>>>,
+    interactive: bool,
+    ticker: RefCell<Option<Ticker>>,
+}
+
+impl<W: Write + Send + 'static> ProgressObserver<W> {
+    pub fn new(writer: W, interactive: bool, action_names: Vec<String>) -> Self {
+        Self {
+            screen: Arc::new(Mutex::new(Screen {
+                progress: Progress::new(action_names),
+                writer,
+                width: DEFAULT_WIDTH,
+                lines: 0,
+            })),
+            interactive,
+            ticker: RefCell::new(None),
+        }
+    }
+
+    pub fn with_width(self, width: usize) -> Self {
+        if let Ok(mut screen) = self.screen.lock() {
+            screen.width = width;
+        }
+        self
+    }
+
+    fn start_ticker(&self) {
+        let stop = Arc::new(AtomicBool::new(false));
+        let screen = self.screen.clone();
+        let should_stop = stop.clone();
+        let handle = thread::spawn(move || {
+            while !should_stop.load(Ordering::Relaxed) {
+                thread::sleep(TICK);
+                if let Ok(mut screen) = screen.lock() {
+                    if should_stop.load(Ordering::Relaxed) {
+                        break;
+                    }
+                    screen.progress.tick();
+                    screen.draw();
+                }
+            }
+        });
+        *self.ticker.borrow_mut() = Some(Ticker { stop, handle });
+    }
+
+    fn stop_ticker(&self) {
+        if let Some(ticker) = self.ticker.borrow_mut().take() {
+            ticker.stop.store(true, Ordering::Relaxed);
+            let _ = ticker.handle.join();
+        }
+    }
+
+    fn print(&self, screen: &mut Screen<W>, event: &Event) {
+        let line = match event.kind() {
+            EventKind::ActionStarted { action } => Some(format!("{}: running", action)),
+            EventKind::ActionFinished { action, output } => Some(match output.duration() {
+                Some(duration) => format!(
+                    "{}: {} ({})",
+                    action,
+                    output.status(),
+                    seconds(Some(duration))
+                ),
+                None => format!("{}: {}", action, output.status()),
+            }),
+            _ => None,
+        };
+        if let Some(line) = line {
+            if let Err(err) = writeln!(screen.writer, "{}", line) {
+                warn!("Unable to print progress: {}", err);
+            }
+        }
+    }
+}
+
+impl<W: Write + Send + 'static> Observer for ProgressObserver<W> {
+    fn notify(&self, event: &Event) {
+        let finished = {
+            let Ok(mut screen) = self.screen.lock() else {
+                return;
+            };
+            screen.progress.update(event);
+            if self.interactive {
+                screen.draw();
+            } else {
+                self.print(&mut screen, event);
+            }
+            screen.progress.is_finished()
+        };
+        if self.interactive {
+            if finished {
+                self.stop_ticker();
+            } else if self.ticker.borrow().is_none() {
+                self.start_ticker();
+            }
+        }
+    }
+}
+
+impl<W: Write + Send + 'static> Drop for ProgressObserver<W> {
+    fn drop(&mut self) {
+        self.stop_ticker();
+    }
+}
+
+fn seconds(duration: Option<Duration>) -> String {
+    format!("{:.1}s", duration.unwrap_or_default().as_secs_f64())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Buffer {
+        fn content(&self) -> String {
+            String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+        }
+    }
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn event(kind: EventKind) -> Event {
+        Event::new(String::from("workflow1"), kind)
+    }
+
+    fn started(action: &str) -> Event {
+        event(EventKind::ActionStarted {
+            action: action.into(),
+        })
+    }
+
+    fn finished(action: &str, status: Status) -> Event {
+        event(EventKind::ActionFinished {
+            action: action.into(),
+            output: Output::new(status).with_timing(0, 1500, Duration::from_millis(1500)),
+        })
+    }
+
+    fn workflow_finished() -> Event {
+        event(EventKind::WorkflowFinished {
+            status: Status::Changed,
+        })
+    }
+
+    mod progress {
+        use super::*;
+
+        mod update {
+            use super::*;
+
+            #[test]
+            fn should_track_action_states() {
+                let mut progress =
+                    Progress::new(vec![String::from("action1"), String::from("action2")]);
+                progress.update(&event(EventKind::WorkflowStarted));
+                assert_eq!(progress.state("action1"), Some(ActionState::Pending));
+                progress.update(&started("action1"));
+                assert_eq!(progress.state("action1"), Some(ActionState::Running));
+                progress.update(&finished("action1", Status::Changed));
+                assert_eq!(
+                    progress.state("action1"),
+                    Some(ActionState::Done(Status::Changed))
+                );
+                assert!(!progress.is_finished());
+                progress.update(&workflow_finished());
+                assert_eq!(
+                    progress.state("action2"),
+                    Some(ActionState::Done(Status::Skipped))
+                );
+                assert!(progress.is_finished());
+            }
+
+            #[test]
+            fn should_insert_nested_actions_after_parent() {
+                let mut progress =
+                    Progress::new(vec![String::from("block1"), String::from("action2")]);
+                progress.update(&event(EventKind::WorkflowStarted));
+                progress.update(&started("block1"));
+                progress.update(&started("action1"));
+                progress.update(&finished("action1", Status::Unchanged));
+                let names: Vec<(&str, usize)> = progress
+                    .entries
+                    .iter()
+                    .map(|entry| (entry.name.as_str(), entry.depth))
+                    .collect();
+                assert_eq!(names, vec![("block1", 0), ("action1", 1), ("action2", 0)]);
+                assert_eq!(progress.state("block1"), Some(ActionState::Running));
+            }
+
+            #[test]
+            fn should_keep_tail_of_output() {
+                let mut progress = Progress::new(vec![String::from("action1")]);
+                progress.update(&started("action1"));
+                for idx in 0..7 {
+                    progress.update(&event(EventKind::OutputChunk {
+                        action: String::from("action1"),
+                        stream: crate::command::Stream::Stdout,
+                        chunk: format!("line{}\n", idx),
+                    }));
+                }
+                let tail: Vec<&str> = progress.tail.iter().map(String::as_str).collect();
+                assert_eq!(tail, vec!["line2", "line3", "line4", "line5", "line6"]);
+                progress.update(&started("action2"));
+                assert!(progress.tail.is_empty());
+            }
+        }
+
+        mod render {
+            use super::*;
+
+            #[test]
+            fn should_return_lines() {
+                let mut progress = Progress::new(vec![
+                    String::from("action1"),
+                    String::from("action2"),
+                    String::from("action3"),
+                ]);
+                progress.update(&started("action1"));
+                progress.update(&finished("action1", Status::Failed));
+                progress.update(&started("action2"));
+                progress.update(&event(EventKind::OutputChunk {
+                    action: String::from("action2"),
+                    stream: crate::command::Stream::Stdout,
+                    chunk: String::from("0123456789\n"),
+                }));
+                let expected = vec![
+                    format!("{}✗{} action1 failed 1.5s", RED, RESET),
+                    format!("{}⠋{} action2 0.0s", CYAN, RESET),
+                    format!("{}· action3{}", DIM, RESET),
+                    format!("{}  │ 012345{}", DIM, RESET),
+                ];
+                assert_eq!(progress.render(10), expected);
+            }
+        }
+    }
+
+    mod progress_observer {
+        use super::*;
+
+        mod notify {
+            use super::*;
+
+            #[test]
+            fn should_print_plain_lines_if_not_interactive() {
+                let buffer = Buffer::default();
+                let observer =
+                    ProgressObserver::new(buffer.clone(), false, vec![String::from("action1")]);
+                observer.notify(&event(EventKind::WorkflowStarted));
+                observer.notify(&started("action1"));
+                observer.notify(&finished("action1", Status::Changed));
+                observer.notify(&workflow_finished());
+                assert_eq!(
+                    buffer.content(),
+                    "action1: running\naction1: changed (1.5s)\n"
+                );
+                assert!(observer.ticker.borrow().is_none());
+            }
+
+            #[test]
+            fn should_redraw_screen_if_interactive() {
+                let buffer = Buffer::default();
+                let observer =
+                    ProgressObserver::new(buffer.clone(), true, vec![String::from("action1")]);
+                observer.notify(&event(EventKind::WorkflowStarted));
+                assert!(observer.ticker.borrow().is_some());
+                observer.notify(&started("action1"));
+                observer.notify(&finished("action1", Status::Changed));
+                observer.notify(&workflow_finished());
+                assert!(observer.ticker.borrow().is_none());
+                let content = buffer.content();
+                assert!(content.starts_with("\x1b[?25l"));
+                assert!(content.contains("\x1b[1F\x1b[J"));
+                assert!(content.ends_with(&format!(
+                    "\x1b[1F\x1b[J{}✓{} action1 changed 1.5s\n\x1b[?25h",
+                    YELLOW, RESET
+                )));
+            }
+        }
+    }
+}
@@ -0,0 +1,308 @@
+use crate::{
+    action::*,
+    report::escape,
+    run::{self, ActionRun, Run},
+    var::*,
+};
+use std::{fmt::Write, time::Duration};
+
+const STYLE: &str = r#"body { font-family: sans-serif; margin: 2em; color: #222; }
+h1 { font-size: 1.6em; }
+h2 { font-size: 1.2em; margin-top: 1.5em; }
+table.summary td { padding: 0.2em 1em 0.2em 0; }
+.badge { display: inline-block; padding: 0.1em 0.6em; border-radius: 0.8em; font-size: 0.85em; color: #fff; }
+.changed { background: #d98e04; }
+.unchanged { background: #2e8b57; }
+.failed { background: #c0392b; }
+.skipped { background: #7f8c8d; }
+.timeline { border-left: 1px solid #ccc; }
+.row { display: flex; align-items: center; margin: 0.2em 0; }
+.row .name { width: 12em; overflow: hidden; text-overflow: ellipsis; white-space: nowrap; padding: 0 0.5em; }
+.row .track { flex: 1; position: relative; height: 1em; background: #f2f2f2; }
+.row .bar { position: absolute; top: 0; height: 100%; min-width: 2px; }
+.row .time { width: 6em; text-align: right; font-size: 0.85em; }
+details { margin: 0.4em 0; }
+summary { cursor: pointer; }
+.action { border: 1px solid #ddd; border-radius: 4px; padding: 0.5em 1em; margin: 0.5em 0; }
+pre { background: #f7f7f7; padding: 0.5em; overflow-x: auto; }
+ul.vars { list-style: none; padding-left: 1.2em; margin: 0; }
+.key { font-weight: bold; }
+"#;
+
+pub fn html(run: &Run) -> String {
+    let title = format!("{} #{}", run.workflow(), run.id());
+    let mut html = String::from("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n");
+    html.push_str("<meta charset=\"UTF-8\">\n");
+    let _ = writeln!(html, "<title>{}</title>", escape(&title));
+    let _ = writeln!(html, "<style>\n{}</style>", STYLE);
+    html.push_str("</head>\n<body>\n");
+    let _ = writeln!(html, "<h1>{}</h1>", escape(&title));
+    html.push_str("<table class=\"summary\">\n");
+    let _ = writeln!(
+        html,
+        "<tr><td>Status</td><td>{}</td></tr>",
+        badge(run.status())
+    );
+    let _ = writeln!(
+        html,
+        "<tr><td>Started</td><td>{}</td></tr>",
+        run::format_timestamp(run.started_at())
+    );
+    let _ = writeln!(
+        html,
+        "<tr><td>Finished</td><td>{}</td></tr>",
+        run::format_timestamp(run.finished_at())
+    );
+    let _ = writeln!(
+        html,
+        "<tr><td>Duration</td><td>{}</td></tr>",
+        run::format_duration(run.duration())
+    );
+    html.push_str("</table>\n");
+    html.push_str("<h2>Timeline</h2>\n");
+    html.push_str(&timeline(run));
+    html.push_str("<h2>Actions</h2>\n");
+    for action in run.actions() {
+        html.push_str(&action_section(action));
+    }
+    html.push_str("<h2>Variables</h2>\n");
+    html.push_str(&tree(&Value::Hash(run.vars().clone())));
+    html.push('\n');
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+fn badge(status: Status) -> String {
+    format!("<span class=\"badge {}\">{}</span>", status, status)
+}
+
+fn timeline(run: &Run) -> String {
+    let mut offset = Duration::ZERO;
+    let bars: Vec<(&ActionRun, Duration, Duration)> = run
+        .actions()
+        .iter()
+        .map(|action| {
+            let output = action.output();
+            let duration = output.duration().unwrap_or_default();
+            let start = output
+                .started_at()
+                .map(|started_at| {
+                    Duration::from_millis(started_at.saturating_sub(run.started_at()))
+                })
+                .unwrap_or(offset);
+            offset = start + duration;
+            (action, start, duration)
+        })
+        .collect();
+    let total = bars
+        .iter()
+        .map(|(_, start, duration)| *start + *duration)
+        .max()
+        .unwrap_or_default()
+        .max(run.duration())
+        .as_secs_f64();
+    let percent = |duration: Duration| {
+        if total > 0.0 {
+            duration.as_secs_f64() * 100.0 / total
+        } else {
+            0.0
+        }
+    };
+    let mut html = String::from("<div class=\"timeline\">\n");
+    for (action, start, duration) in bars {
+        let _ = writeln!(
+            html,
+            "<div class=\"row\"><span class=\"name\">{}</span><span class=\"track\"><span class=\"bar {}\" style=\"left: {:.2}%; width: {:.2}%\"></span></span><span class=\"time\">{}</span></div>",
+            escape(action.name()),
+            action.output().status(),
+            percent(start),
+            percent(duration),
+            run::format_duration(duration)
+        );
+    }
+    html.push_str("</div>\n");
+    html
+}
+
+fn action_section(action: &ActionRun) -> String {
+    let output = action.output();
+    let mut html = String::from("<div class=\"action\">\n");
+    let _ = writeln!(
+        html,
+        "<h3>{} {}</h3>",
+        escape(action.name()),
+        badge(output.status())
+    );
+    if let (Some(started_at), Some(duration)) = (output.started_at(), output.duration()) {
+        let _ = writeln!(
+            html,
+            "<p class=\"timing\">Started at {}, took {}</p>",
+            run::format_timestamp(started_at),
+            run::format_duration(duration)
+        );
+    }
+    for stream in ["stdout", "stderr"] {
+        let text = output
+            .value(stream)
+            .map(Value::to_string)
+            .unwrap_or_default();
+        if !text.is_empty() {
+            let _ = writeln!(
+                html,
+                "<details><summary>{}</summary><pre>{}</pre></details>",
+                stream,
+                escape(&text)
+            );
+        }
+    }
+    let vars: Hash = output
+        .vars()
+        .iter()
+        .filter(|(name, _)| !matches!(name.as_str(), "stdout" | "stderr"))
+        .map(|(name, value)| (name.clone(), value.clone()))
+        .collect();
+    if !vars.is_empty() {
+        let _ = writeln!(
+            html,
+            "<details><summary>vars</summary>{}</details>",
+            tree(&Value::Hash(vars))
+        );
+    }
+    html.push_str("</div>\n");
+    html
+}
+
+fn tree(value: &Value) -> String {
+    let entries: Vec<(String, &Value)> = match value {
+        Value::Hash(hash) => {
+            let mut entries: Vec<(String, &Value)> = hash
+                .iter()
+                .map(|(key, value)| (key.clone(), value))
+                .collect();
+            entries.sort_by(|(key1, _), (key2, _)| key1.cmp(key2));
+            entries
+        }
+        Value::Array(array) => array
+            .items()
+            .iter()
+            .enumerate()
+            .map(|(idx, value)| (idx.to_string(), value))
+            .collect(),
+        Value::Null => return String::from("<code>null</code>"),
+        value => return format!("<code>{}</code>", escape(&value.to_string())),
+    };
+    let mut html = String::from("<ul class=\"vars\">");
+    for (key, value) in entries {
+        let _ = write!(
+            html,
+            "<li><span class=\"key\">{}</span>: {}</li>",
+            escape(&key),
+            tree(value)
+        );
+    }
+    html.push_str("</ul>");
+    html
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    mod html {
+        use super::*;
+
+        #[test]
+        fn should_return_report() {
+            let run: Run = serde_json::from_value(serde_json::json!({
+                "id": "1",
+                "workflow": "deploy",
+                "status": "failed",
+                "started_at": 0,
+                "finished_at": 2000,
+                "vars": {"env": "prod"},
+                "actions": [
+                    {
+                        "name": "build",
+                        "status": "changed",
+                        "vars": {"stdout": "built <app>\n", "stderr": "", "rc": 0},
+                        "started_at": 0,
+                        "finished_at": 1000,
+                        "duration": {"secs": 1, "nanos": 0}
+                    },
+                    {
+                        "name": "push",
+                        "status": "failed",
+                        "vars": {"stdout": "", "stderr": "denied"},
+                        "duration": {"secs": 1, "nanos": 0}
+                    }
+                ]
+            }))
+            .unwrap();
+            let html = html(&run);
+            assert!(html.starts_with("<!DOCTYPE html>\n"));
+            assert!(html.contains("<title>deploy #1</title>"));
+            assert!(html.contains("<td>Status</td><td><span class=\"badge failed\">failed</span>"));
+            assert!(
+                html.contains("<span class=\"bar changed\" style=\"left: 0.00%; width: 50.00%\">")
+            );
+            assert!(
+                html.contains("<span class=\"bar failed\" style=\"left: 50.00%; width: 50.00%\">")
+            );
+            assert!(html
+                .contains("<p class=\"timing\">Started at 1970-01-01T00:00:00Z, took 1.000s</p>"));
+            assert!(html.contains(
+                "<details><summary>stdout</summary><pre>built &lt;app&gt;\n</pre></details>"
+            ));
+            assert!(html.contains("<details><summary>stderr</summary><pre>denied</pre></details>"));
+            assert!(html.contains(
+                "<details><summary>vars</summary><ul class=\"vars\"><li><span class=\"key\">rc</span>: <code>0</code></li></ul></details>"
+            ));
+            assert!(html.contains(
+                "<ul class=\"vars\"><li><span class=\"key\">env</span>: <code>prod</code></li></ul>"
+            ));
+            assert!(!html.contains("src=") && !html.contains("href="));
+        }
+    }
+
+    mod tree {
+        use super::*;
+        use crate::*;
+
+        macro_rules! test {
+            ($name:ident, $value:expr, $expected:expr) => {
+                #[test]
+                fn $name() {
+                    assert_eq!(tree(&$value), $expected);
+                }
+            };
+        }
+
+        test!(null, Value::Null, "<code>null</code>");
+        test!(string, Value::from("<b>"), "<code>&lt;b&gt;</code>");
+        test!(
+            secret,
+            Value::Secret(Secret::new(String::from("password"))),
+            "<code>***</code>"
+        );
+        test!(
+            array,
+            Value::from(array!(1, 2)),
+            concat!(
+                "<ul class=\"vars\">",
+                "<li><span class=\"key\">0</span>: <code>1</code></li>",
+                "<li><span class=\"key\">1</span>: <code>2</code></li>",
+                "</ul>"
+            )
+        );
+        test!(
+            hash,
+            Value::from(hash!("b", 1, "a", hash!("c", true))),
+            concat!(
+                "<ul class=\"vars\">",
+                "<li><span class=\"key\">a</span>: <ul class=\"vars\"><li><span class=\"key\">c</span>: <code>true</code></li></ul></li>",
+                "<li><span class=\"key\">b</span>: <code>1</code></li>",
+                "</ul>"
+            )
+        );
+    }
+}
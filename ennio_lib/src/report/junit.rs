@@ -0,0 +1,145 @@
+use crate::{
+    action::*,
+    report::escape,
+    run::{self, ActionRun, Run},
+    var::*,
+};
+use std::{fmt::Write, time::Duration};
+
+pub fn junit(run: &Run) -> String {
+    let count = |status: Status| {
+        run.actions()
+            .iter()
+            .filter(|action| action.output().status() == status)
+            .count()
+    };
+    let tests = run.actions().len();
+    let failures = count(Status::Failed);
+    let skipped = count(Status::Skipped);
+    let time = seconds(run.duration());
+    let name = escape(run.workflow());
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let _ = writeln!(
+        xml,
+        "<testsuites name=\"ennio\" tests=\"{}\" failures=\"{}\" skipped=\"{}\" time=\"{}\">",
+        tests, failures, skipped, time
+    );
+    let _ = writeln!(
+        xml,
+        "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"0\" skipped=\"{}\" time=\"{}\" timestamp=\"{}\">",
+        name,
+        tests,
+        failures,
+        skipped,
+        time,
+        run::format_timestamp(run.started_at())
+    );
+    for action in run.actions() {
+        xml.push_str(&testcase(&name, action));
+    }
+    xml.push_str("  </testsuite>\n</testsuites>\n");
+    xml
+}
+
+fn testcase(classname: &str, action: &ActionRun) -> String {
+    let output = action.output();
+    let var = |name: &str| output.value(name).map(Value::to_string).unwrap_or_default();
+    let stdout = var("stdout");
+    let stderr = var("stderr");
+    let mut xml = String::new();
+    let _ = writeln!(
+        xml,
+        "    <testcase name=\"{}\" classname=\"{}\" time=\"{}\">",
+        escape(action.name()),
+        classname,
+        seconds(output.duration().unwrap_or_default())
+    );
+    match output.status() {
+        Status::Failed => {
+            let _ = writeln!(
+                xml,
+                "      <failure message=\"{}\">{}</failure>",
+                escape(&format!("Action '{}' failed", action.name())),
+                escape(&stderr)
+            );
+        }
+        Status::Skipped => {
+            let message = output
+                .value("reason")
+                .map(|reason| format!(" message=\"{}\"", escape(&reason.to_string())))
+                .unwrap_or_default();
+            let _ = writeln!(xml, "      <skipped{}/>", message);
+        }
+        Status::Changed | Status::Unchanged => {}
+    }
+    if !stdout.is_empty() {
+        let _ = writeln!(xml, "      <system-out>{}</system-out>", escape(&stdout));
+    }
+    if !stderr.is_empty() {
+        let _ = writeln!(xml, "      <system-err>{}</system-err>", escape(&stderr));
+    }
+    xml.push_str("    </testcase>\n");
+    xml
+}
+
+fn seconds(duration: Duration) -> String {
+    format!("{:.3}", duration.as_secs_f64())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    mod junit {
+        use super::*;
+
+        #[test]
+        fn should_return_report() {
+            let run: Run = serde_json::from_value(serde_json::json!({
+                "id": "1",
+                "workflow": "deploy",
+                "status": "failed",
+                "started_at": 0,
+                "finished_at": 1500,
+                "actions": [
+                    {
+                        "name": "build",
+                        "status": "changed",
+                        "vars": {"stdout": "built <app>\n", "stderr": ""},
+                        "duration": {"secs": 1, "nanos": 0}
+                    },
+                    {
+                        "name": "test",
+                        "status": "skipped",
+                        "vars": {}
+                    },
+                    {
+                        "name": "push",
+                        "status": "failed",
+                        "vars": {"stdout": "", "stderr": "denied"},
+                        "duration": {"secs": 0, "nanos": 250000000}
+                    }
+                ]
+            }))
+            .unwrap();
+            let expected = r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuites name="ennio" tests="3" failures="1" skipped="1" time="1.500">
+  <testsuite name="deploy" tests="3" failures="1" errors="0" skipped="1" time="1.500" timestamp="1970-01-01T00:00:00Z">
+    <testcase name="build" classname="deploy" time="1.000">
+      <system-out>built &lt;app&gt;
+</system-out>
+    </testcase>
+    <testcase name="test" classname="deploy" time="0.000">
+      <skipped/>
+    </testcase>
+    <testcase name="push" classname="deploy" time="0.250">
+      <failure message="Action &#39;push&#39; failed">denied</failure>
+      <system-err>denied</system-err>
+    </testcase>
+  </testsuite>
+</testsuites>
+"#;
+            assert_eq!(junit(&run), expected);
+        }
+    }
+}
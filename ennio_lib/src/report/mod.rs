@@ -0,0 +1,45 @@
+pub mod html;
+pub mod junit;
+pub mod prometheus;
+
+fn escape(text: &str) -> String {
+    text.chars()
+        .filter(|c| !c.is_control() || matches!(c, '\n' | '\r' | '\t'))
+        .fold(String::new(), |mut escaped, c| {
+            match c {
+                '&' => escaped.push_str("&amp;"),
+                '<' => escaped.push_str("&lt;"),
+                '>' => escaped.push_str("&gt;"),
+                '"' => escaped.push_str("&quot;"),
+                '\'' => escaped.push_str("&#39;"),
+                c => escaped.push(c),
+            }
+            escaped
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    mod escape {
+        use super::*;
+
+        macro_rules! test {
+            ($name:ident, $text:expr, $expected:expr) => {
+                #[test]
+                fn $name() {
+                    assert_eq!(escape($text), $expected);
+                }
+            };
+        }
+
+        test!(text, "it works!", "it works!");
+        test!(
+            special_chars,
+            r#"<a href="x">'&'</a>"#,
+            "&lt;a href=&quot;x&quot;&gt;&#39;&amp;&#39;&lt;/a&gt;"
+        );
+        test!(control_chars, "a\u{1b}[0mb\n", "a[0mb\n");
+    }
+}
@@ -0,0 +1,250 @@
+use crate::{action::*, run::Run};
+use std::{collections::BTreeMap, fmt::Write, time::Duration};
+
+pub static DURATION_BUCKETS: [f64; 11] = [
+    0.1, 0.5, 1.0, 5.0, 10.0, 30.0, 60.0, 300.0, 600.0, 1800.0, 3600.0,
+];
+
+const STATUSES: [Status; 4] = [
+    Status::Unchanged,
+    Status::Changed,
+    Status::Failed,
+    Status::Skipped,
+];
+
+pub fn metrics(run: &Run, history: &[Run]) -> String {
+    let runs: Vec<&Run> = history
+        .iter()
+        .filter(|previous| previous.workflow() == run.workflow() && previous.id() != run.id())
+        .chain([run])
+        .collect();
+    let workflow = format!("workflow=\"{}\"", label(run.workflow()));
+    let mut text = String::new();
+    let _ = writeln!(
+        text,
+        "# HELP ennio_workflow_duration_seconds Duration of the last workflow run."
+    );
+    let _ = writeln!(text, "# TYPE ennio_workflow_duration_seconds gauge");
+    let _ = writeln!(
+        text,
+        "ennio_workflow_duration_seconds{{{}}} {}",
+        workflow,
+        seconds(run.duration())
+    );
+    let _ = writeln!(
+        text,
+        "# HELP ennio_workflow_last_run_timestamp_seconds End time of the last workflow run."
+    );
+    let _ = writeln!(
+        text,
+        "# TYPE ennio_workflow_last_run_timestamp_seconds gauge"
+    );
+    let _ = writeln!(
+        text,
+        "ennio_workflow_last_run_timestamp_seconds{{{}}} {}",
+        workflow,
+        timestamp(run.finished_at())
+    );
+    let last_success = runs.iter().rev().find(|run| run.status() != Status::Failed);
+    if let Some(last_success) = last_success {
+        let _ = writeln!(
+            text,
+            "# HELP ennio_workflow_last_success_timestamp_seconds End time of the last successful workflow run."
+        );
+        let _ = writeln!(
+            text,
+            "# TYPE ennio_workflow_last_success_timestamp_seconds gauge"
+        );
+        let _ = writeln!(
+            text,
+            "ennio_workflow_last_success_timestamp_seconds{{{}}} {}",
+            workflow,
+            timestamp(last_success.finished_at())
+        );
+    }
+    let _ = writeln!(
+        text,
+        "# HELP ennio_actions Number of actions of the last workflow run by status."
+    );
+    let _ = writeln!(text, "# TYPE ennio_actions gauge");
+    for status in STATUSES {
+        let count = run
+            .actions()
+            .iter()
+            .filter(|action| action.output().status() == status)
+            .count();
+        let _ = writeln!(
+            text,
+            "ennio_actions{{{},status=\"{}\"}} {}",
+            workflow, status, count
+        );
+    }
+    let mut durations: BTreeMap<&str, Vec<Duration>> = BTreeMap::new();
+    for action in runs.iter().flat_map(|run| run.actions()) {
+        if let Some(duration) = action.output().duration() {
+            durations.entry(action.name()).or_default().push(duration);
+        }
+    }
+    let _ = writeln!(
+        text,
+        "# HELP ennio_action_duration_seconds Duration of the actions over the recorded runs."
+    );
+    let _ = writeln!(text, "# TYPE ennio_action_duration_seconds histogram");
+    for (action, durations) in durations {
+        let labels = format!("{},action=\"{}\"", workflow, label(action));
+        let secs: Vec<f64> = durations.iter().map(Duration::as_secs_f64).collect();
+        for bucket in DURATION_BUCKETS {
+            let count = secs.iter().filter(|secs| **secs <= bucket).count();
+            let _ = writeln!(
+                text,
+                "ennio_action_duration_seconds_bucket{{{},le=\"{}\"}} {}",
+                labels, bucket, count
+            );
+        }
+        let _ = writeln!(
+            text,
+            "ennio_action_duration_seconds_bucket{{{},le=\"+Inf\"}} {}",
+            labels,
+            secs.len()
+        );
+        let _ = writeln!(
+            text,
+            "ennio_action_duration_seconds_sum{{{}}} {}",
+            labels,
+            seconds(durations.iter().sum())
+        );
+        let _ = writeln!(
+            text,
+            "ennio_action_duration_seconds_count{{{}}} {}",
+            labels,
+            secs.len()
+        );
+    }
+    text
+}
+
+fn label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn seconds(duration: Duration) -> String {
+    format!("{:.3}", duration.as_secs_f64())
+}
+
+fn timestamp(millis: u64) -> String {
+    format!("{:.3}", millis as f64 / 1000.0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn run(id: &str, workflow: &str, status: &str, durations: [u64; 2]) -> Run {
+        let started_at: u64 = id.parse().unwrap();
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "workflow": workflow,
+            "status": status,
+            "started_at": started_at,
+            "finished_at": started_at + durations.iter().sum::<u64>(),
+            "actions": [
+                {
+                    "name": "build",
+                    "status": "changed",
+                    "vars": {},
+                    "duration": {"secs": durations[0] / 1000, "nanos": durations[0] % 1000 * 1_000_000}
+                },
+                {
+                    "name": "push",
+                    "status": status,
+                    "vars": {},
+                    "duration": {"secs": durations[1] / 1000, "nanos": durations[1] % 1000 * 1_000_000}
+                }
+            ]
+        }))
+        .unwrap()
+    }
+
+    mod metrics {
+        use super::*;
+
+        #[test]
+        fn should_return_metrics() {
+            let history = vec![
+                run("1000", "deploy", "changed", [2000, 250]),
+                run("5000", "other", "changed", [1000, 1000]),
+                run("9000", "deploy", "failed", [4000, 50]),
+            ];
+            let current = run("20000", "deploy", "failed", [3000, 50]);
+            let expected = r#"# HELP ennio_workflow_duration_seconds Duration of the last workflow run.
+# TYPE ennio_workflow_duration_seconds gauge
+ennio_workflow_duration_seconds{workflow="deploy"} 3.050
+# HELP ennio_workflow_last_run_timestamp_seconds End time of the last workflow run.
+# TYPE ennio_workflow_last_run_timestamp_seconds gauge
+ennio_workflow_last_run_timestamp_seconds{workflow="deploy"} 23.050
+# HELP ennio_workflow_last_success_timestamp_seconds End time of the last successful workflow run.
+# TYPE ennio_workflow_last_success_timestamp_seconds gauge
+ennio_workflow_last_success_timestamp_seconds{workflow="deploy"} 3.250
+# HELP ennio_actions Number of actions of the last workflow run by status.
+# TYPE ennio_actions gauge
+ennio_actions{workflow="deploy",status="unchanged"} 0
+ennio_actions{workflow="deploy",status="changed"} 1
+ennio_actions{workflow="deploy",status="failed"} 1
+ennio_actions{workflow="deploy",status="skipped"} 0
+# HELP ennio_action_duration_seconds Duration of the actions over the recorded runs.
+# TYPE ennio_action_duration_seconds histogram
+ennio_action_duration_seconds_bucket{workflow="deploy",action="build",le="0.1"} 0
+ennio_action_duration_seconds_bucket{workflow="deploy",action="build",le="0.5"} 0
+ennio_action_duration_seconds_bucket{workflow="deploy",action="build",le="1"} 0
+ennio_action_duration_seconds_bucket{workflow="deploy",action="build",le="5"} 3
+ennio_action_duration_seconds_bucket{workflow="deploy",action="build",le="10"} 3
+ennio_action_duration_seconds_bucket{workflow="deploy",action="build",le="30"} 3
+ennio_action_duration_seconds_bucket{workflow="deploy",action="build",le="60"} 3
+ennio_action_duration_seconds_bucket{workflow="deploy",action="build",le="300"} 3
+ennio_action_duration_seconds_bucket{workflow="deploy",action="build",le="600"} 3
+ennio_action_duration_seconds_bucket{workflow="deploy",action="build",le="1800"} 3
+ennio_action_duration_seconds_bucket{workflow="deploy",action="build",le="3600"} 3
+ennio_action_duration_seconds_bucket{workflow="deploy",action="build",le="+Inf"} 3
+ennio_action_duration_seconds_sum{workflow="deploy",action="build"} 9.000
+ennio_action_duration_seconds_count{workflow="deploy",action="build"} 3
+ennio_action_duration_seconds_bucket{workflow="deploy",action="push",le="0.1"} 2
+ennio_action_duration_seconds_bucket{workflow="deploy",action="push",le="0.5"} 3
+ennio_action_duration_seconds_bucket{workflow="deploy",action="push",le="1"} 3
+ennio_action_duration_seconds_bucket{workflow="deploy",action="push",le="5"} 3
+ennio_action_duration_seconds_bucket{workflow="deploy",action="push",le="10"} 3
+ennio_action_duration_seconds_bucket{workflow="deploy",action="push",le="30"} 3
+ennio_action_duration_seconds_bucket{workflow="deploy",action="push",le="60"} 3
+ennio_action_duration_seconds_bucket{workflow="deploy",action="push",le="300"} 3
+ennio_action_duration_seconds_bucket{workflow="deploy",action="push",le="600"} 3
+ennio_action_duration_seconds_bucket{workflow="deploy",action="push",le="1800"} 3
+ennio_action_duration_seconds_bucket{workflow="deploy",action="push",le="3600"} 3
+ennio_action_duration_seconds_bucket{workflow="deploy",action="push",le="+Inf"} 3
+ennio_action_duration_seconds_sum{workflow="deploy",action="push"} 0.350
+ennio_action_duration_seconds_count{workflow="deploy",action="push"} 3
+"#;
+            assert_eq!(metrics(&current, &history), expected);
+        }
+
+        #[test]
+        fn should_not_return_last_success_if_never_succeeded() {
+            let current = run("1000", "deploy", "failed", [100, 100]);
+            let metrics = metrics(&current, std::slice::from_ref(&current));
+            assert!(!metrics.contains("ennio_workflow_last_success_timestamp_seconds"));
+            assert!(metrics.contains(
+                "ennio_action_duration_seconds_count{workflow=\"deploy\",action=\"build\"} 1"
+            ));
+        }
+    }
+
+    mod label {
+        use super::*;
+
+        #[test]
+        fn should_escape_value() {
+            assert_eq!(label("a\"b\\c\nd"), "a\\\"b\\\\c\\nd");
+        }
+    }
+}
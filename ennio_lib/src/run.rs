@@ -0,0 +1,492 @@
+use crate::{action::*, context::Outputs, var::*};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::{self, Display, Formatter},
+    fs::{self, OpenOptions},
+    io::Write,
+    path::PathBuf,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+pub static HISTORY_FILE: &str = ".ennio/history.jsonl";
+
+const SECS_PER_DAY: u64 = 86400;
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct ActionRun {
+    name: String,
+    #[serde(flatten)]
+    output: Output,
+}
+
+impl ActionRun {
+    pub fn new(name: String, output: Output) -> Self {
+        Self { name, output }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn output(&self) -> &Output {
+        &self.output
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Run {
+    id: String,
+    workflow: String,
+    status: Status,
+    started_at: u64,
+    finished_at: u64,
+    #[serde(default)]
+    vars: Hash,
+    #[serde(default)]
+    actions: Vec<ActionRun>,
+}
+
+impl Run {
+    pub fn start(workflow: String) -> Self {
+        let started_at = now();
+        Self {
+            id: started_at.to_string(),
+            workflow,
+            status: Status::Unchanged,
+            started_at,
+            finished_at: started_at,
+            vars: Hash::new(),
+            actions: vec![],
+        }
+    }
+
+    pub fn actions(&self) -> &[ActionRun] {
+        &self.actions
+    }
+
+    pub fn duration(&self) -> Duration {
+        Duration::from_millis(self.finished_at.saturating_sub(self.started_at))
+    }
+
+    pub fn finish(mut self, actions: Vec<ActionRun>) -> Self {
+        self.finished_at = now();
+        self.status = Status::aggregate(actions.iter().map(|action| action.output.status()));
+        self.actions = actions;
+        self
+    }
+
+    pub fn finished_at(&self) -> u64 {
+        self.finished_at
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn outputs(&self) -> Outputs {
+        self.actions
+            .iter()
+            .map(|action| (action.name.clone(), action.output.clone()))
+            .collect()
+    }
+
+    pub fn slowest(&self, limit: usize) -> Vec<&ActionRun> {
+        let mut actions: Vec<(&ActionRun, Duration)> = self
+            .actions
+            .iter()
+            .filter_map(|action| action.output.duration().map(|duration| (action, duration)))
+            .collect();
+        actions.sort_by(|(_, duration1), (_, duration2)| duration2.cmp(duration1));
+        actions
+            .into_iter()
+            .take(limit)
+            .map(|(action, _)| action)
+            .collect()
+    }
+
+    pub fn started_at(&self) -> u64 {
+        self.started_at
+    }
+
+    pub fn status(&self) -> Status {
+        self.status
+    }
+
+    pub fn vars(&self) -> &Hash {
+        &self.vars
+    }
+
+    pub fn with_vars(mut self, vars: Hash) -> Self {
+        self.vars = vars;
+        self
+    }
+
+    pub fn workflow(&self) -> &str {
+        &self.workflow
+    }
+}
+
+pub struct RunStore {
+    path: PathBuf,
+}
+
+impl RunStore {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    pub fn last(&self, workflow_name: &str) -> Result<Option<Run>, RunError> {
+        let runs = self.list()?;
+        Ok(runs
+            .into_iter()
+            .rev()
+            .find(|run| run.workflow == workflow_name))
+    }
+
+    pub fn list(&self) -> Result<Vec<Run>, RunError> {
+        if !self.path.is_file() {
+            return Ok(vec![]);
+        }
+        let content = fs::read_to_string(&self.path)
+            .map_err(|err| RunError::Reading(self.path.clone(), err.to_string()))?;
+        content
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                serde_json::from_str(line)
+                    .map_err(|err| RunError::Parsing(self.path.clone(), err.to_string()))
+            })
+            .collect()
+    }
+
+    pub fn load(&self, id: &str) -> Result<Run, RunError> {
+        self.list()?
+            .into_iter()
+            .find(|run| run.id == id)
+            .ok_or_else(|| RunError::UnknownRun(id.into()))
+    }
+
+    pub fn save(&self, run: &Run) -> Result<(), RunError> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)
+                .map_err(|err| RunError::Writing(dir.into(), err.to_string()))?;
+        }
+        let line = serde_json::to_string(run)
+            .map_err(|err| RunError::Writing(self.path.clone(), err.to_string()))?;
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut file| writeln!(file, "{}", line))
+            .map_err(|err| RunError::Writing(self.path.clone(), err.to_string()))
+    }
+}
+
+#[derive(Debug)]
+pub enum RunError {
+    Reading(PathBuf, String),
+    Writing(PathBuf, String),
+    Parsing(PathBuf, String),
+    UnknownRun(String),
+}
+
+impl Display for RunError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Self::Reading(path, err) => write!(f, "Unable to read {}: {}", path.display(), err),
+            Self::Writing(path, err) => write!(f, "Unable to write {}: {}", path.display(), err),
+            Self::Parsing(path, err) => write!(f, "Unable to parse {}: {}", path.display(), err),
+            Self::UnknownRun(id) => write!(f, "Run '{}' does not exist", id),
+        }
+    }
+}
+
+pub fn days(days: u64) -> Duration {
+    Duration::from_secs(days * SECS_PER_DAY)
+}
+
+pub fn format_duration(duration: Duration) -> String {
+    format!("{:.3}s", duration.as_secs_f64())
+}
+
+pub fn format_timestamp(millis: u64) -> String {
+    let secs = millis / 1000;
+    let days = (secs / SECS_PER_DAY) as i64;
+    let time = secs % SECS_PER_DAY;
+    let shifted_days = days + 719468;
+    let era = shifted_days.div_euclid(146097);
+    let day_of_era = shifted_days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_idx = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_idx + 2) / 5 + 1;
+    let month = if month_idx < 10 {
+        month_idx + 3
+    } else {
+        month_idx - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
+
+pub(crate) fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::*;
+    use tempfile::TempDir;
+
+    fn run(id: &str, workflow: &str) -> Run {
+        Run {
+            id: id.into(),
+            workflow: workflow.into(),
+            status: Status::Changed,
+            started_at: 1000,
+            finished_at: 2500,
+            vars: hash!("foo", "bar"),
+            actions: vec![ActionRun::new(
+                String::from("action1"),
+                Output::new(Status::Changed).add_var("stdout", "foo".into()),
+            )],
+        }
+    }
+
+    mod run {
+        use super::*;
+
+        mod duration {
+            use super::*;
+
+            #[test]
+            fn should_return_duration() {
+                let run = run("1", "workflow1");
+                assert_eq!(run.duration(), Duration::from_millis(1500));
+            }
+        }
+
+        mod finish {
+            use super::*;
+
+            #[test]
+            fn should_set_actions_and_status() {
+                let actions = vec![
+                    ActionRun::new(String::from("action1"), Output::new(Status::Changed)),
+                    ActionRun::new(String::from("action2"), Output::new(Status::Failed)),
+                ];
+                let run = Run::start(String::from("workflow1")).finish(actions.clone());
+                assert_eq!(run.id(), run.started_at().to_string());
+                assert!(run.finished_at() >= run.started_at());
+                assert_eq!(run.status(), Status::Failed);
+                assert_eq!(run.actions(), actions);
+            }
+        }
+
+        mod slowest {
+            use super::*;
+
+            #[test]
+            fn should_return_slowest_timed_actions() {
+                let action = |name: &str, millis: Option<u64>| {
+                    let output = Output::new(Status::Changed);
+                    let output = match millis {
+                        Some(millis) => {
+                            output.with_timing(0, millis, Duration::from_millis(millis))
+                        }
+                        None => output,
+                    };
+                    ActionRun::new(name.into(), output)
+                };
+                let run = Run::start(String::from("workflow1")).finish(vec![
+                    action("action1", Some(100)),
+                    action("action2", None),
+                    action("action3", Some(300)),
+                    action("action4", Some(200)),
+                ]);
+                let names: Vec<&str> = run.slowest(2).into_iter().map(ActionRun::name).collect();
+                assert_eq!(names, vec!["action3", "action4"]);
+            }
+        }
+
+        mod outputs {
+            use super::*;
+
+            #[test]
+            fn should_return_outputs() {
+                let run = run("1", "workflow1");
+                let expected = Outputs::from([(
+                    String::from("action1"),
+                    Output::new(Status::Changed).add_var("stdout", "foo".into()),
+                )]);
+                assert_eq!(run.outputs(), expected);
+            }
+        }
+    }
+
+    mod run_store {
+        use super::*;
+
+        mod last {
+            use super::*;
+
+            #[test]
+            fn should_return_none_if_no_file() {
+                let dir = TempDir::new().unwrap();
+                let store = RunStore::new(dir.path().join(HISTORY_FILE));
+                assert!(store.last("workflow1").unwrap().is_none());
+            }
+
+            #[test]
+            fn should_return_last_run_of_workflow() {
+                let dir = TempDir::new().unwrap();
+                let store = RunStore::new(dir.path().join(HISTORY_FILE));
+                store.save(&run("1", "workflow1")).unwrap();
+                store.save(&run("2", "workflow1")).unwrap();
+                store.save(&run("3", "workflow2")).unwrap();
+                let run = store.last("workflow1").unwrap().unwrap();
+                assert_eq!(run.id(), "2");
+            }
+        }
+
+        mod list {
+            use super::*;
+
+            #[test]
+            fn should_return_parsing_err() {
+                let dir = TempDir::new().unwrap();
+                let path = dir.path().join("history.jsonl");
+                fs::write(&path, "foo\n").unwrap();
+                let store = RunStore::new(path);
+                match store.list() {
+                    Ok(_) => panic!("should fail"),
+                    Err(RunError::Parsing(_, _)) => {}
+                    Err(err) => panic!("{}", err),
+                }
+            }
+
+            #[test]
+            fn should_return_runs_in_order() {
+                let dir = TempDir::new().unwrap();
+                let store = RunStore::new(dir.path().join(HISTORY_FILE));
+                let expected = vec![run("1", "workflow1"), run("2", "workflow2")];
+                for run in expected.iter() {
+                    store.save(run).unwrap();
+                }
+                assert_eq!(store.list().unwrap(), expected);
+            }
+        }
+
+        mod load {
+            use super::*;
+
+            #[test]
+            fn should_return_unknown_run_err() {
+                let dir = TempDir::new().unwrap();
+                let store = RunStore::new(dir.path().join(HISTORY_FILE));
+                match store.load("1") {
+                    Ok(_) => panic!("should fail"),
+                    Err(RunError::UnknownRun(id)) => assert_eq!(id, "1"),
+                    Err(err) => panic!("{}", err),
+                }
+            }
+
+            #[test]
+            fn should_return_saved_run() {
+                let dir = TempDir::new().unwrap();
+                let store = RunStore::new(dir.path().join(HISTORY_FILE));
+                let expected = run("1", "workflow1");
+                store.save(&expected).unwrap();
+                assert_eq!(store.load("1").unwrap(), expected);
+            }
+        }
+    }
+
+    mod run_error {
+        use super::*;
+
+        mod display {
+            use super::*;
+
+            macro_rules! test {
+                ($name:ident, $value:expr, $expected:expr) => {
+                    #[test]
+                    fn $name() {
+                        let err = $value(PathBuf::from("history.jsonl"), String::from("error"));
+                        assert_eq!(err.to_string(), $expected);
+                    }
+                };
+            }
+
+            test!(
+                reading,
+                RunError::Reading,
+                "Unable to read history.jsonl: error"
+            );
+            test!(
+                writing,
+                RunError::Writing,
+                "Unable to write history.jsonl: error"
+            );
+            test!(
+                parsing,
+                RunError::Parsing,
+                "Unable to parse history.jsonl: error"
+            );
+
+            #[test]
+            fn unknown_run() {
+                let err = RunError::UnknownRun(String::from("1"));
+                assert_eq!(err.to_string(), "Run '1' does not exist");
+            }
+        }
+    }
+
+    mod days {
+        use super::*;
+
+        #[test]
+        fn should_return_whole_days() {
+            assert_eq!(days(2), Duration::from_secs(172800));
+        }
+    }
+
+    mod format_duration {
+        use super::*;
+
+        #[test]
+        fn should_return_seconds() {
+            assert_eq!(format_duration(Duration::from_millis(1234)), "1.234s");
+        }
+    }
+
+    mod format_timestamp {
+        use super::*;
+
+        macro_rules! test {
+            ($name:ident, $millis:expr, $expected:expr) => {
+                #[test]
+                fn $name() {
+                    assert_eq!(format_timestamp($millis), $expected);
+                }
+            };
+        }
+
+        test!(epoch, 0, "1970-01-01T00:00:00Z");
+        test!(leap_day, 951782400000, "2000-02-29T00:00:00Z");
+        test!(end_of_year, 1704067199999, "2023-12-31T23:59:59Z");
+    }
+}
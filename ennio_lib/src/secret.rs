@@ -0,0 +1,318 @@
+use crate::var::*;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use chacha20poly1305::{
+    aead::{Aead, AeadCore, KeyInit, OsRng},
+    XChaCha20Poly1305, XNonce,
+};
+use serde::Deserialize;
+use std::{
+    env,
+    fmt::{self, Display, Formatter},
+    fs,
+    path::{Path, PathBuf},
+};
+
+pub static DEFAULT_KEY_FILE: &str = ".ennio/secret.key";
+
+const NONCE_LEN: usize = 24;
+const MIN_MASKED_LEN: usize = 6;
+
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SecretSource {
+    Env(String),
+    File(String),
+    EncryptedFile {
+        path: String,
+        #[serde(default)]
+        key_file: Option<String>,
+    },
+}
+
+impl SecretSource {
+    pub fn load(&self, base_dir: &Path) -> Result<Value, SecretError> {
+        let plaintext = match self {
+            Self::Env(var_name) => {
+                env::var(var_name).map_err(|_| SecretError::UndefinedEnvVar(var_name.clone()))?
+            }
+            Self::File(path) => {
+                let content = read(&base_dir.join(path))?;
+                content.trim_end_matches('\n').into()
+            }
+            Self::EncryptedFile { path, key_file } => {
+                let key_file = match key_file {
+                    Some(key_file) => base_dir.join(key_file),
+                    None => default_key_file()?,
+                };
+                let key = read(&key_file)?;
+                let data = read(&base_dir.join(path))?;
+                decrypt(&key, &data)?
+            }
+        };
+        Ok(Value::Secret(Secret::new(plaintext)))
+    }
+}
+
+#[derive(Debug)]
+pub enum SecretError {
+    UndefinedEnvVar(String),
+    Reading(PathBuf, String),
+    InvalidKey,
+    Decoding(String),
+    Encrypting,
+    Decrypting,
+    KeyDerivation(String),
+    InvalidVault,
+}
+
+impl Display for SecretError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Self::UndefinedEnvVar(var_name) => {
+                write!(f, "Environment variable '{}' is not defined", var_name)
+            }
+            Self::Reading(path, err) => write!(f, "Unable to read {}: {}", path.display(), err),
+            Self::InvalidKey => write!(f, "Key must be 32 bytes encoded in base64"),
+            Self::Decoding(err) => write!(f, "{}", err),
+            Self::Encrypting => write!(f, "Unable to encrypt data"),
+            Self::Decrypting => write!(f, "Unable to decrypt data"),
+            Self::KeyDerivation(err) => write!(f, "Unable to derive key: {}", err),
+            Self::InvalidVault => write!(f, "File is not a valid vault"),
+        }
+    }
+}
+
+pub fn decrypt(key: &str, data: &str) -> Result<String, SecretError> {
+    let cipher = cipher(key)?;
+    let data = BASE64
+        .decode(data.trim())
+        .map_err(|err| SecretError::Decoding(err.to_string()))?;
+    open(&cipher, &data)
+}
+
+pub fn encrypt(key: &str, plaintext: &str) -> Result<String, SecretError> {
+    let cipher = cipher(key)?;
+    let data = seal(&cipher, plaintext)?;
+    Ok(BASE64.encode(data))
+}
+
+pub fn generate_key() -> String {
+    BASE64.encode(XChaCha20Poly1305::generate_key(&mut OsRng))
+}
+
+pub fn mask(text: &str, secrets: &[&str]) -> String {
+    let mut secrets: Vec<&str> = secrets
+        .iter()
+        .copied()
+        .filter(|secret| is_masked(secret))
+        .collect();
+    secrets.sort_by_key(|secret| std::cmp::Reverse(secret.len()));
+    secrets
+        .into_iter()
+        .fold(text.into(), |text: String, secret| {
+            text.replace(secret, SECRET_MASK)
+        })
+}
+
+pub(crate) fn is_masked(secret: &str) -> bool {
+    secret.chars().count() >= MIN_MASKED_LEN
+}
+
+pub(crate) fn open(cipher: &XChaCha20Poly1305, data: &[u8]) -> Result<String, SecretError> {
+    if data.len() < NONCE_LEN {
+        return Err(SecretError::Decrypting);
+    }
+    let (nonce, ciphertext) = data.split_at(NONCE_LEN);
+    let plaintext = cipher
+        .decrypt(XNonce::from_slice(nonce), ciphertext)
+        .map_err(|_| SecretError::Decrypting)?;
+    String::from_utf8(plaintext).map_err(|err| SecretError::Decoding(err.to_string()))
+}
+
+pub(crate) fn read(path: &Path) -> Result<String, SecretError> {
+    fs::read_to_string(path).map_err(|err| SecretError::Reading(path.into(), err.to_string()))
+}
+
+pub(crate) fn seal(cipher: &XChaCha20Poly1305, plaintext: &str) -> Result<Vec<u8>, SecretError> {
+    let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, plaintext.as_bytes())
+        .map_err(|_| SecretError::Encrypting)?;
+    let mut data = nonce.to_vec();
+    data.extend(ciphertext);
+    Ok(data)
+}
+
+fn cipher(key: &str) -> Result<XChaCha20Poly1305, SecretError> {
+    let key = BASE64
+        .decode(key.trim())
+        .map_err(|_| SecretError::InvalidKey)?;
+    XChaCha20Poly1305::new_from_slice(&key).map_err(|_| SecretError::InvalidKey)
+}
+
+pub fn default_key_file() -> Result<PathBuf, SecretError> {
+    env::var("HOME")
+        .map(|home| Path::new(&home).join(DEFAULT_KEY_FILE))
+        .map_err(|_| SecretError::UndefinedEnvVar(String::from("HOME")))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    mod secret_source {
+        use super::*;
+
+        mod load {
+            use super::*;
+
+            #[test]
+            fn should_return_undefined_env_var() {
+                let var_name = "ENNIO_UNDEFINED_SECRET";
+                let src = SecretSource::Env(var_name.into());
+                match src.load(Path::new("./test")) {
+                    Ok(_) => panic!("should fail"),
+                    Err(SecretError::UndefinedEnvVar(name)) => assert_eq!(name, var_name),
+                    Err(err) => panic!("{}", err),
+                }
+            }
+
+            #[test]
+            fn should_return_env_secret() {
+                let src = SecretSource::Env(String::from("PATH"));
+                let val = src.load(Path::new("./test")).unwrap();
+                let expected = Value::Secret(Secret::new(env::var("PATH").unwrap()));
+                assert_eq!(val, expected);
+            }
+
+            #[test]
+            fn should_return_reading_err() {
+                let src = SecretSource::File(String::from("null"));
+                match src.load(Path::new("./test")) {
+                    Ok(_) => panic!("should fail"),
+                    Err(SecretError::Reading(path, _)) => {
+                        assert_eq!(path, Path::new("./test/null"))
+                    }
+                    Err(err) => panic!("{}", err),
+                }
+            }
+
+            #[test]
+            fn should_return_file_secret() {
+                let src = SecretSource::File(String::from("secret.txt"));
+                let val = src.load(Path::new("./test")).unwrap();
+                let expected = Value::Secret(Secret::new(String::from("password")));
+                assert_eq!(val, expected);
+            }
+
+            #[test]
+            fn should_return_encrypted_file_secret() {
+                let src = SecretSource::EncryptedFile {
+                    path: String::from("secret.enc"),
+                    key_file: Some(String::from("secret.key")),
+                };
+                let val = src.load(Path::new("./test")).unwrap();
+                let expected = Value::Secret(Secret::new(String::from("password")));
+                assert_eq!(val, expected);
+            }
+        }
+    }
+
+    mod secret_error {
+        use super::*;
+
+        mod display {
+            use super::*;
+
+            macro_rules! test {
+                ($name:ident, $value:expr, $expected:expr) => {
+                    #[test]
+                    fn $name() {
+                        assert_eq!(format!("{}", $value), $expected);
+                    }
+                };
+            }
+
+            test!(
+                undefined_env_var,
+                SecretError::UndefinedEnvVar(String::from("TOKEN")),
+                "Environment variable 'TOKEN' is not defined"
+            );
+            test!(
+                reading,
+                SecretError::Reading(PathBuf::from("secret.txt"), String::from("error")),
+                "Unable to read secret.txt: error"
+            );
+            test!(
+                invalid_key,
+                SecretError::InvalidKey,
+                "Key must be 32 bytes encoded in base64"
+            );
+            test!(
+                decoding,
+                SecretError::Decoding(String::from("error")),
+                "error"
+            );
+            test!(
+                encrypting,
+                SecretError::Encrypting,
+                "Unable to encrypt data"
+            );
+            test!(
+                decrypting,
+                SecretError::Decrypting,
+                "Unable to decrypt data"
+            );
+        }
+    }
+
+    mod decrypt {
+        use super::*;
+
+        #[test]
+        fn should_return_invalid_key() {
+            match decrypt("foo", "bar") {
+                Ok(_) => panic!("should fail"),
+                Err(SecretError::InvalidKey) => {}
+                Err(err) => panic!("{}", err),
+            }
+        }
+
+        #[test]
+        fn should_return_decrypting_err_if_wrong_key() {
+            let data = encrypt(&generate_key(), "password").unwrap();
+            match decrypt(&generate_key(), &data) {
+                Ok(_) => panic!("should fail"),
+                Err(SecretError::Decrypting) => {}
+                Err(err) => panic!("{}", err),
+            }
+        }
+
+        #[test]
+        fn should_return_plaintext() {
+            let expected = "password";
+            let key = generate_key();
+            let data = encrypt(&key, expected).unwrap();
+            let plaintext = decrypt(&key, &data).unwrap();
+            assert_eq!(plaintext, expected);
+        }
+    }
+
+    mod mask {
+        use super::*;
+
+        #[test]
+        fn should_mask_secrets() {
+            let text = "user=admin password=password123 empty=";
+            let masked = mask(text, &["password", "password123", ""]);
+            assert_eq!(masked, "user=admin ***=*** empty=");
+        }
+
+        #[test]
+        fn should_not_mask_short_secrets() {
+            let text = "retries=1 verbose=false token=abcdef";
+            let masked = mask(text, &["1", "false", "abcdef"]);
+            assert_eq!(masked, "retries=1 verbose=false token=***");
+        }
+    }
+}
@@ -0,0 +1,286 @@
+use crate::{context::*, var::*};
+use regex::{Captures, Regex};
+
+pub static EXPRESSION_PATTERN: &str =
+    r"\{\{\s*([^{}|\s]+)(?:\s*\|\s*default\(\s*([^)]*?)\s*\))?\s*\}\}";
+
+#[derive(Debug, PartialEq)]
+pub struct Expression<'a> {
+    var_name: &'a str,
+    default: Option<&'a str>,
+}
+
+impl<'a> Expression<'a> {
+    pub fn default(&self) -> Option<Value> {
+        self.default
+            .map(|default| serde_yaml::from_str(default).unwrap_or_else(|_| Value::from(default)))
+    }
+
+    pub fn eval(&self, ctx: &Context) -> Result<Value, VarError> {
+        match ctx.value(self.var_name) {
+            Ok(val) => Ok(val.clone()),
+            Err(VarError::InvalidSyntax(var_name)) => Err(VarError::InvalidSyntax(var_name)),
+            Err(err) => self.default().ok_or(err),
+        }
+    }
+
+    pub fn var_name(&self) -> &'a str {
+        self.var_name
+    }
+
+    fn from_captures(caps: &Captures<'a>) -> Self {
+        Self {
+            var_name: caps.get(1).map(|mat| mat.as_str()).unwrap_or_default(),
+            default: caps.get(2).map(|mat| mat.as_str()),
+        }
+    }
+}
+
+pub fn expressions(template: &str) -> Vec<Expression<'_>> {
+    let re = Regex::new(EXPRESSION_PATTERN).unwrap();
+    re.captures_iter(template)
+        .map(|caps| Expression::from_captures(&caps))
+        .collect()
+}
+
+pub fn render(template: &str, ctx: &Context) -> Result<String, VarError> {
+    let re = Regex::new(EXPRESSION_PATTERN).unwrap();
+    let mut err = None;
+    let rendered = re.replace_all(
+        template,
+        |caps: &Captures| match Expression::from_captures(caps).eval(ctx) {
+            Ok(val) => val.expose(),
+            Err(var_err) => {
+                err.get_or_insert(var_err);
+                String::new()
+            }
+        },
+    );
+    match err {
+        Some(err) => Err(err),
+        None => Ok(rendered.into_owned()),
+    }
+}
+
+pub fn render_value(val: &Value, ctx: &Context) -> Result<Value, VarError> {
+    match val {
+        Value::String(template) => {
+            let exprs = expressions(template);
+            match exprs.first() {
+                Some(expr) if exprs.len() == 1 && is_single_expression(template) => expr.eval(ctx),
+                _ => render(template, ctx).map(Value::String),
+            }
+        }
+        Value::Array(array) => array
+            .items()
+            .iter()
+            .map(|item| render_value(item, ctx))
+            .collect::<Result<Vec<Value>, VarError>>()
+            .map(|items| Value::from(Array::from(items))),
+        Value::Hash(hash) => hash
+            .iter()
+            .map(|(key, val)| render_value(val, ctx).map(|val| (key.clone(), val)))
+            .collect::<Result<Hash, VarError>>()
+            .map(Value::Hash),
+        _ => Ok(val.clone()),
+    }
+}
+
+fn is_single_expression(template: &str) -> bool {
+    let re = Regex::new(&format!("^{}$", EXPRESSION_PATTERN)).unwrap();
+    re.is_match(template.trim())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::*;
+
+    mod expression {
+        use super::*;
+
+        mod default {
+            use super::*;
+
+            #[test]
+            fn should_return_none() {
+                let expr = Expression {
+                    var_name: "env.HOME",
+                    default: None,
+                };
+                assert!(expr.default().is_none());
+            }
+
+            #[test]
+            fn should_return_parsed_value() {
+                let expr = Expression {
+                    var_name: "env.HOME",
+                    default: Some("1"),
+                };
+                assert_eq!(expr.default(), Some(Value::PositiveInt(1)));
+            }
+
+            #[test]
+            fn should_return_quoted_string() {
+                let expr = Expression {
+                    var_name: "env.HOME",
+                    default: Some("'/root'"),
+                };
+                assert_eq!(expr.default(), Some(Value::from("/root")));
+            }
+        }
+
+        mod eval {
+            use super::*;
+
+            #[test]
+            fn should_return_err() {
+                let ctx = Context::new("workflow1");
+                let expr = Expression {
+                    var_name: "env.HOME",
+                    default: None,
+                };
+                match expr.eval(&ctx) {
+                    Ok(_) => panic!("should fail"),
+                    Err(VarError::UndefinedEnvVar(var_name)) => assert_eq!(var_name, "HOME"),
+                    Err(err) => panic!("{}", err),
+                }
+            }
+
+            #[test]
+            fn should_return_default() {
+                let ctx = Context::new("workflow1");
+                let expr = Expression {
+                    var_name: "env.HOME",
+                    default: Some("/root"),
+                };
+                assert_eq!(expr.eval(&ctx).unwrap(), Value::from("/root"));
+            }
+
+            #[test]
+            fn should_return_value() {
+                let expected = Value::from("/home/ennio");
+                let ctx = Context::new("workflow1").with_env(hash!("HOME", expected.clone()));
+                let expr = Expression {
+                    var_name: "env.HOME",
+                    default: Some("/root"),
+                };
+                assert_eq!(expr.eval(&ctx).unwrap(), expected);
+            }
+        }
+
+        mod var_name {
+            use super::*;
+
+            #[test]
+            fn should_return_var_name() {
+                let expected = "env.HOME";
+                let expr = Expression {
+                    var_name: expected,
+                    default: None,
+                };
+                assert_eq!(expr.var_name(), expected);
+            }
+        }
+    }
+
+    mod expressions {
+        use super::*;
+
+        #[test]
+        fn should_return_expressions() {
+            let expected = vec![
+                Expression {
+                    var_name: "vars.foo",
+                    default: None,
+                },
+                Expression {
+                    var_name: "env.CI",
+                    default: Some("false"),
+                },
+                Expression {
+                    var_name: "env.HOME",
+                    default: Some("\"/root\""),
+                },
+            ];
+            let exprs = expressions(
+                "echo {{ vars.foo }} {{env.CI|default(false)}} {{ env.HOME | default( \"/root\" ) }} {bar}",
+            );
+            assert_eq!(exprs, expected);
+        }
+    }
+
+    mod render {
+        use super::*;
+
+        #[test]
+        fn should_return_err() {
+            let ctx = Context::new("workflow1");
+            match render("echo {{ vars.foo }}", &ctx) {
+                Ok(_) => panic!("should fail"),
+                Err(VarError::UndefinedVar(var_name)) => assert_eq!(var_name, "foo"),
+                Err(err) => panic!("{}", err),
+            }
+        }
+
+        #[test]
+        fn should_return_rendered_template() {
+            let ctx = Context::new("workflow1")
+                .with_vars(hash!("foo", "bar", "baz", 1u8))
+                .with_env(hash!("HOME", "/root"))
+                .with_secrets(hash!(
+                    "token",
+                    Value::Secret(Secret::new(String::from("password")))
+                ));
+            let rendered = render(
+                "echo {{ vars.foo }} {{vars.baz}} {{ env.HOME }} {{ env.CI | default(false) }} {{ secrets.token }}",
+                &ctx,
+            )
+            .unwrap();
+            assert_eq!(rendered, "echo bar 1 /root false password");
+        }
+    }
+
+    mod render_value {
+        use super::*;
+
+        #[test]
+        fn should_return_err() {
+            let ctx = Context::new("workflow1");
+            match render_value(&Value::from("{{ vars.foo }}"), &ctx) {
+                Ok(_) => panic!("should fail"),
+                Err(VarError::UndefinedVar(var_name)) => assert_eq!(var_name, "foo"),
+                Err(err) => panic!("{}", err),
+            }
+        }
+
+        #[test]
+        fn should_return_rendered_value() {
+            let secret = Value::Secret(Secret::new(String::from("password")));
+            let ctx = Context::new("workflow1")
+                .with_vars(hash!("foo", "bar", "port", 5432u16))
+                .with_secrets(hash!("token", secret.clone()));
+            let val = Value::from(hash!(
+                "port",
+                "{{ vars.port }}",
+                "url",
+                "http://{{ vars.foo }}:{{ vars.port }}",
+                "token",
+                " {{ secrets.token }} ",
+                "hosts",
+                array!("{{ vars.foo }}", "baz")
+            ));
+            let expected = Value::from(hash!(
+                "port",
+                5432u16,
+                "url",
+                "http://bar:5432",
+                "token",
+                secret,
+                "hosts",
+                array!("bar", "baz")
+            ));
+            assert_eq!(render_value(&val, &ctx).unwrap(), expected);
+        }
+    }
+}
@@ -0,0 +1,669 @@
+use crate::{action::*, event::*, var::*};
+use blake2::{Blake2s256, Digest};
+use log::{debug, warn};
+use serde_json::json;
+use std::{
+    cell::RefCell,
+    fmt::{self, Display, Formatter},
+    fs::{self, OpenOptions},
+    io::Write,
+    path::PathBuf,
+    process,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+pub static SERVICE_NAME: &str = "ennio";
+
+const NANOS_PER_MILLI: u64 = 1_000_000;
+const SPAN_KIND_INTERNAL: u8 = 1;
+const STATUS_CODE_OK: u8 = 1;
+const STATUS_CODE_ERROR: u8 = 2;
+const TRACES_PATH: &str = "/v1/traces";
+const EXPORT_TIMEOUT: Duration = Duration::from_secs(10);
+
+static ID_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+pub trait Exporter {
+    fn export(&self, request: &serde_json::Value) -> Result<(), TraceError>;
+}
+
+pub struct FileExporter {
+    path: PathBuf,
+}
+
+impl FileExporter {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+}
+
+impl Exporter for FileExporter {
+    fn export(&self, request: &serde_json::Value) -> Result<(), TraceError> {
+        if let Some(dir) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)
+                .map_err(|err| TraceError::Writing(dir.into(), err.to_string()))?;
+        }
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut file| writeln!(file, "{}", request))
+            .map_err(|err| TraceError::Writing(self.path.clone(), err.to_string()))
+    }
+}
+
+pub struct OtlpExporter {
+    endpoint: String,
+    agent: ureq::Agent,
+}
+
+impl OtlpExporter {
+    pub fn new(endpoint: String) -> Self {
+        Self {
+            endpoint: traces_url(endpoint),
+            agent: agent(EXPORT_TIMEOUT),
+        }
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.agent = agent(timeout);
+        self
+    }
+}
+
+impl Exporter for OtlpExporter {
+    fn export(&self, request: &serde_json::Value) -> Result<(), TraceError> {
+        debug!("Sending traces to {}", self.endpoint);
+        self.agent
+            .post(&self.endpoint)
+            .set("Content-Type", "application/json")
+            .send_string(&request.to_string())
+            .map(|_| ())
+            .map_err(|err| TraceError::Sending(self.endpoint.clone(), err.to_string()))
+    }
+}
+
+fn agent(timeout: Duration) -> ureq::Agent {
+    ureq::AgentBuilder::new().timeout(timeout).build()
+}
+
+fn traces_url(endpoint: String) -> String {
+    let authority_and_path = endpoint
+        .split_once("://")
+        .map_or(endpoint.as_str(), |(_, rest)| rest);
+    match authority_and_path.find('/') {
+        Some(idx) if idx + 1 < authority_and_path.len() => endpoint,
+        _ => format!("{}{}", endpoint.trim_end_matches('/'), TRACES_PATH),
+    }
+}
+
+#[derive(Debug)]
+pub enum TraceError {
+    Writing(PathBuf, String),
+    Sending(String, String),
+}
+
+impl Display for TraceError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Self::Writing(path, err) => write!(f, "Unable to write {}: {}", path.display(), err),
+            Self::Sending(endpoint, err) => {
+                write!(f, "Unable to send traces to {}: {}", endpoint, err)
+            }
+        }
+    }
+}
+
+pub struct TraceObserver<E: Exporter> {
+    exporter: E,
+    trace: RefCell<Trace>,
+    errors: RefCell<Vec<TraceError>>,
+}
+
+impl<E: Exporter> TraceObserver<E> {
+    pub fn new(exporter: E) -> Self {
+        Self {
+            exporter,
+            trace: RefCell::new(Trace::default()),
+            errors: RefCell::new(vec![]),
+        }
+    }
+
+    pub fn take_errors(&self) -> Vec<TraceError> {
+        self.errors.take()
+    }
+
+    fn start(&self, name: &str, attribute: (&str, &str), timestamp: u64) {
+        let mut trace = self.trace.borrow_mut();
+        if trace.stack.is_empty() {
+            trace.id = new_id(16);
+        }
+        let span = Span {
+            id: new_id(8),
+            parent_id: trace.stack.last().map(|parent| parent.id.clone()),
+            name: name.into(),
+            start: timestamp,
+            end: timestamp,
+            attributes: vec![(attribute.0.into(), json!({ "stringValue": attribute.1 }))],
+            status: Status::Unchanged,
+        };
+        trace.stack.push(span);
+    }
+
+    fn finish(&self, status: Status, output: Option<&Output>, timestamp: u64) {
+        let mut trace = self.trace.borrow_mut();
+        let Some(mut span) = trace.stack.pop() else {
+            warn!("Unable to trace event: no span started");
+            return;
+        };
+        span.end = timestamp;
+        span.status = status;
+        span.attributes.push((
+            String::from("ennio.status"),
+            json!({ "stringValue": status.to_string() }),
+        ));
+        let code = output
+            .and_then(|output| output.value("rc"))
+            .and_then(|code| match code {
+                Value::PositiveInt(_) | Value::NegativeInt(_) => Some(code.to_string()),
+                _ => None,
+            });
+        if let Some(code) = code {
+            span.attributes.push((
+                String::from("process.exit_code"),
+                json!({ "intValue": code }),
+            ));
+        }
+        trace.spans.push(span);
+        if trace.stack.is_empty() {
+            let request = request(&trace.id, &trace.spans);
+            trace.spans.clear();
+            drop(trace);
+            if let Err(err) = self.exporter.export(&request) {
+                warn!("Unable to export trace: {}", err);
+                self.errors.borrow_mut().push(err);
+            }
+        }
+    }
+}
+
+impl<E: Exporter> Observer for TraceObserver<E> {
+    fn notify(&self, event: &Event) {
+        match event.kind() {
+            EventKind::WorkflowStarted => self.start(
+                event.workflow(),
+                ("ennio.workflow", event.workflow()),
+                event.timestamp(),
+            ),
+            EventKind::ActionStarted { action } => {
+                self.start(action, ("ennio.action", action), event.timestamp())
+            }
+            EventKind::OutputChunk { .. } => {}
+            EventKind::ActionFinished { output, .. } => {
+                self.finish(output.status(), Some(output), event.timestamp())
+            }
+            EventKind::WorkflowFinished { status } => self.finish(*status, None, event.timestamp()),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Trace {
+    id: String,
+    stack: Vec<Span>,
+    spans: Vec<Span>,
+}
+
+struct Span {
+    id: String,
+    parent_id: Option<String>,
+    name: String,
+    start: u64,
+    end: u64,
+    attributes: Vec<(String, serde_json::Value)>,
+    status: Status,
+}
+
+impl Span {
+    fn to_json(&self, trace_id: &str) -> serde_json::Value {
+        let attributes: Vec<serde_json::Value> = self
+            .attributes
+            .iter()
+            .map(|(key, value)| json!({ "key": key, "value": value }))
+            .collect();
+        let status = match self.status {
+            Status::Failed => json!({
+                "code": STATUS_CODE_ERROR,
+                "message": format!("{} failed", self.name),
+            }),
+            _ => json!({ "code": STATUS_CODE_OK }),
+        };
+        json!({
+            "traceId": trace_id,
+            "spanId": self.id,
+            "parentSpanId": self.parent_id.clone().unwrap_or_default(),
+            "name": self.name,
+            "kind": SPAN_KIND_INTERNAL,
+            "startTimeUnixNano": (self.start * NANOS_PER_MILLI).to_string(),
+            "endTimeUnixNano": (self.end * NANOS_PER_MILLI).to_string(),
+            "attributes": attributes,
+            "status": status,
+        })
+    }
+}
+
+fn request(trace_id: &str, spans: &[Span]) -> serde_json::Value {
+    let spans: Vec<serde_json::Value> = spans.iter().map(|span| span.to_json(trace_id)).collect();
+    json!({
+        "resourceSpans": [{
+            "resource": {
+                "attributes": [{
+                    "key": "service.name",
+                    "value": { "stringValue": SERVICE_NAME },
+                }],
+            },
+            "scopeSpans": [{
+                "scope": {
+                    "name": SERVICE_NAME,
+                    "version": env!("CARGO_PKG_VERSION"),
+                },
+                "spans": spans,
+            }],
+        }],
+    })
+}
+
+fn new_id(len: usize) -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_nanos())
+        .unwrap_or_default();
+    let mut hasher = Blake2s256::new();
+    hasher.update(process::id().to_le_bytes());
+    hasher.update(nanos.to_le_bytes());
+    hasher.update(ID_COUNTER.fetch_add(1, Ordering::Relaxed).to_le_bytes());
+    hasher.finalize()[..len]
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::{
+        io::{BufRead, BufReader, Read},
+        net::TcpListener,
+        thread,
+        time::Instant,
+    };
+    use tempfile::TempDir;
+
+    #[derive(Default)]
+    struct ExporterStub(RefCell<Vec<serde_json::Value>>);
+
+    impl Exporter for ExporterStub {
+        fn export(&self, request: &serde_json::Value) -> Result<(), TraceError> {
+            self.0.borrow_mut().push(request.clone());
+            Ok(())
+        }
+    }
+
+    fn event(workflow: &str, kind: EventKind) -> Event {
+        Event::new(workflow.into(), kind)
+    }
+
+    fn action_events(workflow: &str, action: &str, output: Output) -> Vec<Event> {
+        vec![
+            event(
+                workflow,
+                EventKind::ActionStarted {
+                    action: action.into(),
+                },
+            ),
+            event(
+                workflow,
+                EventKind::ActionFinished {
+                    action: action.into(),
+                    output,
+                },
+            ),
+        ]
+    }
+
+    fn spans(request: &serde_json::Value) -> &Vec<serde_json::Value> {
+        request["resourceSpans"][0]["scopeSpans"][0]["spans"]
+            .as_array()
+            .unwrap()
+    }
+
+    fn attribute<'a>(span: &'a serde_json::Value, key: &str) -> Option<&'a serde_json::Value> {
+        span["attributes"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|attribute| attribute["key"] == key)
+            .map(|attribute| &attribute["value"])
+    }
+
+    mod trace_observer {
+        use super::*;
+
+        mod notify {
+            use super::*;
+
+            #[test]
+            fn should_export_workflow_as_trace() {
+                let observer = TraceObserver::new(ExporterStub::default());
+                let mut events = vec![event("workflow1", EventKind::WorkflowStarted)];
+                events.extend(action_events(
+                    "workflow1",
+                    "action1",
+                    Output::new(Status::Changed).add_var("rc", Value::from(0)),
+                ));
+                events.extend(action_events(
+                    "workflow1",
+                    "action2",
+                    Output::new(Status::Failed).add_var("rc", Value::from(2)),
+                ));
+                events.push(event(
+                    "workflow1",
+                    EventKind::WorkflowFinished {
+                        status: Status::Failed,
+                    },
+                ));
+                for event in events.iter() {
+                    observer.notify(event);
+                }
+                let requests = observer.exporter.0.borrow();
+                assert_eq!(requests.len(), 1);
+                let spans = spans(&requests[0]);
+                let names: Vec<&str> = spans
+                    .iter()
+                    .map(|span| span["name"].as_str().unwrap())
+                    .collect();
+                assert_eq!(names, vec!["action1", "action2", "workflow1"]);
+                let root = &spans[2];
+                let trace_id = root["traceId"].as_str().unwrap();
+                assert_eq!(trace_id.len(), 32);
+                assert_eq!(root["parentSpanId"], "");
+                assert_eq!(root["status"]["code"], STATUS_CODE_ERROR);
+                assert_eq!(
+                    attribute(root, "ennio.workflow"),
+                    Some(&json!({ "stringValue": "workflow1" }))
+                );
+                for span in spans[..2].iter() {
+                    assert_eq!(span["traceId"], trace_id);
+                    assert_eq!(span["parentSpanId"], root["spanId"]);
+                    assert_eq!(span["spanId"].as_str().unwrap().len(), 16);
+                }
+                assert_eq!(spans[0]["status"]["code"], STATUS_CODE_OK);
+                assert_eq!(
+                    attribute(&spans[0], "ennio.status"),
+                    Some(&json!({ "stringValue": "changed" }))
+                );
+                assert_eq!(
+                    attribute(&spans[1], "process.exit_code"),
+                    Some(&json!({ "intValue": "2" }))
+                );
+                assert_eq!(spans[1]["status"]["code"], STATUS_CODE_ERROR);
+            }
+
+            #[test]
+            fn should_nest_sub_workflow_spans() {
+                let observer = TraceObserver::new(ExporterStub::default());
+                let mut events = vec![
+                    event("workflow1", EventKind::WorkflowStarted),
+                    event(
+                        "workflow1",
+                        EventKind::ActionStarted {
+                            action: String::from("action1"),
+                        },
+                    ),
+                    event("workflow2", EventKind::WorkflowStarted),
+                ];
+                events.extend(action_events(
+                    "workflow2",
+                    "action2",
+                    Output::new(Status::Unchanged),
+                ));
+                events.extend([
+                    event(
+                        "workflow2",
+                        EventKind::WorkflowFinished {
+                            status: Status::Unchanged,
+                        },
+                    ),
+                    event(
+                        "workflow1",
+                        EventKind::ActionFinished {
+                            action: String::from("action1"),
+                            output: Output::new(Status::Unchanged),
+                        },
+                    ),
+                    event(
+                        "workflow1",
+                        EventKind::WorkflowFinished {
+                            status: Status::Unchanged,
+                        },
+                    ),
+                ]);
+                for (idx, event) in events.iter().enumerate() {
+                    observer.notify(event);
+                    let exported = observer.exporter.0.borrow().len();
+                    assert_eq!(exported, usize::from(idx == events.len() - 1));
+                }
+                let requests = observer.exporter.0.borrow();
+                let spans = spans(&requests[0]);
+                let parent = |idx: usize| spans[idx]["parentSpanId"].clone();
+                let id = |idx: usize| spans[idx]["spanId"].clone();
+                assert_eq!(parent(0), id(1));
+                assert_eq!(parent(1), id(2));
+                assert_eq!(parent(2), id(3));
+                assert_eq!(spans[3]["name"], "workflow1");
+            }
+
+            #[test]
+            fn should_keep_export_errors() {
+                let observer = TraceObserver::new(FileExporter::new(PathBuf::from("/")));
+                observer.notify(&event("workflow1", EventKind::WorkflowStarted));
+                observer.notify(&event(
+                    "workflow1",
+                    EventKind::WorkflowFinished {
+                        status: Status::Changed,
+                    },
+                ));
+                let errors = observer.take_errors();
+                assert_eq!(errors.len(), 1);
+                assert!(matches!(errors[0], TraceError::Writing(_, _)));
+                assert!(observer.take_errors().is_empty());
+            }
+
+            #[test]
+            fn should_ignore_unstarted_span() {
+                let observer = TraceObserver::new(ExporterStub::default());
+                observer.notify(&event(
+                    "workflow1",
+                    EventKind::WorkflowFinished {
+                        status: Status::Changed,
+                    },
+                ));
+                assert!(observer.exporter.0.borrow().is_empty());
+            }
+        }
+    }
+
+    mod file_exporter {
+        use super::*;
+
+        mod export {
+            use super::*;
+
+            #[test]
+            fn should_append_request() {
+                let dir = TempDir::new().unwrap();
+                let path = dir.path().join("traces").join("trace.jsonl");
+                let exporter = FileExporter::new(path.clone());
+                exporter.export(&json!({ "id": 1 })).unwrap();
+                exporter.export(&json!({ "id": 2 })).unwrap();
+                let content = fs::read_to_string(path).unwrap();
+                assert_eq!(content, "{\"id\":1}\n{\"id\":2}\n");
+            }
+        }
+    }
+
+    mod otlp_exporter {
+        use super::*;
+
+        mod export {
+            use super::*;
+
+            fn collector(status: &'static str) -> (String, thread::JoinHandle<(String, String)>) {
+                let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+                let endpoint = format!("http://{}", listener.local_addr().unwrap());
+                let handle = thread::spawn(move || {
+                    let (mut stream, _) = listener.accept().unwrap();
+                    let mut reader = BufReader::new(stream.try_clone().unwrap());
+                    let mut request_line = String::new();
+                    reader.read_line(&mut request_line).unwrap();
+                    let mut len = 0;
+                    loop {
+                        let mut line = String::new();
+                        reader.read_line(&mut line).unwrap();
+                        let line = line.trim_end();
+                        if line.is_empty() {
+                            break;
+                        }
+                        if let Some((name, value)) = line.split_once(':') {
+                            if name.eq_ignore_ascii_case("content-length") {
+                                len = value.trim().parse().unwrap();
+                            }
+                        }
+                    }
+                    let mut body = vec![0; len];
+                    reader.read_exact(&mut body).unwrap();
+                    write!(stream, "HTTP/1.1 {}\r\nContent-Length: 0\r\n\r\n", status).unwrap();
+                    (request_line, String::from_utf8(body).unwrap())
+                });
+                (endpoint, handle)
+            }
+
+            #[test]
+            fn should_post_request() {
+                let (endpoint, handle) = collector("200 OK");
+                let exporter = OtlpExporter::new(endpoint);
+                exporter.export(&json!({ "resourceSpans": [] })).unwrap();
+                let (request_line, body) = handle.join().unwrap();
+                assert_eq!(request_line.trim_end(), "POST /v1/traces HTTP/1.1");
+                assert_eq!(body, r#"{"resourceSpans":[]}"#);
+            }
+
+            #[test]
+            fn should_post_request_to_endpoint_path() {
+                let (endpoint, handle) = collector("200 OK");
+                let exporter = OtlpExporter::new(format!("{}/custom/traces", endpoint));
+                exporter.export(&json!({})).unwrap();
+                let (request_line, _) = handle.join().unwrap();
+                assert_eq!(request_line.trim_end(), "POST /custom/traces HTTP/1.1");
+            }
+
+            #[test]
+            fn should_return_err_if_rejected() {
+                let (endpoint, handle) = collector("400 Bad Request");
+                let exporter = OtlpExporter::new(endpoint.clone());
+                match exporter.export(&json!({})) {
+                    Ok(_) => panic!("should fail"),
+                    Err(TraceError::Sending(url, _)) => {
+                        assert_eq!(url, format!("{}/v1/traces", endpoint))
+                    }
+                    Err(err) => panic!("{}", err),
+                }
+                handle.join().unwrap();
+            }
+
+            #[test]
+            fn should_support_https_endpoints() {
+                let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+                let endpoint = format!("https://{}", listener.local_addr().unwrap());
+                drop(listener);
+                let exporter = OtlpExporter::new(endpoint);
+                match exporter.export(&json!({})) {
+                    Ok(_) => panic!("should fail"),
+                    Err(TraceError::Sending(_, err)) => assert!(!err.contains("TLS"), "{}", err),
+                    Err(err) => panic!("{}", err),
+                }
+            }
+
+            #[test]
+            fn should_return_err_if_timed_out() {
+                let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+                let endpoint = format!("http://{}", listener.local_addr().unwrap());
+                let exporter = OtlpExporter::new(endpoint).with_timeout(Duration::from_millis(100));
+                let start = Instant::now();
+                match exporter.export(&json!({})) {
+                    Ok(_) => panic!("should fail"),
+                    Err(TraceError::Sending(_, _)) => (),
+                    Err(err) => panic!("{}", err),
+                }
+                assert!(start.elapsed() < Duration::from_secs(5));
+                drop(listener);
+            }
+        }
+    }
+
+    mod traces_url {
+        use super::*;
+
+        #[test]
+        fn should_append_traces_path_to_base_endpoint() {
+            assert_eq!(
+                traces_url(String::from("http://localhost:4318")),
+                "http://localhost:4318/v1/traces"
+            );
+            assert_eq!(
+                traces_url(String::from("http://localhost:4318/")),
+                "http://localhost:4318/v1/traces"
+            );
+        }
+
+        #[test]
+        fn should_keep_endpoint_with_path() {
+            assert_eq!(
+                traces_url(String::from("http://localhost:4318/v1/traces")),
+                "http://localhost:4318/v1/traces"
+            );
+        }
+    }
+
+    mod trace_error {
+        use super::*;
+
+        mod to_string {
+            use super::*;
+
+            macro_rules! test {
+                ($name:ident, $err:expr, $expected:expr) => {
+                    #[test]
+                    fn $name() {
+                        assert_eq!($err.to_string(), $expected);
+                    }
+                };
+            }
+
+            test!(
+                writing,
+                TraceError::Writing(PathBuf::from("trace.jsonl"), String::from("denied")),
+                "Unable to write trace.jsonl: denied"
+            );
+            test!(
+                sending,
+                TraceError::Sending(
+                    String::from("http://localhost:4318/v1/traces"),
+                    String::from("refused")
+                ),
+                "Unable to send traces to http://localhost:4318/v1/traces: refused"
+            );
+        }
+    }
+}
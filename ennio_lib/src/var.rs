@@ -1,4 +1,10 @@
-use std::collections::HashMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::{
+    collections::HashMap,
+    fmt::{self, Debug, Display, Formatter},
+};
+
+pub static SECRET_MASK: &str = "***";
 
 #[macro_export]
 macro_rules! array {
@@ -34,13 +40,17 @@ macro_rules! impl_primitive_from_for_value {
     };
 }
 
-#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[derive(Clone, Debug, Default, PartialEq)]
 pub struct Array(Vec<Value>);
 
 impl Array {
     pub fn new() -> Self {
         Self(vec![])
     }
+
+    pub fn items(&self) -> &[Value] {
+        &self.0
+    }
 }
 
 impl_primitive_from_for_array!(bool);
@@ -61,14 +71,110 @@ impl From<Vec<Value>> for Array {
     }
 }
 
-#[derive(Clone, Debug, Eq, PartialEq)]
+#[derive(Clone, PartialEq)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn new(plaintext: String) -> Self {
+        Self(plaintext)
+    }
+
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Debug for Secret {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "Secret({})", SECRET_MASK)
+    }
+}
+
+impl Display for Secret {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", SECRET_MASK)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub enum Value {
+    Null,
     Bool(bool),
     PositiveInt(u64),
     NegativeInt(i64),
+    Float(f64),
     String(String),
     Array(Array),
     Hash(Hash),
+    Secret(Secret),
+}
+
+impl Value {
+    pub fn get(&self, path: &str) -> Option<&Value> {
+        path.split('.').try_fold(self, |val, key| match val {
+            Self::Hash(hash) => hash.get(key),
+            Self::Array(items) => key.parse::<usize>().ok().and_then(|idx| items.0.get(idx)),
+            _ => None,
+        })
+    }
+
+    pub fn expose(&self) -> String {
+        match self {
+            Self::Secret(secret) => secret.expose().into(),
+            Self::Array(_) | Self::Hash(_) => self.exposed_json().to_string(),
+            _ => self.to_string(),
+        }
+    }
+
+    pub fn secrets(&self) -> Vec<&str> {
+        match self {
+            Self::Secret(secret) => vec![secret.expose()],
+            Self::Array(items) => items.0.iter().flat_map(Self::secrets).collect(),
+            Self::Hash(hash) => hash.values().flat_map(Self::secrets).collect(),
+            _ => vec![],
+        }
+    }
+
+    fn exposed_json(&self) -> serde_json::Value {
+        match self {
+            Self::Secret(secret) => serde_json::Value::String(secret.expose().into()),
+            Self::Array(items) => items.0.iter().map(Self::exposed_json).collect(),
+            Self::Hash(hash) => hash
+                .iter()
+                .map(|(key, val)| (key.clone(), val.exposed_json()))
+                .collect(),
+            _ => serde_json::Value::from(self.clone()),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Value {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        serde_json::Value::deserialize(deserializer).map(Self::from)
+    }
+}
+
+impl Serialize for Value {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serde_json::Value::from(self.clone()).serialize(serializer)
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Self::Null => Ok(()),
+            Self::Bool(val) => write!(f, "{}", val),
+            Self::PositiveInt(val) => write!(f, "{}", val),
+            Self::NegativeInt(val) => write!(f, "{}", val),
+            Self::Float(val) => write!(f, "{}", val),
+            Self::String(val) => write!(f, "{}", val),
+            Self::Array(_) | Self::Hash(_) => {
+                write!(f, "{}", serde_json::Value::from(self.clone()))
+            }
+            Self::Secret(secret) => write!(f, "{}", secret),
+        }
+    }
 }
 
 impl_primitive_from_for_value!(bool, Value::Bool, bool);
@@ -82,14 +188,82 @@ impl_primitive_from_for_value!(i32, Value::NegativeInt, i64);
 impl_primitive_from_for_value!(i64, Value::NegativeInt);
 impl_primitive_from_for_value!(String, Value::String);
 
+impl_primitive_from_for_value!(f64, Value::Float);
+
 impl From<&str> for Value {
     fn from(val: &str) -> Self {
         Self::String(val.into())
     }
 }
 
+impl From<Array> for Value {
+    fn from(items: Array) -> Self {
+        Self::Array(items)
+    }
+}
+
+impl From<Hash> for Value {
+    fn from(hash: Hash) -> Self {
+        Self::Hash(hash)
+    }
+}
+
+impl From<serde_json::Value> for Value {
+    fn from(val: serde_json::Value) -> Self {
+        match val {
+            serde_json::Value::Null => Self::Null,
+            serde_json::Value::Bool(val) => Self::Bool(val),
+            serde_json::Value::Number(val) => {
+                if let Some(val) = val.as_u64() {
+                    Self::PositiveInt(val)
+                } else if let Some(val) = val.as_i64() {
+                    Self::NegativeInt(val)
+                } else {
+                    Self::Float(val.as_f64().unwrap_or_default())
+                }
+            }
+            serde_json::Value::String(val) => Self::String(val),
+            serde_json::Value::Array(items) => {
+                Self::Array(Array(items.into_iter().map(Self::from).collect()))
+            }
+            serde_json::Value::Object(map) => Self::Hash(
+                map.into_iter()
+                    .map(|(key, val)| (key, Self::from(val)))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl From<Value> for serde_json::Value {
+    fn from(val: Value) -> Self {
+        match val {
+            Value::Null => Self::Null,
+            Value::Bool(val) => Self::Bool(val),
+            Value::PositiveInt(val) => Self::from(val),
+            Value::NegativeInt(val) => Self::from(val),
+            Value::Float(val) => Self::from(val),
+            Value::String(val) => Self::String(val),
+            Value::Array(items) => Self::Array(items.0.into_iter().map(Self::from).collect()),
+            Value::Hash(hash) => Self::Object(
+                hash.into_iter()
+                    .map(|(key, val)| (key, Self::from(val)))
+                    .collect(),
+            ),
+            Value::Secret(_) => Self::String(SECRET_MASK.into()),
+        }
+    }
+}
+
 pub type Hash = HashMap<String, Value>;
 
+pub fn lookup<'a>(hash: &'a Hash, path: &str) -> Option<&'a Value> {
+    match path.split_once('.') {
+        Some((name, path)) => hash.get(name).and_then(|val| val.get(path)),
+        None => hash.get(path),
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -133,11 +307,161 @@ mod test {
                 assert_eq!(array.0, expected);
             }
         }
+
+        mod items {
+            use super::*;
+
+            #[test]
+            fn should_return_items() {
+                let expected = vec![Value::Bool(true)];
+                let array = Array(expected.clone());
+                assert_eq!(array.items(), expected);
+            }
+        }
+    }
+
+    mod lookup {
+        use super::*;
+
+        #[test]
+        fn should_return_none() {
+            let hash = hash!("foo", true);
+            assert!(lookup(&hash, "bar").is_none());
+            assert!(lookup(&hash, "foo.bar").is_none());
+        }
+
+        #[test]
+        fn should_return_value() {
+            let hash = hash!("foo", true);
+            assert_eq!(lookup(&hash, "foo"), Some(&Value::Bool(true)));
+        }
+
+        #[test]
+        fn should_return_nested_value() {
+            let hash = hash!("foo", hash!("bar", array!("baz", "qux")));
+            assert_eq!(lookup(&hash, "foo.bar.1"), Some(&Value::from("qux")));
+            assert!(lookup(&hash, "foo.bar.2").is_none());
+        }
+    }
+
+    mod secret {
+        use super::*;
+
+        mod debug {
+            use super::*;
+
+            #[test]
+            fn should_mask_plaintext() {
+                let secret = Secret(String::from("password"));
+                assert_eq!(format!("{:?}", secret), "Secret(***)");
+            }
+        }
+
+        mod display {
+            use super::*;
+
+            #[test]
+            fn should_mask_plaintext() {
+                let secret = Secret(String::from("password"));
+                assert_eq!(secret.to_string(), SECRET_MASK);
+            }
+        }
+
+        mod expose {
+            use super::*;
+
+            #[test]
+            fn should_return_plaintext() {
+                let expected = "password";
+                let secret = Secret(expected.into());
+                assert_eq!(secret.expose(), expected);
+            }
+        }
+
+        mod new {
+            use super::*;
+
+            #[test]
+            fn should_return_secret() {
+                let expected = "password";
+                let secret = Secret::new(expected.into());
+                assert_eq!(secret.0, expected);
+            }
+        }
     }
 
     mod value {
         use super::*;
 
+        mod get {
+            use super::*;
+
+            #[test]
+            fn should_return_none_if_not_container() {
+                let val = Value::from("foo");
+                assert!(val.get("foo").is_none());
+            }
+
+            #[test]
+            fn should_return_nested_value() {
+                let val = Value::Hash(hash!("foo", array!(Value::Hash(hash!("bar", 1u8)))));
+                assert_eq!(val.get("foo.0.bar"), Some(&Value::PositiveInt(1)));
+            }
+        }
+
+        mod expose {
+            use super::*;
+
+            #[test]
+            fn should_return_plaintext_if_secret() {
+                let expected = "password";
+                let val = Value::Secret(Secret(expected.into()));
+                assert_eq!(val.expose(), expected);
+            }
+
+            #[test]
+            fn should_return_string() {
+                let val = Value::from(1u8);
+                assert_eq!(val.expose(), "1");
+            }
+
+            #[test]
+            fn should_return_plaintext_of_nested_secrets() {
+                let val = Value::Hash(hash!(
+                    "foo",
+                    array!(Value::Secret(Secret(String::from("password"))))
+                ));
+                assert_eq!(val.expose(), r#"{"foo":["password"]}"#);
+                assert_eq!(val.to_string(), r#"{"foo":["***"]}"#);
+            }
+        }
+
+        mod secrets {
+            use super::*;
+
+            #[test]
+            fn should_return_nested_secrets() {
+                let val = Value::Hash(hash!(
+                    "foo",
+                    Value::Secret(Secret(String::from("password1"))),
+                    "bar",
+                    array!(
+                        Value::from("val"),
+                        Value::Secret(Secret(String::from("password2")))
+                    )
+                ));
+                let mut secrets = val.secrets();
+                secrets.sort();
+                assert_eq!(secrets, vec!["password1", "password2"]);
+            }
+
+            #[test]
+            fn should_return_empty() {
+                let val = Value::from("val");
+                assert!(val.secrets().is_empty());
+            }
+        }
+
         mod from {
             use super::*;
 
@@ -160,6 +484,7 @@ mod test {
             test_primitive!(i16, Value::NegativeInt, -1i16, i64);
             test_primitive!(i32, Value::NegativeInt, -1i32, i64);
             test_primitive!(i64, Value::NegativeInt, -1i64);
+            test_primitive!(f64, Value::Float, 1.5f64);
             test_primitive!(string, Value::String, String::from("val"));
 
             #[test]
@@ -168,6 +493,154 @@ mod test {
                 let val = Value::from(expected);
                 assert_eq!(val, Value::String(expected.into()));
             }
+
+            #[test]
+            fn array() {
+                let expected = array!(Value::Bool(true));
+                let val = Value::from(expected.clone());
+                assert_eq!(val, Value::Array(expected));
+            }
+
+            #[test]
+            fn hash() {
+                let expected = hash!("foo", true);
+                let val = Value::from(expected.clone());
+                assert_eq!(val, Value::Hash(expected));
+            }
+
+            macro_rules! test_json {
+                ($name:ident, $json:expr, $expected:expr) => {
+                    #[test]
+                    fn $name() {
+                        let val = Value::from($json);
+                        assert_eq!(val, $expected);
+                    }
+                };
+            }
+
+            test_json!(json_null, serde_json::Value::Null, Value::Null);
+            test_json!(json_bool, serde_json::json!(true), Value::Bool(true));
+            test_json!(
+                json_positive_int,
+                serde_json::json!(1),
+                Value::PositiveInt(1)
+            );
+            test_json!(
+                json_negative_int,
+                serde_json::json!(-1),
+                Value::NegativeInt(-1)
+            );
+            test_json!(json_float, serde_json::json!(1.5), Value::Float(1.5));
+            test_json!(json_string, serde_json::json!("val"), Value::from("val"));
+            test_json!(
+                json_array,
+                serde_json::json!([true, "val"]),
+                Value::Array(array!(Value::Bool(true), Value::from("val")))
+            );
+            test_json!(
+                json_object,
+                serde_json::json!({"foo": 1}),
+                Value::Hash(hash!("foo", 1u8))
+            );
+        }
+
+        mod deserialize {
+            use super::*;
+
+            #[test]
+            fn should_return_value() {
+                let expected = Value::Hash(hash!("foo", array!(Value::from(1u8))));
+                let val: Value = serde_yaml::from_str("foo: [1]").unwrap();
+                assert_eq!(val, expected);
+            }
+        }
+
+        mod display {
+            use super::*;
+
+            macro_rules! test {
+                ($name:ident, $value:expr, $expected:expr) => {
+                    #[test]
+                    fn $name() {
+                        assert_eq!($value.to_string(), $expected);
+                    }
+                };
+            }
+
+            test!(null, Value::Null, "");
+            test!(bool, Value::Bool(true), "true");
+            test!(positive_int, Value::PositiveInt(1), "1");
+            test!(negative_int, Value::NegativeInt(-1), "-1");
+            test!(float, Value::Float(1.5), "1.5");
+            test!(string, Value::from("val"), "val");
+            test!(
+                array,
+                Value::Array(array!(Value::Bool(true), Value::from("val"))),
+                r#"[true,"val"]"#
+            );
+            test!(hash, Value::Hash(hash!("foo", 1u8)), r#"{"foo":1}"#);
+            test!(
+                secret,
+                Value::Secret(Secret(String::from("password"))),
+                SECRET_MASK
+            );
+        }
+
+        mod serialize {
+            use super::*;
+
+            #[test]
+            fn should_return_json_with_masked_secrets() {
+                let val = Value::Hash(hash!(
+                    "token",
+                    Value::Secret(Secret::new(String::from("password")))
+                ));
+                assert_eq!(
+                    serde_json::to_string(&val).unwrap(),
+                    format!(r#"{{"token":"{}"}}"#, SECRET_MASK)
+                );
+            }
+        }
+    }
+
+    mod json_value {
+        use super::*;
+
+        mod from {
+            use super::*;
+
+            #[test]
+            fn value() {
+                let expected = serde_json::json!({
+                    "null": null,
+                    "bool": true,
+                    "positive_int": 1,
+                    "negative_int": -1,
+                    "float": 1.5,
+                    "string": "val",
+                    "array": [true],
+                    "secret": SECRET_MASK,
+                });
+                let val = Value::Hash(hash!(
+                    "null",
+                    Value::Null,
+                    "bool",
+                    true,
+                    "positive_int",
+                    1u8,
+                    "negative_int",
+                    -1i8,
+                    "float",
+                    1.5,
+                    "string",
+                    "val",
+                    "array",
+                    array!(Value::Bool(true)),
+                    "secret",
+                    Value::Secret(Secret(String::from("password")))
+                ));
+                assert_eq!(serde_json::Value::from(val), expected);
+            }
         }
     }
 }
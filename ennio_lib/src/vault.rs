@@ -0,0 +1,206 @@
+use crate::{
+    secret::{self, SecretError},
+    var::*,
+};
+use argon2::Argon2;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use chacha20poly1305::{
+    aead::{rand_core::RngCore, KeyInit, OsRng},
+    XChaCha20Poly1305,
+};
+use std::{env, path::Path};
+
+pub static VAULT_HEADER: &str = "$ENNIO_VAULT;1.0;ARGON2ID;XCHACHA20POLY1305";
+pub static VAULT_PASSWORD_ENV_VAR: &str = "ENNIO_VAULT_PASSWORD";
+
+const KEY_LEN: usize = 32;
+const LINE_LEN: usize = 80;
+const SALT_LEN: usize = 16;
+
+pub type PassphraseFn<'a> = dyn Fn() -> Result<String, SecretError> + 'a;
+
+pub fn decrypt(passphrase: &str, content: &str) -> Result<String, SecretError> {
+    let mut lines = content.lines();
+    if lines.next().map(str::trim) != Some(VAULT_HEADER) {
+        return Err(SecretError::InvalidVault);
+    }
+    let data: String = lines.map(str::trim).collect();
+    let data = BASE64
+        .decode(data)
+        .map_err(|err| SecretError::Decoding(err.to_string()))?;
+    if data.len() < SALT_LEN {
+        return Err(SecretError::InvalidVault);
+    }
+    let (salt, data) = data.split_at(SALT_LEN);
+    let cipher = cipher(passphrase, salt)?;
+    secret::open(&cipher, data)
+}
+
+pub fn encrypt(passphrase: &str, plaintext: &str) -> Result<String, SecretError> {
+    let mut salt = [0u8; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    let cipher = cipher(passphrase, &salt)?;
+    let mut data = salt.to_vec();
+    data.extend(secret::seal(&cipher, plaintext)?);
+    let data = BASE64.encode(data);
+    let lines: Vec<&str> = data
+        .as_bytes()
+        .chunks(LINE_LEN)
+        .filter_map(|chunk| std::str::from_utf8(chunk).ok())
+        .collect();
+    Ok(format!("{}\n{}\n", VAULT_HEADER, lines.join("\n")))
+}
+
+pub fn load(path: &Path, passphrase: &str) -> Result<Hash, SecretError> {
+    let content = secret::read(path)?;
+    let plaintext = decrypt(passphrase, &content)?;
+    Ok(parse(&plaintext)?
+        .into_iter()
+        .map(|(name, val)| (name, secret(val)))
+        .collect())
+}
+
+pub fn parse(plaintext: &str) -> Result<Hash, SecretError> {
+    if plaintext.trim().is_empty() {
+        return Ok(Hash::new());
+    }
+    serde_yaml::from_str(plaintext).map_err(|err| SecretError::Decoding(err.to_string()))
+}
+
+pub fn passphrase() -> Result<String, SecretError> {
+    env::var(VAULT_PASSWORD_ENV_VAR)
+        .map_err(|_| SecretError::UndefinedEnvVar(VAULT_PASSWORD_ENV_VAR.into()))
+}
+
+fn secret(val: Value) -> Value {
+    match val {
+        Value::String(val) => Value::Secret(Secret::new(val)),
+        Value::Array(array) => {
+            let items: Vec<Value> = array.items().iter().cloned().map(secret).collect();
+            Value::from(Array::from(items))
+        }
+        Value::Hash(hash) => Value::Hash(
+            hash.into_iter()
+                .map(|(name, val)| (name, secret(val)))
+                .collect(),
+        ),
+        val => val,
+    }
+}
+
+fn cipher(passphrase: &str, salt: &[u8]) -> Result<XChaCha20Poly1305, SecretError> {
+    let mut key = [0u8; KEY_LEN];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|err| SecretError::KeyDerivation(err.to_string()))?;
+    Ok(XChaCha20Poly1305::new(&key.into()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::*;
+
+    mod decrypt {
+        use super::*;
+
+        #[test]
+        fn should_return_invalid_vault_if_no_header() {
+            match decrypt("ennio", "foo") {
+                Ok(_) => panic!("should fail"),
+                Err(SecretError::InvalidVault) => {}
+                Err(err) => panic!("{}", err),
+            }
+        }
+
+        #[test]
+        fn should_return_decrypting_err_if_wrong_passphrase() {
+            let content = encrypt("ennio", "token: password").unwrap();
+            match decrypt("wrong", &content) {
+                Ok(_) => panic!("should fail"),
+                Err(SecretError::Decrypting) => {}
+                Err(err) => panic!("{}", err),
+            }
+        }
+
+        #[test]
+        fn should_return_plaintext() {
+            let expected = "token: password\n".repeat(10);
+            let content = encrypt("ennio", &expected).unwrap();
+            assert!(content.starts_with(VAULT_HEADER));
+            assert!(content
+                .lines()
+                .all(|line| line.len() <= LINE_LEN || line == VAULT_HEADER));
+            let plaintext = decrypt("ennio", &content).unwrap();
+            assert_eq!(plaintext, expected);
+        }
+    }
+
+    mod parse {
+        use super::*;
+
+        #[test]
+        fn should_return_vars() {
+            assert_eq!(
+                parse("token: password").unwrap(),
+                hash!("token", "password")
+            );
+        }
+
+        #[test]
+        fn should_return_empty_hash_if_empty() {
+            assert!(parse("\n").unwrap().is_empty());
+        }
+
+        #[test]
+        fn should_return_decoding_err_if_not_a_mapping() {
+            match parse("- token") {
+                Ok(_) => panic!("should fail"),
+                Err(SecretError::Decoding(_)) => {}
+                Err(err) => panic!("{}", err),
+            }
+        }
+    }
+
+    mod load {
+        use super::*;
+
+        #[test]
+        fn should_return_secrets() {
+            let expected = hash!(
+                "vault_token",
+                Value::Secret(Secret::new(String::from("vault_password"))),
+                "vault_port",
+                5432u16
+            );
+            let secrets = load(Path::new("./test/secrets.vault"), "ennio").unwrap();
+            assert_eq!(secrets, expected);
+        }
+
+        #[test]
+        fn should_keep_structure_and_types_of_nested_secrets() {
+            let dir = tempfile::TempDir::new().unwrap();
+            let path = dir.path().join("secrets.vault");
+            let content = encrypt(
+                "ennio",
+                "db:\n  password: pw\n  ports: [5432]\n  tls: true\n  host: null\n",
+            )
+            .unwrap();
+            std::fs::write(&path, content).unwrap();
+            let expected = hash!(
+                "db",
+                hash!(
+                    "password",
+                    Value::Secret(Secret::new(String::from("pw"))),
+                    "ports",
+                    array!(Value::from(5432u16)),
+                    "tls",
+                    true,
+                    "host",
+                    Value::Null
+                )
+            );
+            assert_eq!(load(&path, "ennio").unwrap(), expected);
+        }
+    }
+}
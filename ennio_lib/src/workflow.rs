@@ -1,44 +1,486 @@
-use crate::{action::*, context::*};
-use log::info;
+use crate::{action::*, context::*, event::*, run, var::*};
+use log::{debug, info};
+use std::{collections::HashSet, env, ffi::OsString, time::Instant};
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Selection {
+    tags: Vec<String>,
+    skip_tags: Vec<String>,
+}
+
+impl Selection {
+    pub fn new(tags: Vec<String>, skip_tags: Vec<String>) -> Self {
+        Self { tags, skip_tags }
+    }
+
+    pub fn matches(&self, tags: &[String]) -> bool {
+        let selected = self.tags.is_empty() || self.tags.iter().any(|tag| tags.contains(tag));
+        selected && !self.skip_tags.iter().any(|tag| tags.contains(tag))
+    }
+
+    pub fn skip_tags(&self) -> &[String] {
+        &self.skip_tags
+    }
+
+    pub fn tags(&self) -> &[String] {
+        &self.tags
+    }
+}
+
+pub struct Step {
+    action: Box<dyn Action>,
+    vars: Hash,
+    notify: Vec<String>,
+    tags: Vec<String>,
+    needs: Vec<String>,
+}
+
+impl Step {
+    pub fn new(action: Box<dyn Action>) -> Self {
+        Self {
+            action,
+            vars: Hash::new(),
+            notify: vec![],
+            tags: vec![],
+            needs: vec![],
+        }
+    }
+
+    pub fn action(&self) -> &dyn Action {
+        self.action.as_ref()
+    }
+
+    pub fn needs(&self) -> &[String] {
+        &self.needs
+    }
+
+    pub fn notify(&self) -> &[String] {
+        &self.notify
+    }
+
+    pub fn run(&self, ctx: &Context) -> Output {
+        let action_name = self.action.name();
+        info!("Executing action '{}'", action_name);
+        ctx.notify(EventKind::ActionStarted {
+            action: action_name.into(),
+        });
+        let started_at = run::now();
+        let started = Instant::now();
+        let output = self.action.run(&ctx.scope(&self.vars));
+        let output = output.with_timing(started_at, run::now(), started.elapsed());
+        info!(
+            "Action '{}' terminated with status: {}",
+            action_name,
+            output.status()
+        );
+        ctx.notify(EventKind::ActionFinished {
+            action: action_name.into(),
+            output: output.clone(),
+        });
+        if output.status() == Status::Changed {
+            ctx.notify_handlers(&self.notify);
+        }
+        output
+    }
+
+    pub fn tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    pub fn vars(&self) -> &Hash {
+        &self.vars
+    }
+
+    pub fn with_needs(mut self, needs: Vec<String>) -> Self {
+        self.needs = needs;
+        self
+    }
+
+    pub fn with_notify(mut self, notify: Vec<String>) -> Self {
+        self.notify = notify;
+        self
+    }
+
+    pub fn with_tags(mut self, tags: Vec<String>) -> Self {
+        self.tags = tags;
+        self
+    }
+
+    pub fn with_vars(mut self, vars: Hash) -> Self {
+        self.vars = vars;
+        self
+    }
+}
 
 pub struct Workflow {
     name: String,
-    actions: Vec<Box<dyn Action>>,
+    vars: Hash,
+    secrets: Hash,
+    steps: Vec<Step>,
+    handlers: Vec<Step>,
+    finally: Vec<Step>,
+    selection: Selection,
+    start_at: Option<String>,
+    previous_outputs: Outputs,
+    observers: Observers,
 }
 
 impl Workflow {
     pub fn new(name: String) -> Self {
         Self {
             name,
-            actions: vec![],
+            vars: Hash::new(),
+            secrets: Hash::new(),
+            steps: vec![],
+            handlers: vec![],
+            finally: vec![],
+            selection: Selection::default(),
+            start_at: None,
+            previous_outputs: Outputs::new(),
+            observers: Observers::new(),
         }
     }
 
+    pub fn finally(&self) -> &[Step] {
+        &self.finally
+    }
+
+    pub fn handlers(&self) -> &[Step] {
+        &self.handlers
+    }
+
     pub fn name(&self) -> &str {
         &self.name
     }
 
+    pub fn previous_outputs(&self) -> &Outputs {
+        &self.previous_outputs
+    }
+
+    pub fn resume_point(&self, previous_outputs: &Outputs) -> Option<&str> {
+        self.steps
+            .iter()
+            .map(|step| step.action.name())
+            .find(|action_name| {
+                previous_outputs.get(*action_name).is_none_or(|output| {
+                    matches!(output.status(), Status::Failed | Status::Skipped)
+                })
+            })
+    }
+
     pub fn run(&self) -> Outputs {
-        let mut ctx = Context::new(&self.name);
-        for action in self.actions.iter() {
-            let action_name = action.name();
-            info!("Executing action '{}'", action_name);
-            let output = action.run(&ctx);
-            info!(
-                "Action '{}' terminated with status: {}",
-                action_name,
-                output.status()
-            );
-            ctx.update(action_name, output);
+        self.run_with_inputs(Hash::new())
+    }
+
+    pub fn observers(&self) -> &Observers {
+        &self.observers
+    }
+
+    pub fn run_with_inputs(&self, inputs: Hash) -> Outputs {
+        self.run_observed(inputs, &self.observers)
+    }
+
+    pub fn run_observed(&self, inputs: Hash, observers: &Observers) -> Outputs {
+        let env = env_vars(env::vars_os());
+        let mut vars = self.vars.clone();
+        vars.extend(inputs);
+        let mut ctx = Context::new(&self.name)
+            .with_vars(vars)
+            .with_env(env)
+            .with_secrets(self.secrets.clone())
+            .with_observers(observers.clone());
+        ctx.notify(EventKind::WorkflowStarted);
+        let selected = self.selected_steps();
+        let start_idx = self.start_idx();
+        for (idx, step) in self.steps.iter().enumerate() {
+            let action_name = step.action.name();
+            if idx < start_idx {
+                let output = match self.previous_outputs.get(action_name) {
+                    Some(output) if output.status() != Status::Failed => {
+                        debug!("Reusing previous output of action '{}'", action_name);
+                        output.clone()
+                    }
+                    _ => {
+                        debug!("Action '{}' is before start action", action_name);
+                        Output::new(Status::Skipped)
+                    }
+                };
+                notify_not_run(&ctx, action_name, &output);
+                ctx.update(action_name, output);
+                continue;
+            }
+            if !selected.contains(action_name) {
+                debug!("Action '{}' is not selected", action_name);
+                let output = Output::new(Status::Skipped);
+                notify_not_run(&ctx, action_name, &output);
+                ctx.update(action_name, output);
+                continue;
+            }
+            let output = step.run(&ctx);
+            ctx.update(step.action.name(), output);
+        }
+        for handler in self.handlers.iter() {
+            let handler_name = handler.action.name();
+            let output = if ctx.is_notified(handler_name) {
+                handler.run(&ctx)
+            } else {
+                debug!("Handler '{}' was not notified", handler_name);
+                let output = Output::new(Status::Skipped);
+                notify_not_run(&ctx, handler_name, &output);
+                output
+            };
+            ctx.update(handler_name, output);
         }
+        for step in self.finally.iter() {
+            let output = step.run(&ctx);
+            ctx.update(step.action.name(), output);
+        }
+        let status = Status::aggregate(ctx.outputs().values().map(Output::status));
+        ctx.notify(EventKind::WorkflowFinished { status });
         ctx.take_outputs()
     }
+
+    pub fn secrets(&self) -> &Hash {
+        &self.secrets
+    }
+
+    pub fn selection(&self) -> &Selection {
+        &self.selection
+    }
+
+    pub fn start_at(&self) -> Option<&str> {
+        self.start_at.as_deref()
+    }
+
+    pub fn steps(&self) -> &[Step] {
+        &self.steps
+    }
+
+    pub fn vars(&self) -> &Hash {
+        &self.vars
+    }
+
+    pub fn with_finally(mut self, finally: Vec<Step>) -> Self {
+        self.finally = finally;
+        self
+    }
+
+    pub fn with_handlers(mut self, handlers: Vec<Step>) -> Self {
+        self.handlers = handlers;
+        self
+    }
+
+    pub fn with_observers(mut self, observers: Observers) -> Self {
+        self.observers = observers;
+        self
+    }
+
+    pub fn with_secrets(mut self, secrets: Hash) -> Self {
+        self.secrets = secrets;
+        self
+    }
+
+    pub fn with_selection(mut self, selection: Selection) -> Self {
+        self.selection = selection;
+        self
+    }
+
+    pub fn with_start_at(
+        mut self,
+        action_name: String,
+        previous_outputs: Outputs,
+    ) -> Result<Self, BuildError> {
+        if !self
+            .steps
+            .iter()
+            .any(|step| step.action.name() == action_name)
+        {
+            return Err(BuildError::UnknownStartAction(self.name, action_name));
+        }
+        self.start_at = Some(action_name);
+        self.previous_outputs = previous_outputs;
+        Ok(self)
+    }
+
+    pub fn with_steps(mut self, steps: Vec<Step>) -> Self {
+        self.steps = steps;
+        self
+    }
+
+    pub fn with_vars(mut self, vars: Hash) -> Self {
+        self.vars = vars;
+        self
+    }
+
+    fn selected_steps(&self) -> HashSet<&str> {
+        let mut selected: HashSet<&str> = self
+            .steps
+            .iter()
+            .filter(|step| self.selection.matches(&step.tags))
+            .map(|step| step.action.name())
+            .collect();
+        for step in self.steps.iter().rev() {
+            if selected.contains(step.action.name()) {
+                selected.extend(step.needs.iter().map(String::as_str));
+            }
+        }
+        selected
+    }
+
+    fn start_idx(&self) -> usize {
+        self.start_at
+            .as_ref()
+            .and_then(|start_at| {
+                self.steps
+                    .iter()
+                    .position(|step| step.action.name() == start_at)
+            })
+            .unwrap_or_default()
+    }
+}
+
+fn notify_not_run(ctx: &Context, action_name: &str, output: &Output) {
+    ctx.notify(EventKind::ActionStarted {
+        action: action_name.into(),
+    });
+    ctx.notify(EventKind::ActionFinished {
+        action: action_name.into(),
+        output: output.clone(),
+    });
+}
+
+fn env_vars<I: IntoIterator<Item = (OsString, OsString)>>(vars: I) -> Hash {
+    vars.into_iter()
+        .filter_map(
+            |(name, val)| match (name.into_string(), val.into_string()) {
+                (Ok(name), Ok(val)) => Some((name, val.into())),
+                (name, _) => {
+                    debug!(
+                        "Ignoring environment variable {} which is not valid UTF-8",
+                        name.unwrap_or_else(|name| name.to_string_lossy().into_owned())
+                    );
+                    None
+                }
+            },
+        )
+        .collect()
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::{action::test::*, var::*, *};
+    use crate::{action::test::*, *};
+
+    fn untimed(outputs: &Outputs) -> Outputs {
+        outputs
+            .iter()
+            .map(|(name, output)| {
+                let output = Output::new(output.status()).with_vars(output.vars().clone());
+                (name.clone(), output)
+            })
+            .collect()
+    }
+
+    mod selection {
+        use super::*;
+
+        mod matches {
+            use super::*;
+
+            macro_rules! test {
+                ($name:ident, $tags:expr, $skip_tags:expr, $action_tags:expr, $expected:expr) => {
+                    #[test]
+                    fn $name() {
+                        let to_strings =
+                            |tags: &[&str]| tags.iter().map(|tag| String::from(*tag)).collect();
+                        let selection = Selection::new(to_strings(&$tags), to_strings(&$skip_tags));
+                        let action_tags: Vec<String> = to_strings(&$action_tags);
+                        assert_eq!(selection.matches(&action_tags), $expected);
+                    }
+                };
+            }
+
+            test!(should_match_if_no_tags, [], [], [], true);
+            test!(should_match_tag, ["deploy"], [], ["build", "deploy"], true);
+            test!(should_not_match_untagged, ["deploy"], [], [], false);
+            test!(
+                should_not_match_skip_tag,
+                [],
+                ["slow"],
+                ["build", "slow"],
+                false
+            );
+            test!(
+                should_not_match_tag_and_skip_tag,
+                ["deploy"],
+                ["slow"],
+                ["deploy", "slow"],
+                false
+            );
+        }
+    }
+
+    mod step {
+        use super::*;
+
+        mod new {
+            use super::*;
+
+            #[test]
+            fn should_return_step() {
+                let name = "action1";
+                let step = Step::new(action_stub!(name, |_| Output::new(Status::Changed)));
+                assert_eq!(step.action.name(), name);
+                assert!(step.vars.is_empty());
+            }
+        }
+
+        mod action {
+            use super::*;
+
+            #[test]
+            fn should_return_action() {
+                let name = "action1";
+                let step = Step {
+                    action: action_stub!(name, |_| Output::new(Status::Changed)),
+                    vars: Hash::new(),
+                    notify: vec![],
+                    tags: vec![],
+                    needs: vec![],
+                };
+                assert_eq!(step.action().name(), name);
+            }
+        }
+
+        mod vars {
+            use super::*;
+
+            #[test]
+            fn should_return_vars() {
+                let expected = hash!("foo", true);
+                let step = Step {
+                    action: action_stub!("action1", |_| Output::new(Status::Changed)),
+                    vars: expected.clone(),
+                    notify: vec![],
+                    tags: vec![],
+                    needs: vec![],
+                };
+                assert_eq!(*step.vars(), expected);
+            }
+        }
+
+        mod with_vars {
+            use super::*;
+
+            #[test]
+            fn should_set_vars() {
+                let expected = hash!("foo", true);
+                let step = Step::new(action_stub!("action1", |_| Output::new(Status::Changed)))
+                    .with_vars(expected.clone());
+                assert_eq!(step.vars, expected);
+            }
+        }
+    }
 
     mod workflow {
         use super::*;
@@ -51,7 +493,9 @@ mod test {
                 let name = "workflow1";
                 let workflow = Workflow::new(name.into());
                 assert_eq!(workflow.name, name);
-                assert!(workflow.actions.is_empty());
+                assert!(workflow.vars.is_empty());
+                assert!(workflow.secrets.is_empty());
+                assert!(workflow.steps.is_empty());
             }
         }
 
@@ -63,12 +507,56 @@ mod test {
                 let expected = "workflow1";
                 let workflow = Workflow {
                     name: expected.into(),
-                    actions: vec![],
+                    vars: Hash::new(),
+                    secrets: Hash::new(),
+                    steps: vec![],
+                    handlers: vec![],
+                    finally: vec![],
+                    selection: Selection::default(),
+                    start_at: None,
+                    previous_outputs: Outputs::new(),
+                    observers: Observers::new(),
                 };
                 assert_eq!(workflow.name(), expected);
             }
         }
 
+        mod resume_point {
+            use super::*;
+
+            macro_rules! test {
+                ($name:ident, [$(($action_name:expr, $status:expr)),*], $expected:expr) => {
+                    #[test]
+                    fn $name() {
+                        let workflow = Workflow::new(String::from("workflow1")).with_steps(vec![
+                            Step::new(action_stub!("action1", |_| Output::new(Status::Changed))),
+                            Step::new(action_stub!("action2", |_| Output::new(Status::Changed))),
+                        ]);
+                        let previous_outputs =
+                            Outputs::from([$((String::from($action_name), Output::new($status))),*]);
+                        assert_eq!(workflow.resume_point(&previous_outputs), $expected);
+                    }
+                };
+            }
+
+            test!(
+                succeeded,
+                [("action1", Status::Changed), ("action2", Status::Unchanged)],
+                None
+            );
+            test!(
+                skipped,
+                [("action1", Status::Changed), ("action2", Status::Skipped)],
+                Some("action2")
+            );
+            test!(
+                failed,
+                [("action1", Status::Unchanged), ("action2", Status::Failed)],
+                Some("action2")
+            );
+            test!(missing, [("action2", Status::Changed)], Some("action1"));
+        }
+
         mod run {
             use super::*;
 
@@ -108,7 +596,7 @@ mod test {
                     move |ctx| {
                         let expected =
                             Outputs::from([(action1_name.into(), action1_output.clone())]);
-                        assert_eq!(ctx.outputs().clone(), expected);
+                        assert_eq!(untimed(ctx.outputs()), expected);
                         action2_output.clone()
                     }
                 });
@@ -121,7 +609,7 @@ mod test {
                             (action1_name.into(), action1_output.clone()),
                             (action2_name.into(), action2_output.clone()),
                         ]);
-                        assert_eq!(ctx.outputs().clone(), expected);
+                        assert_eq!(untimed(ctx.outputs()), expected);
                         action3_output.clone()
                     }
                 });
@@ -131,16 +619,453 @@ mod test {
                         (action2_name.into(), action2_output.clone()),
                         (action3_name.into(), action3_output.clone()),
                     ]);
-                    assert_eq!(ctx.outputs().clone(), expected);
+                    assert_eq!(untimed(ctx.outputs()), expected);
                     action4_output.clone()
                 });
                 let workflow = Workflow {
                     name: workflow_name.into(),
-                    actions: vec![action1, action2, action3, action4],
+                    vars: Hash::new(),
+                    secrets: Hash::new(),
+                    steps: vec![
+                        Step::new(action1),
+                        Step::new(action2),
+                        Step::new(action3),
+                        Step::new(action4),
+                    ],
+                    handlers: vec![],
+                    finally: vec![],
+                    selection: Selection::default(),
+                    start_at: None,
+                    previous_outputs: Outputs::new(),
+                    observers: Observers::new(),
+                };
+                let outputs = workflow.run();
+                assert!(outputs.values().all(|output| output.duration().is_some()));
+                assert_eq!(untimed(&outputs), expected);
+            }
+
+            #[test]
+            fn should_scope_vars() {
+                let action1 = action_stub!("action1", |ctx| {
+                    assert!(!ctx.env().is_empty());
+                    assert_eq!(*ctx.vars(), hash!("foo", 2u8, "bar", true));
+                    Output::new(Status::Changed)
+                });
+                let action2 = action_stub!("action2", |ctx| {
+                    assert_eq!(*ctx.vars(), hash!("foo", 1u8, "bar", true));
+                    Output::new(Status::Changed)
+                });
+                let workflow = Workflow {
+                    name: String::from("workflow1"),
+                    vars: hash!("foo", 1u8, "bar", true),
+                    secrets: Hash::new(),
+                    steps: vec![
+                        Step::new(action1).with_vars(hash!("foo", 2u8)),
+                        Step::new(action2),
+                    ],
+                    handlers: vec![],
+                    finally: vec![],
+                    selection: Selection::default(),
+                    start_at: None,
+                    previous_outputs: Outputs::new(),
+                    observers: Observers::new(),
                 };
+                workflow.run();
+            }
+
+            mod selection {
+                use super::*;
+
+                #[test]
+                fn should_skip_unselected_actions() {
+                    let workflow = Workflow::new(String::from("workflow1"))
+                        .with_steps(vec![
+                            Step::new(action_stub!("build", |_| Output::new(Status::Changed)))
+                                .with_tags(vec![String::from("build")]),
+                            Step::new(action_stub!("lint", |_| panic!("should not be run")))
+                                .with_tags(vec![String::from("build"), String::from("slow")]),
+                            Step::new(action_stub!("test", |_| panic!("should not be run"))),
+                            Step::new(action_stub!("deploy", |_| Output::new(Status::Changed)))
+                                .with_tags(vec![String::from("deploy")])
+                                .with_needs(vec![String::from("build")]),
+                        ])
+                        .with_selection(Selection::new(
+                            vec![String::from("deploy")],
+                            vec![String::from("slow")],
+                        ));
+                    let outputs = workflow.run();
+                    assert_eq!(outputs["build"].status(), Status::Changed);
+                    assert_eq!(outputs["lint"].status(), Status::Skipped);
+                    assert_eq!(outputs["test"].status(), Status::Skipped);
+                    assert_eq!(outputs["deploy"].status(), Status::Changed);
+                }
+            }
+
+            mod finally {
+                use super::*;
+
+                #[test]
+                fn should_run_finally_with_workflow_status() {
+                    let workflow = Workflow::new(String::from("workflow1"))
+                        .with_steps(vec![
+                            Step::new(action_stub!("action1", |_| Output::new(Status::Changed))),
+                            Step::new(action_stub!("action2", |_| Output::new(Status::Failed))),
+                        ])
+                        .with_finally(vec![Step::new(action_stub!("cleanup", |ctx| {
+                            assert_eq!(ctx.value("workflow.status").unwrap(), &"failed".into());
+                            assert_eq!(
+                                ctx.value("workflow.failed_action").unwrap(),
+                                &"action2".into()
+                            );
+                            Output::new(Status::Changed)
+                        }))]);
+                    let outputs = workflow.run();
+                    assert_eq!(outputs["cleanup"].status(), Status::Changed);
+                }
+            }
+
+            mod handlers {
+                use super::*;
+
+                #[test]
+                fn should_run_notified_handlers_once() {
+                    let workflow = Workflow::new(String::from("workflow1"))
+                        .with_steps(vec![
+                            Step::new(action_stub!("action1", |_| Output::new(Status::Changed)))
+                                .with_notify(vec![String::from("restart")]),
+                            Step::new(action_stub!("action2", |_| Output::new(Status::Changed)))
+                                .with_notify(vec![String::from("restart")]),
+                            Step::new(action_stub!("action3", |_| Output::new(Status::Unchanged)))
+                                .with_notify(vec![String::from("reload")]),
+                        ])
+                        .with_handlers(vec![
+                            Step::new(action_stub!("reload", |_| panic!("should not be run"))),
+                            Step::new(action_stub!("restart", |ctx| {
+                                assert!(ctx.output("action3").is_some());
+                                Output::new(Status::Changed)
+                            })),
+                        ]);
+                    let outputs = workflow.run();
+                    assert_eq!(outputs.len(), 5);
+                    assert_eq!(outputs["restart"].status(), Status::Changed);
+                    assert_eq!(outputs["reload"].status(), Status::Skipped);
+                }
+
+                #[test]
+                fn should_run_handlers_notified_by_nested_actions() {
+                    let block = block::BlockAction::new(
+                        String::from("action1"),
+                        vec![
+                            Step::new(action_stub!("nested1", |_| Output::new(Status::Changed)))
+                                .with_notify(vec![String::from("restart")]),
+                        ],
+                    );
+                    let workflow = Workflow::new(String::from("workflow1"))
+                        .with_steps(vec![Step::new(Box::new(block))])
+                        .with_handlers(vec![Step::new(action_stub!("restart", |_| Output::new(
+                            Status::Changed
+                        )))]);
+                    let outputs = workflow.run();
+                    assert_eq!(outputs["restart"].status(), Status::Changed);
+                }
+            }
+
+            mod start_at {
+                use super::*;
+
+                #[test]
+                fn should_reuse_previous_outputs() {
+                    let previous_outputs = Outputs::from([
+                        (
+                            String::from("action1"),
+                            Output::new(Status::Changed).add_var("stdout", "foo".into()),
+                        ),
+                        (String::from("action3"), Output::new(Status::Failed)),
+                    ]);
+                    let workflow = Workflow::new(String::from("workflow1"))
+                        .with_steps(vec![
+                            Step::new(action_stub!("action1", |_| panic!("should not be run")))
+                                .with_notify(vec![String::from("restart")]),
+                            Step::new(action_stub!("action2", |_| panic!("should not be run"))),
+                            Step::new(action_stub!("action3", |ctx| {
+                                assert_eq!(ctx.value("action1.stdout").unwrap(), &"foo".into());
+                                Output::new(Status::Unchanged)
+                            })),
+                        ])
+                        .with_handlers(vec![Step::new(action_stub!("restart", |_| panic!(
+                            "should not be run"
+                        )))])
+                        .with_start_at(String::from("action3"), previous_outputs)
+                        .unwrap();
+                    let outputs = workflow.run();
+                    assert_eq!(outputs["action1"].status(), Status::Changed);
+                    assert_eq!(outputs["action2"].status(), Status::Skipped);
+                    assert_eq!(outputs["action3"].status(), Status::Unchanged);
+                }
+            }
+        }
+
+        mod run_observed {
+            use super::*;
+            use crate::event::test::*;
+            use std::rc::Rc;
+
+            #[test]
+            fn should_notify_events() {
+                let observer = Rc::new(ObserverStub::default());
+                let workflow = Workflow::new(String::from("workflow1"))
+                    .with_steps(vec![
+                        Step::new(action_stub!("action1", |_| Output::new(Status::Changed))),
+                        Step::new(action_stub!("action2", |_| Output::new(Status::Failed))),
+                    ])
+                    .with_observers(Observers::new().with(observer.clone()));
                 let outputs = workflow.run();
-                assert_eq!(outputs, expected);
+                let expected = vec![
+                    EventKind::WorkflowStarted,
+                    EventKind::ActionStarted {
+                        action: String::from("action1"),
+                    },
+                    EventKind::ActionFinished {
+                        action: String::from("action1"),
+                        output: outputs["action1"].clone(),
+                    },
+                    EventKind::ActionStarted {
+                        action: String::from("action2"),
+                    },
+                    EventKind::ActionFinished {
+                        action: String::from("action2"),
+                        output: outputs["action2"].clone(),
+                    },
+                    EventKind::WorkflowFinished {
+                        status: Status::Failed,
+                    },
+                ];
+                assert_eq!(observer.kinds(), expected);
+                assert!(observer
+                    .events()
+                    .iter()
+                    .all(|event| event.workflow() == "workflow1"));
             }
+
+            #[test]
+            fn should_notify_skipped_actions() {
+                let observer = Rc::new(ObserverStub::default());
+                let workflow = Workflow::new(String::from("workflow1"))
+                    .with_steps(vec![
+                        Step::new(action_stub!("action1", |_| panic!("should not be run"))),
+                        Step::new(action_stub!("action2", |_| panic!("should not be run")))
+                            .with_tags(vec![String::from("slow")]),
+                        Step::new(action_stub!("action3", |_| Output::new(Status::Unchanged))),
+                    ])
+                    .with_handlers(vec![Step::new(action_stub!("handler1", |_| panic!(
+                        "should not be run"
+                    )))])
+                    .with_selection(Selection::new(vec![], vec![String::from("slow")]))
+                    .with_start_at(String::from("action2"), Outputs::new())
+                    .unwrap()
+                    .with_observers(Observers::new().with(observer.clone()));
+                let outputs = workflow.run();
+                let skipped = |action: &str| {
+                    [
+                        EventKind::ActionStarted {
+                            action: action.into(),
+                        },
+                        EventKind::ActionFinished {
+                            action: action.into(),
+                            output: Output::new(Status::Skipped),
+                        },
+                    ]
+                };
+                let mut expected = vec![EventKind::WorkflowStarted];
+                expected.extend(skipped("action1"));
+                expected.extend(skipped("action2"));
+                expected.extend([
+                    EventKind::ActionStarted {
+                        action: String::from("action3"),
+                    },
+                    EventKind::ActionFinished {
+                        action: String::from("action3"),
+                        output: outputs["action3"].clone(),
+                    },
+                ]);
+                expected.extend(skipped("handler1"));
+                expected.push(EventKind::WorkflowFinished {
+                    status: Status::Unchanged,
+                });
+                assert_eq!(observer.kinds(), expected);
+                assert_eq!(outputs["handler1"].status(), Status::Skipped);
+            }
+        }
+
+        mod run_with_inputs {
+            use super::*;
+
+            #[test]
+            fn should_override_vars_with_inputs() {
+                let action1 = action_stub!("action1", |ctx| {
+                    assert_eq!(*ctx.vars(), hash!("foo", 2u8, "bar", true));
+                    Output::new(Status::Changed)
+                });
+                let workflow = Workflow {
+                    name: String::from("workflow1"),
+                    vars: hash!("foo", 1u8, "bar", true),
+                    secrets: Hash::new(),
+                    steps: vec![Step::new(action1)],
+                    handlers: vec![],
+                    finally: vec![],
+                    selection: Selection::default(),
+                    start_at: None,
+                    previous_outputs: Outputs::new(),
+                    observers: Observers::new(),
+                };
+                let outputs = workflow.run_with_inputs(hash!("foo", 2u8));
+                assert_eq!(outputs["action1"].status(), Status::Changed);
+            }
+        }
+
+        mod secrets {
+            use super::*;
+
+            #[test]
+            fn should_return_secrets() {
+                let expected = hash!("token", Value::Secret(Secret::new("password".into())));
+                let workflow =
+                    Workflow::new(String::from("workflow1")).with_secrets(expected.clone());
+                assert_eq!(*workflow.secrets(), expected);
+            }
+        }
+
+        mod steps {
+            use super::*;
+
+            #[test]
+            fn should_return_steps() {
+                let name = "action1";
+                let workflow = Workflow {
+                    name: String::from("workflow1"),
+                    vars: Hash::new(),
+                    secrets: Hash::new(),
+                    steps: vec![Step::new(action_stub!(name, |_| Output::new(
+                        Status::Changed
+                    )))],
+                    handlers: vec![],
+                    finally: vec![],
+                    selection: Selection::default(),
+                    start_at: None,
+                    previous_outputs: Outputs::new(),
+                    observers: Observers::new(),
+                };
+                let steps = workflow.steps();
+                assert_eq!(steps.len(), 1);
+                assert_eq!(steps[0].action().name(), name);
+            }
+        }
+
+        mod vars {
+            use super::*;
+
+            #[test]
+            fn should_return_vars() {
+                let expected = hash!("foo", true);
+                let workflow = Workflow {
+                    name: String::from("workflow1"),
+                    vars: expected.clone(),
+                    secrets: Hash::new(),
+                    steps: vec![],
+                    handlers: vec![],
+                    finally: vec![],
+                    selection: Selection::default(),
+                    start_at: None,
+                    previous_outputs: Outputs::new(),
+                    observers: Observers::new(),
+                };
+                assert_eq!(*workflow.vars(), expected);
+            }
+        }
+
+        mod with_secrets {
+            use super::*;
+
+            #[test]
+            fn should_set_secrets() {
+                let expected = hash!("token", Value::Secret(Secret::new("password".into())));
+                let workflow =
+                    Workflow::new(String::from("workflow1")).with_secrets(expected.clone());
+                assert_eq!(workflow.secrets, expected);
+            }
+        }
+
+        mod with_start_at {
+            use super::*;
+
+            #[test]
+            fn should_set_start_at() {
+                let previous_outputs =
+                    Outputs::from([(String::from("action1"), Output::new(Status::Changed))]);
+                let workflow = Workflow::new(String::from("workflow1"))
+                    .with_steps(vec![Step::new(action_stub!("action1", |_| Output::new(
+                        Status::Changed
+                    )))])
+                    .with_start_at(String::from("action1"), previous_outputs.clone())
+                    .unwrap();
+                assert_eq!(workflow.start_at(), Some("action1"));
+                assert_eq!(*workflow.previous_outputs(), previous_outputs);
+            }
+
+            #[test]
+            fn should_return_err_if_unknown_action() {
+                let workflow =
+                    Workflow::new(String::from("workflow1")).with_steps(vec![Step::new(
+                        action_stub!("action1", |_| Output::new(Status::Changed)),
+                    )]);
+                match workflow.with_start_at(String::from("action2"), Outputs::new()) {
+                    Ok(_) => panic!("should fail"),
+                    Err(BuildError::UnknownStartAction(workflow_name, action_name)) => {
+                        assert_eq!(workflow_name, "workflow1");
+                        assert_eq!(action_name, "action2");
+                    }
+                    Err(err) => panic!("{}", err),
+                }
+            }
+        }
+
+        mod with_steps {
+            use super::*;
+
+            #[test]
+            fn should_set_steps() {
+                let workflow =
+                    Workflow::new(String::from("workflow1")).with_steps(vec![Step::new(
+                        action_stub!("action1", |_| Output::new(Status::Changed)),
+                    )]);
+                assert_eq!(workflow.steps.len(), 1);
+            }
+        }
+
+        mod with_vars {
+            use super::*;
+
+            #[test]
+            fn should_set_vars() {
+                let expected = hash!("foo", true);
+                let workflow = Workflow::new(String::from("workflow1")).with_vars(expected.clone());
+                assert_eq!(workflow.vars, expected);
+            }
+        }
+    }
+
+    mod env_vars {
+        use super::*;
+        use std::os::unix::ffi::OsStringExt;
+
+        #[test]
+        fn should_ignore_non_utf8_vars() {
+            let vars = vec![
+                (OsString::from("FOO"), OsString::from("foo")),
+                (OsString::from("BAD"), OsString::from_vec(vec![0xff])),
+                (OsString::from_vec(vec![0xff]), OsString::from("bar")),
+            ];
+            assert_eq!(env_vars(vars), hash!("FOO", "foo"));
         }
     }
 }